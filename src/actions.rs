@@ -1,6 +1,8 @@
+mod chain;
 mod deletion;
 #[cfg(feature = "german")]
 mod german;
+mod locale;
 mod lower;
 mod normalization;
 /// Replacing inputs.
@@ -14,12 +16,14 @@ mod upper;
 use std::error::Error;
 use std::fmt;
 
+pub use chain::Actions;
 pub use deletion::Deletion;
 #[cfg(feature = "german")]
 pub use german::German;
+pub use locale::{Locale, LocaleError};
 pub use lower::Lower;
 pub use normalization::Normalization;
-pub use replace::{Replacement, ReplacementError};
+pub use replace::{CaptureReplacements, Replacement, ReplacementError};
 pub use style::Style;
 #[cfg(feature = "symbols")]
 pub use symbols::{inversion::Symbols as SymbolsInversion, Symbols};
@@ -27,23 +31,46 @@ pub use titlecase::Titlecase;
 pub use upper::Upper;
 
 use crate::scoping::scope::ScopeContext;
+#[cfg(doc)]
+use crate::scoping::view::ScopedView;
 
 /// An action in the processing pipeline.
 ///
 /// Actions are the core of the text processing pipeline and can be applied in any
 /// order, [any number of times each](https://en.wikipedia.org/wiki/Idempotence) (more
 /// than once being wasted work, though).
+///
+/// Implementors provide [`Action::act`]. Actions which can fail (calling out to
+/// external programs etc.) additionally override [`Action::try_act`], and have
+/// [`Action::act`] panic on failure.
 pub trait Action: Send + Sync {
     /// Apply this action to the given input.
     ///
     /// This is infallible: it cannot fail in the sense of [`Result`]. It can only
     /// return incorrect results, which would be bugs (please report).
+    ///
+    /// # Panics
+    ///
+    /// Fallible actions panic here if they fail. They are hence best applied through
+    /// fallible means, like [`ScopedView::map_with_context`].
     fn act(&self, input: &str) -> String;
 
+    /// Apply this action to the given input, which might fail.
+    ///
+    /// By default, calls [`Action::act`] and never fails.
+    ///
+    /// # Errors
+    ///
+    /// Implementation-specific, see [`ActionError::Failed`].
+    fn try_act(&self, input: &str) -> Result<String, ActionError> {
+        Ok(self.act(input))
+    }
+
     /// Acts taking into account additional context.
     ///
-    /// By default, the context is ignored and [`Action::act`] is called. Implementors
-    /// which need and know how to handle additional context can overwrite this method.
+    /// By default, the context is ignored and [`Action::try_act`] is called.
+    /// Implementors which need and know how to handle additional context can overwrite
+    /// this method.
     ///
     /// # Errors
     ///
@@ -55,7 +82,7 @@ pub trait Action: Send + Sync {
         context: &ScopeContext<'_>,
     ) -> Result<String, ActionError> {
         let _ = context; // Mark variable as used
-        Ok(self.act(input))
+        self.try_act(input)
     }
 }
 
@@ -64,6 +91,9 @@ pub trait Action: Send + Sync {
 pub enum ActionError {
     /// Produced if [`Replacement`] fails.
     ReplacementError(ReplacementError),
+    /// Produced by fallible actions (see [`Action::try_act`]), with a description of
+    /// what went wrong.
+    Failed(String),
 }
 
 impl fmt::Display for ActionError {
@@ -72,6 +102,7 @@ impl fmt::Display for ActionError {
             Self::ReplacementError(re) => {
                 write!(f, "Action failed in replacement: {re}")
             }
+            Self::Failed(reason) => write!(f, "Action failed: {reason}"),
         }
     }
 }
@@ -95,6 +126,10 @@ impl Action for Box<dyn Action> {
         self.as_ref().act(input)
     }
 
+    fn try_act(&self, input: &str) -> Result<String, ActionError> {
+        self.as_ref().try_act(input)
+    }
+
     fn act_with_context(
         &self,
         input: &str,
@@ -0,0 +1,163 @@
+use std::fmt;
+
+use super::{Action, ActionError};
+use crate::scoping::scope::ScopeContext;
+#[cfg(doc)]
+use crate::scoping::view::ScopedView;
+
+/// A sequence of [`Action`]s, fused into a single one which applies each in order.
+///
+/// Mapping a [`ScopedView`] with one action after another walks the view once per
+/// action, storing each result in it (and comparing it to the previous one) before the
+/// next action gets to run. Mapping it with all actions fused instead walks the view
+/// once, passing each scope through all actions in one go and only storing the final
+/// result. Each action still returns a fresh [`String`] though: intermediate results
+/// are produced as before, but dropped right away instead of being stored.
+///
+/// ## Example
+///
+/// ```rust
+/// use srgn::actions::{Action, Actions, Replacement, Upper};
+///
+/// let actions: Vec<Box<dyn Action>> = vec![
+///     Box::new(Replacement::try_from(String::from("world")).unwrap()),
+///     Box::new(Upper::default()),
+/// ];
+///
+/// assert_eq!(Actions::new(&actions).act("hello"), "WORLD");
+/// ```
+pub struct Actions<'a, A> {
+    actions: &'a [A],
+}
+
+impl<A> fmt::Debug for Actions<'_, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Actions")
+            .field("actions", &self.actions.len())
+            .finish()
+    }
+}
+
+impl<A> Clone for Actions<'_, A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<A> Copy for Actions<'_, A> {}
+
+impl<'a, A: Action> Actions<'a, A> {
+    /// Fuse the given `actions`, to be applied in order. Without any, input is
+    /// returned unchanged.
+    #[must_use]
+    pub const fn new(actions: &'a [A]) -> Self {
+        Self { actions }
+    }
+
+    /// Passes `input` through all actions in order, each applied using `act`.
+    fn fold(
+        self,
+        input: &str,
+        mut act: impl FnMut(&A, &str) -> Result<String, ActionError>,
+    ) -> Result<String, ActionError> {
+        let Some((first, rest)) = self.actions.split_first() else {
+            return Ok(input.to_owned());
+        };
+
+        let mut res = act(first, input)?;
+        for action in rest {
+            res = act(action, &res)?;
+        }
+
+        Ok(res)
+    }
+}
+
+impl<'a, A: Action> From<&'a [A]> for Actions<'a, A> {
+    fn from(actions: &'a [A]) -> Self {
+        Self::new(actions)
+    }
+}
+
+impl<A: Action> Action for Actions<'_, A> {
+    fn act(&self, input: &str) -> String {
+        self.actions
+            .iter()
+            .fold(input.to_owned(), |res, action| action.act(&res))
+    }
+
+    fn try_act(&self, input: &str) -> Result<String, ActionError> {
+        self.fold(input, A::try_act)
+    }
+
+    fn act_with_context(
+        &self,
+        input: &str,
+        context: &ScopeContext<'_>,
+    ) -> Result<String, ActionError> {
+        self.fold(input, |action, input| {
+            action.act_with_context(input, context)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::actions::{Deletion, Lower, Replacement, Upper};
+    use crate::scoping::regex::CaptureGroup;
+
+    fn action(action: impl Action + 'static) -> Box<dyn Action> {
+        Box::new(action)
+    }
+
+    #[rstest]
+    #[case(vec![], "Hello")]
+    #[case(vec![action(Upper::default())], "HELLO")]
+    #[case(vec![action(Upper::default()), action(|s: &str| format!("{s}!"))], "HELLO!")]
+    #[case(vec![action(Lower::default()), action(Upper::default())], "HELLO")] // In order
+    #[case(vec![action(Upper::default()), action(Lower::default())], "hello")] // In order
+    #[case(vec![action(Deletion::default()), action(|s: &str| format!("{s}!"))], "!")]
+    fn test_actions(#[case] actions: Vec<Box<dyn Action>>, #[case] expected: &str) {
+        assert_eq!(Actions::new(&actions).act("Hello"), expected);
+    }
+
+    #[test]
+    fn test_actions_with_context() {
+        let actions = [Replacement::try_from(String::from("$1$1")).unwrap()];
+        let context = ScopeContext::CaptureGroups([(CaptureGroup::Numbered(1), "x")].into());
+
+        assert_eq!(
+            Actions::new(&actions).act_with_context("a", &context),
+            Ok(String::from("xx"))
+        );
+    }
+
+    #[test]
+    fn test_actions_failing() {
+        struct Failing;
+
+        impl Action for Failing {
+            fn act(&self, input: &str) -> String {
+                self.try_act(input).unwrap()
+            }
+
+            fn try_act(&self, _input: &str) -> Result<String, ActionError> {
+                Err(ActionError::Failed(String::from("nope")))
+            }
+        }
+
+        let actions: [Box<dyn Action>; 3] = [
+            action(Upper::default()),
+            action(Failing),
+            action(Lower::default()),
+        ];
+
+        assert_eq!(
+            Actions::new(&actions).try_act("a"),
+            Err(ActionError::Failed(String::from("nope")))
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use unicode_normalization::char::canonical_combining_class;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_titlecase::TitleCase;
+
+#[cfg(doc)]
+use super::{Lower, Titlecase, Upper};
+
+/// Combining dot above, as in `i̇`.
+const DOT_ABOVE: char = '\u{307}';
+
+/// Canonical combining class of marks placed above their base, like accents.
+const ABOVE: u8 = 230;
+
+/// Language-specific rules for changing case, as used by [`Upper`], [`Lower`] and
+/// [`Titlecase`].
+///
+/// Parses from language tags like `tr` or `nl-BE` (only the language itself counts).
+///
+/// ## Example
+///
+/// ```rust
+/// use srgn::actions::{Action, Locale, Upper};
+///
+/// let locale: Locale = "tr".parse().unwrap();
+/// assert_eq!(Upper::new(locale).act("istanbul"), "İSTANBUL");
+/// assert_eq!(Upper::default().act("istanbul"), "ISTANBUL");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    /// Language-neutral rules, as per Unicode's default case conversion.
+    #[default]
+    Neutral,
+    /// Turkish and Azerbaijani: dotted and dotless `i` are distinct letters, cased as
+    /// `i`/`İ` and `ı`/`I`.
+    Turkish,
+    /// Lithuanian: lowercase `i` and `j` keep their dot when accented, as in `i̇́`.
+    Lithuanian,
+    /// Dutch: the digraph `ij` is titlecased as a whole, as in `IJsland`.
+    Dutch,
+}
+
+/// An error parsing a [`Locale`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocaleError(String);
+
+impl fmt::Display for LocaleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unsupported locale '{}' (expected one of: und, tr, az, lt, nl)",
+            self.0
+        )
+    }
+}
+
+impl Error for LocaleError {}
+
+impl FromStr for Locale {
+    type Err = LocaleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let language = s.split(['-', '_']).next().unwrap_or_default();
+
+        match language.to_ascii_lowercase().as_str() {
+            "und" => Ok(Self::Neutral),
+            "tr" | "az" => Ok(Self::Turkish),
+            "lt" => Ok(Self::Lithuanian),
+            "nl" => Ok(Self::Dutch),
+            _ => Err(LocaleError(s.to_owned())),
+        }
+    }
+}
+
+impl Locale {
+    /// Uppercases `input`.
+    pub(super) fn upper(self, input: &str) -> String {
+        match self {
+            Self::Turkish => input.replace('i', "İ").to_uppercase(),
+            Self::Lithuanian => drop_dots_above_soft_dotted(input).to_uppercase(),
+            Self::Neutral | Self::Dutch => input.to_uppercase(),
+        }
+    }
+
+    /// Lowercases `input`.
+    pub(super) fn lower(self, input: &str) -> String {
+        match self {
+            Self::Turkish => input
+                .replace('İ', "i")
+                .replace(&format!("I{DOT_ABOVE}"), "i")
+                .replace('I', "ı")
+                .to_lowercase(),
+            Self::Lithuanian => add_dots_above_soft_dotted(input).to_lowercase(),
+            Self::Neutral | Self::Dutch => input.to_lowercase(),
+        }
+    }
+
+    /// Titlecases `input`: the first grapheme of each word is titlecased, the rest is
+    /// left alone.
+    pub(super) fn titlecase(self, input: &str) -> String {
+        let mut result = String::with_capacity(input.len());
+
+        for word in input.split_word_bounds() {
+            let mut graphemes = word.graphemes(true);
+            let first = graphemes.next().unwrap_or_default();
+            let rest = graphemes.as_str();
+
+            if !first.chars().next().is_some_and(char::is_alphabetic) {
+                result.push_str(word);
+                continue;
+            }
+
+            let mut chars = first.chars();
+            let base = chars.next().expect("checked to be non-empty");
+            match self {
+                Self::Turkish => result.extend(base.to_titlecase_tr_or_az()),
+                Self::Neutral | Self::Lithuanian | Self::Dutch => {
+                    result.extend(base.to_titlecase());
+                }
+            }
+
+            let marks = chars.as_str();
+            match self {
+                Self::Lithuanian if is_soft_dotted(base) => {
+                    result.extend(marks.chars().filter(|&c| c != DOT_ABOVE));
+                }
+                _ => result.push_str(marks),
+            }
+
+            match (self, base, rest.strip_prefix(['j', 'J'])) {
+                (Self::Dutch, 'i' | 'I', Some(rest)) if marks.is_empty() => {
+                    result.push('J');
+                    result.push_str(rest);
+                }
+                _ => result.push_str(rest),
+            }
+        }
+
+        result
+    }
+}
+
+/// Whether `c` loses its dot when accented or uppercased, like `i` and `j`.
+const fn is_soft_dotted(c: char) -> bool {
+    matches!(c, 'i' | 'j' | 'į' | 'ɨ' | 'ʝ')
+}
+
+/// Drops any dot above following a soft-dotted character, before other marks above.
+fn drop_dots_above_soft_dotted(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+    let mut after_soft_dotted = false;
+
+    for c in input.chars() {
+        match canonical_combining_class(c) {
+            0 => after_soft_dotted = is_soft_dotted(c),
+            ABOVE if c == DOT_ABOVE && after_soft_dotted => continue,
+            ABOVE => after_soft_dotted = false,
+            _ => {}
+        }
+
+        result.push(c);
+    }
+
+    result
+}
+
+/// Inserts a dot above after uppercase `I`, `J` and `Į` followed by other marks above,
+/// and decomposes precomposed accented `I`s likewise, so that lowercasing keeps the
+/// dot.
+fn add_dots_above_soft_dotted(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+
+    for (i, c) in input.char_indices() {
+        match c {
+            'Ì' => result.extend(['I', DOT_ABOVE, '\u{300}']),
+            'Í' => result.extend(['I', DOT_ABOVE, '\u{301}']),
+            'Ĩ' => result.extend(['I', DOT_ABOVE, '\u{303}']),
+            'I' | 'J' | 'Į' if more_above(&input[i + c.len_utf8()..]) => {
+                result.extend([c, DOT_ABOVE]);
+            }
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+/// Whether `rest` starts with marks of which at least one is placed above.
+fn more_above(rest: &str) -> bool {
+    rest.chars()
+        .map(canonical_combining_class)
+        .take_while(|&class| class != 0)
+        .any(|class| class == ABOVE)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("tr", Ok(Locale::Turkish))]
+    #[case("az", Ok(Locale::Turkish))]
+    #[case("tr-TR", Ok(Locale::Turkish))]
+    #[case("TR_tr", Ok(Locale::Turkish))]
+    #[case("lt", Ok(Locale::Lithuanian))]
+    #[case("nl-BE", Ok(Locale::Dutch))]
+    #[case("und", Ok(Locale::Neutral))]
+    #[case("", Err(LocaleError(String::new())))]
+    #[case("xx", Err(LocaleError("xx".to_owned())))]
+    fn test_parse(#[case] input: &str, #[case] expected: Result<Locale, LocaleError>) {
+        assert_eq!(input.parse(), expected);
+    }
+
+    #[rstest]
+    #[case(Locale::Neutral, "istanbul ılık", "ISTANBUL ILIK")]
+    #[case(Locale::Turkish, "istanbul ılık", "İSTANBUL ILIK")]
+    #[case(Locale::Lithuanian, "i\u{307}\u{301}", "I\u{301}")]
+    #[case(Locale::Lithuanian, "j\u{307}", "J")]
+    #[case(Locale::Lithuanian, "i\u{328}\u{307}", "I\u{328}")] // Other marks in between
+    #[case(Locale::Lithuanian, "a\u{307}", "A\u{307}")] // Not soft-dotted
+    #[case(Locale::Neutral, "i\u{307}", "I\u{307}")]
+    #[case(Locale::Dutch, "ijsland", "IJSLAND")]
+    fn test_upper(#[case] locale: Locale, #[case] input: &str, #[case] expected: &str) {
+        assert_eq!(locale.upper(input), expected);
+    }
+
+    #[rstest]
+    #[case(Locale::Neutral, "İSTANBUL ILIK", "i\u{307}stanbul ilik")]
+    #[case(Locale::Turkish, "İSTANBUL ILIK", "istanbul ılık")]
+    #[case(Locale::Turkish, "I\u{307}", "i")]
+    #[case(Locale::Lithuanian, "Í", "i\u{307}\u{301}")]
+    #[case(Locale::Lithuanian, "I\u{301}", "i\u{307}\u{301}")]
+    #[case(Locale::Lithuanian, "J\u{303}", "j\u{307}\u{303}")]
+    #[case(Locale::Lithuanian, "I\u{328}", "i\u{328}")] // Ogonek is not above
+    #[case(Locale::Lithuanian, "I", "i")]
+    #[case(Locale::Neutral, "Í", "í")]
+    #[case(Locale::Dutch, "IJSLAND", "ijsland")]
+    #[case(Locale::Neutral, "ΟΔΟΣ", "οδος")] // Final sigma
+    fn test_lower(#[case] locale: Locale, #[case] input: &str, #[case] expected: &str) {
+        assert_eq!(locale.lower(input), expected);
+    }
+
+    #[rstest]
+    #[case(Locale::Neutral, "", "")]
+    #[case(Locale::Neutral, "a dog's life", "A Dog's Life")]
+    #[case(Locale::Neutral, "miXeD caSe", "MiXeD CaSe")]
+    #[case(Locale::Neutral, "ǆungla", "ǅungla")] // Titlecase, not uppercase
+    #[case(Locale::Neutral, "e\u{301}cole", "E\u{301}cole")] // Marks kept
+    #[case(Locale::Neutral, "  x, 1y ", "  X, 1y ")]
+    #[case(Locale::Turkish, "izmir ılık", "İzmir Ilık")]
+    #[case(Locale::Lithuanian, "i\u{307}\u{301}s", "I\u{301}s")]
+    #[case(Locale::Dutch, "ijsland en ijmuiden", "IJsland En IJmuiden")]
+    #[case(Locale::Dutch, "Ijsland", "IJsland")]
+    #[case(Locale::Dutch, "index", "Index")]
+    #[case(Locale::Neutral, "ijsland", "Ijsland")]
+    fn test_titlecase(#[case] locale: Locale, #[case] input: &str, #[case] expected: &str) {
+        assert_eq!(locale.titlecase(input), expected);
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 
@@ -6,6 +7,8 @@ use unescape::unescape;
 use variables::{inject_variables, VariableExpressionError};
 
 use super::{Action, ActionError};
+#[cfg(doc)]
+use crate::scoping::langs::QuerySource;
 use crate::scoping::scope::ScopeContext;
 
 /// Items for dealing with variables in replacement values.
@@ -131,7 +134,7 @@ impl Action for Replacement {
 
     fn act_with_context(
         &self,
-        _input: &str,
+        input: &str,
         context: &ScopeContext<'_>,
     ) -> Result<String, ActionError> {
         match context {
@@ -140,10 +143,80 @@ impl Action for Replacement {
 
                 Ok(inject_variables(self.0.as_str(), cgs)?)
             }
+            ScopeContext::QueryCapture(_) => Ok(self.act(input)),
         }
     }
 }
 
+/// Replaces input with a fixed string *per query capture*, allowing coordinated
+/// rewrites of multiple parts of a match in a single pass.
+///
+/// Input stemming from a capture without a replacement is left alone, as is input
+/// without any [`ScopeContext::QueryCapture`] (see
+/// [`QuerySource::with_capture_contexts`]).
+///
+/// ## Examples
+///
+/// ```rust
+/// use srgn::actions::{CaptureReplacements, Replacement};
+/// use srgn::scoping::langs::{python::CompiledQuery, QuerySource};
+/// use srgn::scoping::view::ScopedViewBuilder;
+///
+/// let query = QuerySource::from(
+///     "(function_definition name: (identifier) @name parameters: (parameters) @params)"
+///         .to_owned(),
+/// )
+/// .with_capture_contexts();
+/// let scoper = CompiledQuery::try_from(query).unwrap();
+/// let action: CaptureReplacements = [
+///     ("name", "g"),
+///     ("params", "(y)"),
+/// ]
+/// .into_iter()
+/// .map(|(name, value)| (name.to_owned(), Replacement::try_from(value.to_owned()).unwrap()))
+/// .collect();
+///
+/// let mut builder = ScopedViewBuilder::new("def f(x): pass");
+/// builder.explode(&scoper);
+/// let mut view = builder.build();
+/// view.map_with_context(&action).unwrap();
+///
+/// assert_eq!(view.to_string(), "def g(y): pass");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CaptureReplacements(HashMap<String, Replacement>);
+
+impl FromIterator<(String, Replacement)> for CaptureReplacements {
+    fn from_iter<T: IntoIterator<Item = (String, Replacement)>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl Action for CaptureReplacements {
+    fn act(&self, input: &str) -> String {
+        info!("No query capture known, leaving '{}' alone", input);
+        input.to_owned()
+    }
+
+    fn act_with_context(
+        &self,
+        input: &str,
+        context: &ScopeContext<'_>,
+    ) -> Result<String, ActionError> {
+        let ScopeContext::QueryCapture(name) = context else {
+            return Ok(self.act(input));
+        };
+
+        Ok(self.0.get(name).map_or_else(
+            || {
+                debug!("No replacement for capture '{name}'");
+                input.to_owned()
+            },
+            |replacement| replacement.act(input),
+        ))
+    }
+}
+
 impl From<VariableExpressionError> for ActionError {
     fn from(value: VariableExpressionError) -> Self {
         Self::ReplacementError(value.into())
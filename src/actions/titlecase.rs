@@ -1,14 +1,30 @@
 use titlecase::titlecase;
 
-use super::Action;
+use super::{Action, Locale};
 
 /// Renders in titlecase.
+///
+/// For [`Locale::Neutral`], English rules apply: small words like "a" are left alone.
+/// For other locales, every word starts with a capital.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-pub struct Titlecase {}
+pub struct Titlecase {
+    locale: Locale,
+}
+
+impl Titlecase {
+    /// Creates a new instance, titlecasing according to the rules of `locale`.
+    #[must_use]
+    pub const fn new(locale: Locale) -> Self {
+        Self { locale }
+    }
+}
 
 impl Action for Titlecase {
     fn act(&self, input: &str) -> String {
-        titlecase(input)
+        match self.locale {
+            Locale::Neutral => titlecase(input),
+            locale => locale.titlecase(input),
+        }
     }
 }
 
@@ -1,12 +1,22 @@
-use super::Action;
+use super::{Action, Locale};
 
 /// Renders in uppercase.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-pub struct Upper {}
+pub struct Upper {
+    locale: Locale,
+}
+
+impl Upper {
+    /// Creates a new instance, uppercasing according to the rules of `locale`.
+    #[must_use]
+    pub const fn new(locale: Locale) -> Self {
+        Self { locale }
+    }
+}
 
 impl Action for Upper {
     fn act(&self, input: &str) -> String {
-        input.replace('ß', "ẞ").to_uppercase()
+        self.locale.upper(&input.replace('ß', "ẞ"))
     }
 }
 
@@ -0,0 +1,233 @@
+//! `cargo srgn`: [srgn](https://github.com/alexpovel/srgn) for Cargo workspaces.
+//!
+//! Works on the Rust sources (`src`, `tests` and `benches`) of all workspace members,
+//! with paths relative to the workspace root. Prepared Rust queries may be given
+//! directly, so `cargo srgn --doc-comments 'fix'` is short for `srgn --rust
+//! doc-comments 'fix'` run across the workspace. All other arguments are passed to
+//! `srgn` as-is, besides these:
+//!
+//! - `--manifest-path <PATH>`: the `Cargo.toml` to find the workspace by, instead of
+//!   searching upwards from the working directory.
+//! - `-p, --package <NAME>`: only work on the given member. Can be given multiple times.
+//!
+//! Passing `--glob` or `--iglob` overrides which files are worked on.
+
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use srgn::scoping::langs::rust::PreparedQuery;
+
+/// Directories of a package holding Rust sources to work on.
+const SOURCE_DIRECTORIES: &[&str] = &["src", "tests", "benches"];
+
+/// Output of `cargo metadata`, as far as needed, see
+/// <https://doc.rust-lang.org/cargo/commands/cargo-metadata.html#json-format>.
+#[derive(Debug, Deserialize)]
+struct Metadata {
+    packages: Vec<Package>,
+    workspace_members: Vec<String>,
+    workspace_root: PathBuf,
+}
+
+/// A package in [`Metadata`].
+#[derive(Debug, Deserialize)]
+struct Package {
+    id: String,
+    name: String,
+    manifest_path: PathBuf,
+}
+
+/// Arguments meant for us, not `srgn`.
+#[derive(Debug, Default)]
+struct CargoArgs {
+    manifest_path: Option<OsString>,
+    packages: Vec<String>,
+}
+
+fn main() -> Result<()> {
+    let mut args = env::args_os().skip(1).peekable();
+    // Run as `cargo srgn`, Cargo passes the subcommand name along.
+    if args.peek().is_some_and(|arg| arg == "srgn") {
+        args.next();
+    }
+
+    let (cargo_args, srgn_args) = split_args(args)?;
+    let metadata = metadata(cargo_args.manifest_path.as_deref())?;
+
+    let mut command = Command::new(srgn_executable());
+    command.current_dir(&metadata.workspace_root);
+
+    // Always working on files, so stdin is of no interest.
+    if !srgn_args.iter().any(|arg| arg == "--stdin-override-to") {
+        command.args(["--stdin-override-to", "false"]);
+    }
+
+    if !srgn_args
+        .iter()
+        .any(|arg| arg == "--glob" || arg == "--iglob")
+    {
+        let globs = source_globs(&metadata, &cargo_args.packages)?;
+        if globs.is_empty() {
+            bail!("No Rust sources found in workspace members");
+        }
+
+        for glob in globs {
+            command.arg("--glob").arg(glob);
+        }
+    }
+
+    command.args(srgn_args);
+
+    let status = command
+        .status()
+        .context("Failed to run srgn; is it installed?")?;
+    process::exit(status.code().unwrap_or(1));
+}
+
+/// Splits `args` into those meant for us and those meant for `srgn`, expanding
+/// prepared Rust queries given as flags (`--doc-comments`) on the way.
+fn split_args(args: impl Iterator<Item = OsString>) -> Result<(CargoArgs, Vec<OsString>)> {
+    let mut cargo_args = CargoArgs::default();
+    let mut srgn_args = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let Some(s) = arg.to_str() else {
+            srgn_args.push(arg);
+            continue;
+        };
+
+        match s {
+            "--" => {
+                // Everything after is positional.
+                srgn_args.push(arg);
+                srgn_args.extend(args.by_ref());
+            }
+            "--manifest-path" => {
+                cargo_args.manifest_path =
+                    Some(args.next().context("`--manifest-path` requires a value")?);
+            }
+            "-p" | "--package" => {
+                let package = args.next().context("`--package` requires a value")?;
+                cargo_args.packages.push(
+                    package
+                        .into_string()
+                        .map_err(|_| anyhow::anyhow!("Package name is not valid UTF-8"))?,
+                );
+            }
+            _ => {
+                if let Some(path) = s.strip_prefix("--manifest-path=") {
+                    cargo_args.manifest_path = Some(path.into());
+                } else if let Some(package) = s.strip_prefix("--package=") {
+                    cargo_args.packages.push(package.to_owned());
+                } else if let Some(query) = s.strip_prefix("--").filter(|q| is_prepared_query(q)) {
+                    srgn_args.push("--rust".into());
+                    srgn_args.push(query.into());
+                } else {
+                    srgn_args.push(arg);
+                }
+            }
+        }
+    }
+
+    Ok((cargo_args, srgn_args))
+}
+
+/// Whether `name` is that of a prepared Rust query, as on the command line.
+fn is_prepared_query(name: &str) -> bool {
+    PreparedQuery::value_variants()
+        .iter()
+        .filter_map(ValueEnum::to_possible_value)
+        .any(|value| value.matches(name, false))
+}
+
+/// Runs `cargo metadata` for the workspace at `manifest_path`, or the current one.
+fn metadata(manifest_path: Option<&OsStr>) -> Result<Metadata> {
+    // Set by Cargo when running subcommands.
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+
+    let mut command = Command::new(cargo);
+    command.args(["metadata", "--format-version", "1", "--no-deps"]);
+    if let Some(manifest_path) = manifest_path {
+        command.arg("--manifest-path").arg(manifest_path);
+    }
+
+    let output = command.output().context("Failed to run `cargo metadata`")?;
+    if !output.status.success() {
+        bail!(
+            "`cargo metadata` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    serde_json::from_slice(&output.stdout).context("Failed to parse `cargo metadata` output")
+}
+
+/// Globs of all Rust sources in [`SOURCE_DIRECTORIES`] of workspace members, relative
+/// to the workspace root. Only members named in `packages` are considered, unless it
+/// is empty.
+fn source_globs(metadata: &Metadata, packages: &[String]) -> Result<Vec<String>> {
+    let members = metadata
+        .packages
+        .iter()
+        .filter(|p| metadata.workspace_members.contains(&p.id))
+        .collect::<Vec<_>>();
+
+    if let Some(unknown) = packages
+        .iter()
+        .find(|name| !members.iter().any(|p| &&p.name == name))
+    {
+        bail!("Package `{unknown}` is not a member of the workspace");
+    }
+
+    let mut globs = Vec::new();
+    for package in members {
+        if !packages.is_empty() && !packages.contains(&package.name) {
+            continue;
+        }
+
+        let root = package
+            .manifest_path
+            .parent()
+            .context("Manifest path has no parent")?;
+
+        for dir in SOURCE_DIRECTORIES {
+            let dir = root.join(dir);
+            if !dir.is_dir() {
+                continue;
+            }
+
+            let Ok(relative) = dir.strip_prefix(&metadata.workspace_root) else {
+                // Members outside of the workspace root cannot be walked to.
+                continue;
+            };
+
+            globs.push(format!("{}/**/*.rs", escape(relative)));
+        }
+    }
+
+    Ok(globs)
+}
+
+/// `path` as a glob pattern matching itself literally, with `/` as separator.
+fn escape(path: &Path) -> String {
+    path.components()
+        .map(|c| glob::Pattern::escape(&c.as_os_str().to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The `srgn` executable next to our own, as installed alongside, or the one on
+/// `PATH` otherwise.
+fn srgn_executable() -> PathBuf {
+    env::current_exe()
+        .ok()
+        .map(|exe| exe.with_file_name(format!("srgn{}", env::consts::EXE_SUFFIX)))
+        .filter(|srgn| srgn.is_file())
+        .unwrap_or_else(|| PathBuf::from("srgn"))
+}
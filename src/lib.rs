@@ -102,6 +102,39 @@
 //! );
 //! ```
 //!
+//! ### Combining scopers
+//!
+//! Scopers compose using set logic, via the types in
+//! [`combinators`][`scoping::combinators`]. For example, all Python strings except
+//! those inside of test functions:
+//!
+//! ```rust
+//! use srgn::scoping::combinators::Difference;
+//! use srgn::scoping::langs::{
+//!     python::{CompiledQuery, PreparedQuery},
+//!     QuerySource
+//! };
+//! use srgn::scoping::view::ScopedViewBuilder;
+//!
+//! let input = "def test_foo(): return 'a'\ndef foo(): return 'b'\n";
+//!
+//! let strings = CompiledQuery::from(PreparedQuery::Strings);
+//! let tests = CompiledQuery::try_from(QuerySource::from(
+//!     r#"(function_definition name: (identifier) @name (#match? @name "^test_")) @f"#.to_owned(),
+//! )).unwrap();
+//!
+//! let mut builder = ScopedViewBuilder::new(input);
+//! builder.explode(&Difference(strings, tests));
+//!
+//! let mut view = builder.build();
+//! view.upper();
+//!
+//! assert_eq!(
+//!     view.to_string(),
+//!     "def test_foo(): return 'a'\ndef foo(): return 'B'\n"
+//! );
+//! ```
+//!
 //! ## Applying an action (associated function)
 //!
 //! With a usefully scoped view in hand, one can apply any number of actions. The
@@ -184,10 +217,28 @@ use crate::{
 pub mod actions;
 /// Utilities around finding files.
 pub mod find;
+/// A language server, offering pipelines of scopes and actions as code actions.
+#[cfg(feature = "json")]
+pub mod lsp;
+/// Detecting and converting line endings.
+pub mod newlines;
+/// Chains of [`Scoper`]s and [`Action`]s, assembled at runtime.
+pub mod pipeline;
 /// Components to work with collections of [`Range`]s.
 pub mod ranges;
 /// Main components around [`ScopedView`].
 pub mod scoping;
+/// A long-running server, answering requests to scope and act over JSON-RPC.
+#[cfg(feature = "json")]
+pub mod server;
+/// Scopes and actions described as JSON, for bindings and the server.
+#[cfg(feature = "json")]
+pub mod spec;
+/// Processing input piece by piece, as a stream.
+pub mod stream;
+/// Bindings for JavaScript, to run in browsers and Node.js through WebAssembly.
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 /// Pattern signalling global scope, aka matching entire inputs.
 pub const GLOBAL_SCOPE: &str = r".*";
@@ -0,0 +1,893 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+
+use log::{debug, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::ranges::Ranges;
+use crate::scoping::view::ScopedViewBuilder;
+use crate::scoping::Scoper;
+use crate::server::{codes, error_response, CallError};
+use crate::spec::{ActionSpec, ApplyError, ScopeSpec};
+
+/// Prefix of the commands pipelines are advertised as, followed by their name.
+const COMMAND_PREFIX: &str = "srgn.";
+
+/// The kind of code action pipelines are offered as.
+const CODE_ACTION_KIND: &str = "refactor.rewrite";
+
+/// Pipelines for a [`LanguageServer`] to offer, for example:
+///
+/// ```json
+/// {
+///   "pipelines": [
+///     {
+///       "name": "upper-comments",
+///       "title": "Uppercase comments",
+///       "languages": ["python"],
+///       "scopes": [{"python": "comments"}],
+///       "actions": ["upper"]
+///     }
+///   ]
+/// }
+/// ```
+///
+/// Scopes and actions are given as for [`try_apply`][crate::spec::try_apply]. The
+/// `title` defaults to the `name`, and without any `languages` (identifiers as sent by
+/// editors), a pipeline is offered for all documents.
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pipelines: Vec<PipelineConfig>,
+}
+
+impl Config {
+    /// Parses a configuration from `json`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if `json` is not a valid configuration.
+    pub fn from_json(json: &str) -> Result<Self, ApplyError> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// A single pipeline of a [`Config`].
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PipelineConfig {
+    name: String,
+    title: Option<String>,
+    #[serde(default)]
+    languages: Vec<String>,
+    #[serde(default)]
+    scopes: Vec<ScopeSpec>,
+    actions: Vec<ActionSpec>,
+}
+
+/// A [`PipelineConfig`], compiled and ready to run.
+struct Pipeline {
+    name: String,
+    title: String,
+    languages: Vec<String>,
+    scopers: Vec<Box<dyn Scoper>>,
+    actions: Vec<ActionSpec>,
+}
+
+impl fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("name", &self.name)
+            .field("languages", &self.languages)
+            .field("scopers", &self.scopers.len())
+            .field("actions", &self.actions)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TryFrom<PipelineConfig> for Pipeline {
+    type Error = ApplyError;
+
+    fn try_from(config: PipelineConfig) -> Result<Self, Self::Error> {
+        Ok(Self {
+            title: config.title.unwrap_or_else(|| config.name.clone()),
+            name: config.name,
+            languages: config.languages,
+            scopers: config
+                .scopes
+                .into_iter()
+                .map(ScopeSpec::into_scoper)
+                .collect::<Result<_, _>>()?,
+            actions: config.actions,
+        })
+    }
+}
+
+impl Pipeline {
+    fn command(&self) -> String {
+        format!("{COMMAND_PREFIX}{}", self.name)
+    }
+
+    fn is_offered_for(&self, language_id: &str) -> bool {
+        self.languages.is_empty() || self.languages.iter().any(|l| l == language_id)
+    }
+
+    /// Runs on `text`, only acting within `selection` if given. Returns the result if
+    /// anything changed.
+    fn run(
+        &self,
+        text: &str,
+        selection: Option<Range<usize>>,
+    ) -> Result<Option<String>, ApplyError> {
+        let mut builder = ScopedViewBuilder::new(text);
+        for scoper in &self.scopers {
+            builder.explode(scoper);
+        }
+
+        if let Some(selection) = selection {
+            // Only after scoping: language scopers need to see the entire document.
+            builder.restrict(&Ranges::from_iter([selection]));
+        }
+
+        let mut view = builder.build();
+        for action in &self.actions {
+            action.apply(&mut view)?;
+        }
+
+        Ok(view.changed().then(|| view.to_string()))
+    }
+}
+
+/// How positions in documents count characters, see
+/// <https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#positionEncodingKind>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum PositionEncoding {
+    Utf8,
+    /// Mandatory to support, so the default.
+    #[default]
+    Utf16,
+}
+
+impl PositionEncoding {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Utf16 => "utf-16",
+        }
+    }
+
+    const fn len(self, c: char) -> usize {
+        match self {
+            Self::Utf8 => c.len_utf8(),
+            Self::Utf16 => c.len_utf16(),
+        }
+    }
+}
+
+/// A position in a document, zero-based.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+struct Position {
+    line: usize,
+    character: usize,
+}
+
+impl Position {
+    /// The position of byte `offset` into `text`.
+    fn of(offset: usize, text: &str, encoding: PositionEncoding) -> Self {
+        let before = &text[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+        Self {
+            line: before.matches('\n').count(),
+            character: before[line_start..].chars().map(|c| encoding.len(c)).sum(),
+        }
+    }
+
+    /// The byte offset of this position into `text`. Positions past the end of a
+    /// line or the document are clamped to it.
+    fn offset(self, text: &str, encoding: PositionEncoding) -> usize {
+        let line_start = match self.line {
+            0 => 0,
+            n => match text.match_indices('\n').nth(n - 1) {
+                Some((i, _)) => i + 1,
+                None => return text.len(),
+            },
+        };
+
+        let line = &text[line_start..];
+        let line = &line[..line.find('\n').unwrap_or(line.len())];
+
+        let mut character = 0;
+        for (i, c) in line.char_indices() {
+            if character >= self.character {
+                return line_start + i;
+            }
+            character += encoding.len(c);
+        }
+
+        line_start + line.len()
+    }
+}
+
+/// A range in a document, end-exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+struct LspRange {
+    start: Position,
+    end: Position,
+}
+
+/// A document opened in the editor.
+#[derive(Debug)]
+struct Document {
+    text: String,
+    language_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InitializeParams {
+    #[serde(default)]
+    capabilities: Value,
+    initialization_options: Option<Config>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextDocumentItem {
+    uri: String,
+    language_id: String,
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextDocumentIdentifier {
+    uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DidOpenParams {
+    text_document: TextDocumentItem,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContentChange {
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DidChangeParams {
+    text_document: TextDocumentIdentifier,
+    content_changes: Vec<ContentChange>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DidCloseParams {
+    text_document: TextDocumentIdentifier,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CodeActionParams {
+    text_document: TextDocumentIdentifier,
+    range: LspRange,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExecuteCommandParams {
+    command: String,
+    #[serde(default)]
+    arguments: Vec<Value>,
+}
+
+/// A [language server](https://microsoft.github.io/language-server-protocol/),
+/// offering pipelines of scopes and actions to editors.
+///
+/// Each pipeline of its [`Config`] is advertised as a command (`srgn.<name>`, taking
+/// a document URI as its argument) and offered as a code action on documents it
+/// applies to. Code actions act within the selection, if there is one. Further
+/// pipelines can be passed by the editor as `initializationOptions`, in the same
+/// shape as the [`Config`].
+#[derive(Debug, Default)]
+pub struct LanguageServer {
+    pipelines: Vec<Pipeline>,
+    documents: HashMap<String, Document>,
+    encoding: PositionEncoding,
+    /// ID of the next request sent to the client.
+    next_id: u64,
+    exited: bool,
+}
+
+impl LanguageServer {
+    /// Creates a server offering the pipelines of `config`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if any of the pipelines' scopes fails to compile.
+    pub fn new(config: Config) -> Result<Self, ApplyError> {
+        let mut server = Self::default();
+        server.add_pipelines(config)?;
+
+        Ok(server)
+    }
+
+    fn add_pipelines(&mut self, config: Config) -> Result<(), ApplyError> {
+        for pipeline in config.pipelines {
+            let pipeline = Pipeline::try_from(pipeline)?;
+            debug!("Adding pipeline: {pipeline:?}");
+            self.pipelines.push(pipeline);
+        }
+
+        Ok(())
+    }
+
+    /// Talks the language server protocol, reading messages from `reader` and writing
+    /// to `writer`, until told to exit or `reader` is exhausted.
+    ///
+    /// # Errors
+    ///
+    /// Errors if reading or writing fails, or messages are not framed properly.
+    pub fn serve(&mut self, mut reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+        while let Some(message) = read_message(&mut reader)? {
+            let outgoing = match serde_json::from_slice(&message) {
+                Ok(message) => self.handle(message),
+                Err(e) => vec![error_response(
+                    &Value::Null,
+                    codes::PARSE_ERROR,
+                    &e.to_string(),
+                )],
+            };
+
+            for message in outgoing {
+                write_message(&mut writer, &message)?;
+            }
+
+            if self.exited {
+                info!("Told to exit, shutting down language server.");
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles a single incoming `message`, returning all messages to send in turn.
+    fn handle(&mut self, message: Value) -> Vec<Value> {
+        trace!("Handling message: {message}");
+
+        let Value::Object(mut message) = message else {
+            return vec![error_response(
+                &Value::Null,
+                codes::INVALID_REQUEST,
+                "Message is not an object",
+            )];
+        };
+
+        let id = message.remove("id");
+        let Some(Value::String(method)) = message.remove("method") else {
+            // A response to one of our own requests, nothing left to do.
+            trace!("Received response for request {id:?}.");
+            return Vec::new();
+        };
+        let params = message.remove("params").unwrap_or_default();
+
+        debug!("Calling method '{method}'.");
+        let mut outgoing = Vec::new();
+        let result = self.call(&method, params, &mut outgoing);
+
+        match (id, result) {
+            (Some(id), Ok(result)) => {
+                outgoing.push(json!({"jsonrpc": "2.0", "id": id, "result": result}));
+            }
+            (Some(id), Err(e)) => outgoing.push(error_response(&id, e.code(), &e.to_string())),
+            (None, Ok(_)) => {}
+            (None, Err(e)) => warn!("Handling notification '{method}' failed: {e}"),
+        }
+
+        outgoing
+    }
+
+    fn call(
+        &mut self,
+        method: &str,
+        params: Value,
+        outgoing: &mut Vec<Value>,
+    ) -> Result<Value, CallError> {
+        match method {
+            "initialize" => self.initialize(parse(params)?),
+            "shutdown" | "initialized" => Ok(Value::Null),
+            "exit" => {
+                self.exited = true;
+                Ok(Value::Null)
+            }
+            "textDocument/didOpen" => {
+                let DidOpenParams { text_document } = parse(params)?;
+                self.documents.insert(
+                    text_document.uri,
+                    Document {
+                        text: text_document.text,
+                        language_id: text_document.language_id,
+                    },
+                );
+                Ok(Value::Null)
+            }
+            "textDocument/didChange" => {
+                let DidChangeParams {
+                    text_document,
+                    content_changes,
+                } = parse(params)?;
+                // Synchronizing fully, so the last change is the entire document.
+                if let (Some(document), Some(change)) = (
+                    self.documents.get_mut(&text_document.uri),
+                    content_changes.into_iter().last(),
+                ) {
+                    document.text = change.text;
+                }
+                Ok(Value::Null)
+            }
+            "textDocument/didClose" => {
+                let DidCloseParams { text_document } = parse(params)?;
+                self.documents.remove(&text_document.uri);
+                Ok(Value::Null)
+            }
+            "textDocument/codeAction" => Ok(self.code_actions(&parse(params)?)),
+            "workspace/executeCommand" => self.execute_command(&parse(params)?, outgoing),
+            // Optional notifications, free to ignore.
+            _ if method.starts_with("$/") => Ok(Value::Null),
+            _ => Err(CallError::MethodNotFound(method.to_owned())),
+        }
+    }
+
+    fn initialize(&mut self, params: InitializeParams) -> Result<Value, CallError> {
+        if let Some(config) = params.initialization_options {
+            self.add_pipelines(config)?;
+        }
+
+        let encodings = &params.capabilities["general"]["positionEncodings"];
+        if encodings
+            .as_array()
+            .is_some_and(|e| e.contains(&json!(PositionEncoding::Utf8.name())))
+        {
+            self.encoding = PositionEncoding::Utf8;
+        }
+        info!(
+            "Initialized with {} pipelines, position encoding {:?}.",
+            self.pipelines.len(),
+            self.encoding
+        );
+
+        Ok(json!({
+            "capabilities": {
+                "positionEncoding": self.encoding.name(),
+                "textDocumentSync": 1, // Full
+                "codeActionProvider": {"codeActionKinds": [CODE_ACTION_KIND]},
+                "executeCommandProvider": {
+                    "commands": self.pipelines.iter().map(Pipeline::command).collect::<Vec<_>>(),
+                },
+            },
+            "serverInfo": {"name": "srgn", "version": env!("CARGO_PKG_VERSION")},
+        }))
+    }
+
+    fn code_actions(&self, params: &CodeActionParams) -> Value {
+        let Some(document) = self.documents.get(&params.text_document.uri) else {
+            return json!([]);
+        };
+
+        let start = params.range.start.offset(&document.text, self.encoding);
+        let end = params.range.end.offset(&document.text, self.encoding);
+        let selection = (start < end).then_some(start..end);
+
+        let mut actions = Vec::new();
+        for pipeline in &self.pipelines {
+            if !pipeline.is_offered_for(&document.language_id) {
+                continue;
+            }
+
+            let result = match pipeline.run(&document.text, selection.clone()) {
+                Ok(Some(result)) => result,
+                Ok(None) => continue, // Nothing to do, nothing to offer
+                Err(e) => {
+                    warn!("Pipeline '{}' failed: {e}", pipeline.name);
+                    continue;
+                }
+            };
+
+            let title = if selection.is_some() {
+                format!("{} (selection)", pipeline.title)
+            } else {
+                pipeline.title.clone()
+            };
+
+            actions.push(json!({
+                "title": title,
+                "kind": CODE_ACTION_KIND,
+                "edit": self.workspace_edit(&params.text_document.uri, &document.text, &result),
+            }));
+        }
+
+        Value::Array(actions)
+    }
+
+    fn execute_command(
+        &mut self,
+        params: &ExecuteCommandParams,
+        outgoing: &mut Vec<Value>,
+    ) -> Result<Value, CallError> {
+        let pipeline = self
+            .pipelines
+            .iter()
+            .find(|p| p.command() == params.command)
+            .ok_or_else(|| invalid_params(format!("Unknown command: {}", params.command)))?;
+
+        let uri = params
+            .arguments
+            .first()
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid_params("Expected a document URI as argument"))?;
+        let document = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| invalid_params(format!("Unknown document: {uri}")))?;
+
+        if let Some(result) = pipeline.run(&document.text, None)? {
+            let edit = self.workspace_edit(uri, &document.text, &result);
+            let label = pipeline.title.clone();
+
+            self.next_id += 1;
+            outgoing.push(json!({
+                "jsonrpc": "2.0",
+                "id": self.next_id,
+                "method": "workspace/applyEdit",
+                "params": {"label": label, "edit": edit},
+            }));
+        }
+
+        Ok(Value::Null)
+    }
+
+    /// An edit turning `old` into `new` for the document at `uri`, touching as little
+    /// as possible so editors keep cursors and folds intact.
+    fn workspace_edit(&self, uri: &str, old: &str, new: &str) -> Value {
+        let (range, text) = minimal_edit(old, new);
+        let range = LspRange {
+            start: Position::of(range.start, old, self.encoding),
+            end: Position::of(range.end, old, self.encoding),
+        };
+
+        json!({"changes": {uri: [{"range": range, "newText": text}]}})
+    }
+}
+
+/// The range of `old` to replace, and what to replace it with, to arrive at `new`.
+fn minimal_edit<'a>(old: &str, new: &'a str) -> (Range<usize>, &'a str) {
+    fn common_len(a: impl Iterator<Item = char>, b: impl Iterator<Item = char>) -> usize {
+        a.zip(b)
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a.len_utf8())
+            .sum()
+    }
+
+    let prefix = common_len(old.chars(), new.chars());
+    let suffix = common_len(old[prefix..].chars().rev(), new[prefix..].chars().rev());
+
+    (prefix..old.len() - suffix, &new[prefix..new.len() - suffix])
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, CallError> {
+    serde_json::from_value(params).map_err(CallError::InvalidParams)
+}
+
+fn invalid_params(message: impl fmt::Display) -> CallError {
+    CallError::InvalidParams(serde::de::Error::custom(message))
+}
+
+/// Reads a single message, framed by headers. Returns [`None`] once `reader` is
+/// exhausted.
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = Some(
+                    value
+                        .trim()
+                        .parse()
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+                );
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "Message lacks a Content-Length")
+    })?;
+
+    let mut message = vec![0; length];
+    reader.read_exact(&mut message)?;
+
+    Ok(Some(message))
+}
+
+fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let message = serde_json::to_vec(message)?;
+
+    write!(writer, "Content-Length: {}\r\n\r\n", message.len())?;
+    writer.write_all(&message)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("", 0, (0, 0), (0, 0))]
+    #[case("abc", 2, (0, 2), (0, 2))]
+    #[case("a\nbc", 3, (1, 1), (1, 1))]
+    #[case("a\nbc\n", 5, (2, 0), (2, 0))]
+    #[case("ä𝄞x", 6, (0, 3), (0, 6))] // UTF-16 surrogate pair vs. bytes
+    #[case("x\n😀y", 6, (1, 2), (1, 4))]
+    fn test_position_of(
+        #[case] text: &str,
+        #[case] offset: usize,
+        #[case] utf16: (usize, usize),
+        #[case] utf8: (usize, usize),
+    ) {
+        for (encoding, (line, character)) in [
+            (PositionEncoding::Utf16, utf16),
+            (PositionEncoding::Utf8, utf8),
+        ] {
+            let position = Position { line, character };
+            assert_eq!(Position::of(offset, text, encoding), position);
+            assert_eq!(position.offset(text, encoding), offset);
+        }
+    }
+
+    #[rstest]
+    #[case("abc", (0, 10), 3)] // Past the line
+    #[case("abc\ndef", (0, 10), 3)]
+    #[case("abc\ndef", (5, 0), 7)] // Past the document
+    #[case("ä", (0, 1), 2)]
+    fn test_position_offset_clamps(
+        #[case] text: &str,
+        #[case] (line, character): (usize, usize),
+        #[case] expected: usize,
+    ) {
+        let position = Position { line, character };
+        assert_eq!(position.offset(text, PositionEncoding::Utf16), expected);
+    }
+
+    #[rstest]
+    #[case("abc", "abc", 3..3, "")]
+    #[case("abc", "aXc", 1..2, "X")]
+    #[case("abc", "abcd", 3..3, "d")]
+    #[case("abc", "", 0..3, "")]
+    #[case("aaa", "aa", 2..3, "")]
+    #[case("äöü", "äÖü", 2..4, "Ö")]
+    fn test_minimal_edit(
+        #[case] old: &str,
+        #[case] new: &str,
+        #[case] range: Range<usize>,
+        #[case] text: &str,
+    ) {
+        assert_eq!(minimal_edit(old, new), (range, text));
+    }
+
+    fn request(id: u64, method: &str, params: &Value) -> Value {
+        json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+    }
+
+    fn notification(method: &str, params: &Value) -> Value {
+        json!({"jsonrpc": "2.0", "method": method, "params": params})
+    }
+
+    fn server() -> LanguageServer {
+        let config = Config::from_json(
+            r#"{"pipelines": [
+                {"name": "upper-comments", "title": "Uppercase comments", "languages": ["python"], "scopes": [{"python": "comments"}], "actions": ["upper"]},
+                {"name": "redact", "scopes": [{"regex": "secret"}], "actions": [{"replace": "***"}]}
+            ]}"#,
+        )
+        .unwrap();
+
+        let mut server = LanguageServer::new(config).unwrap();
+        server.handle(request(1, "initialize", &json!({"capabilities": {}})));
+        server.handle(notification(
+            "textDocument/didOpen",
+            &json!({"textDocument": {"uri": "file:///a.py", "languageId": "python", "version": 1, "text": "x = 'secret'  # secret\n"}}),
+        ));
+        server
+    }
+
+    #[test]
+    fn test_initialize() {
+        let mut server = LanguageServer::default();
+        let responses = server.handle(request(
+            1,
+            "initialize",
+            &json!({
+                "capabilities": {"general": {"positionEncodings": ["utf-8", "utf-16"]}},
+                "initializationOptions": {"pipelines": [{"name": "upper", "actions": ["upper"]}]},
+            }),
+        ));
+
+        let capabilities = &responses[0]["result"]["capabilities"];
+        assert_eq!(capabilities["positionEncoding"], json!("utf-8"));
+        assert_eq!(
+            capabilities["executeCommandProvider"]["commands"],
+            json!(["srgn.upper"])
+        );
+    }
+
+    #[test]
+    fn test_initialize_invalid_options() {
+        let mut server = LanguageServer::default();
+        let responses = server.handle(request(
+            1,
+            "initialize",
+            &json!({"capabilities": {}, "initializationOptions": {"pipelines": [{"name": "x", "actions": ["shout"]}]}}),
+        ));
+
+        assert_eq!(responses[0]["error"]["code"], json!(codes::INVALID_PARAMS));
+    }
+
+    #[test]
+    fn test_code_actions() {
+        let mut server = server();
+
+        let responses = server.handle(request(
+            2,
+            "textDocument/codeAction",
+            &json!({"textDocument": {"uri": "file:///a.py"}, "range": {"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 0}}, "context": {"diagnostics": []}}),
+        ));
+        assert_eq!(
+            responses,
+            vec![json!({"jsonrpc": "2.0", "id": 2, "result": [
+                {"title": "Uppercase comments", "kind": "refactor.rewrite", "edit": {"changes": {"file:///a.py": [
+                    {"range": {"start": {"line": 0, "character": 16}, "end": {"line": 0, "character": 22}}, "newText": "SECRET"},
+                ]}}},
+                {"title": "redact", "kind": "refactor.rewrite", "edit": {"changes": {"file:///a.py": [
+                    {"range": {"start": {"line": 0, "character": 5}, "end": {"line": 0, "character": 22}}, "newText": "***'  # ***"},
+                ]}}},
+            ]})]
+        );
+    }
+
+    #[test]
+    fn test_code_actions_in_selection() {
+        let mut server = server();
+
+        // Selecting the string only: comments are out of it, so only redacting remains.
+        let responses = server.handle(request(
+            2,
+            "textDocument/codeAction",
+            &json!({"textDocument": {"uri": "file:///a.py"}, "range": {"start": {"line": 0, "character": 4}, "end": {"line": 0, "character": 12}}}),
+        ));
+        assert_eq!(
+            responses[0]["result"],
+            json!([
+                {"title": "redact (selection)", "kind": "refactor.rewrite", "edit": {"changes": {"file:///a.py": [
+                    {"range": {"start": {"line": 0, "character": 5}, "end": {"line": 0, "character": 11}}, "newText": "***"},
+                ]}}},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_code_actions_by_language() {
+        let mut server = server();
+        server.handle(notification(
+            "textDocument/didOpen",
+            &json!({"textDocument": {"uri": "file:///b.rs", "languageId": "rust", "version": 1, "text": "// secret\n"}}),
+        ));
+
+        let responses = server.handle(request(
+            2,
+            "textDocument/codeAction",
+            &json!({"textDocument": {"uri": "file:///b.rs"}, "range": {"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 0}}}),
+        ));
+        let titles: Vec<_> = responses[0]["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|action| action["title"].clone())
+            .collect();
+        assert_eq!(titles, vec![json!("redact")]);
+    }
+
+    #[test]
+    fn test_document_changes() {
+        let mut server = server();
+        server.handle(notification(
+            "textDocument/didChange",
+            &json!({"textDocument": {"uri": "file:///a.py", "version": 2}, "contentChanges": [{"text": "x = 1\n"}]}),
+        ));
+        assert_eq!(server.documents["file:///a.py"].text, "x = 1\n");
+
+        server.handle(notification(
+            "textDocument/didClose",
+            &json!({"textDocument": {"uri": "file:///a.py"}}),
+        ));
+        assert!(server.documents.is_empty());
+    }
+
+    #[test]
+    fn test_execute_command() {
+        let mut server = server();
+
+        let messages = server.handle(request(
+            2,
+            "workspace/executeCommand",
+            &json!({"command": "srgn.redact", "arguments": ["file:///a.py"]}),
+        ));
+        assert_eq!(
+            messages,
+            vec![
+                json!({"jsonrpc": "2.0", "id": 1, "method": "workspace/applyEdit", "params": {"label": "redact", "edit": {"changes": {"file:///a.py": [
+                    {"range": {"start": {"line": 0, "character": 5}, "end": {"line": 0, "character": 22}}, "newText": "***'  # ***"},
+                ]}}}}),
+                json!({"jsonrpc": "2.0", "id": 2, "result": null}),
+            ]
+        );
+
+        let messages = server.handle(request(
+            3,
+            "workspace/executeCommand",
+            &json!({"command": "srgn.nope", "arguments": ["file:///a.py"]}),
+        ));
+        assert_eq!(messages[0]["error"]["code"], json!(codes::INVALID_PARAMS));
+    }
+
+    #[test]
+    fn test_serve() {
+        let mut input = Vec::new();
+        for message in [
+            request(1, "initialize", &json!({"capabilities": {}})),
+            notification("initialized", &json!({})),
+            request(2, "nope", &json!({})),
+            request(3, "shutdown", &Value::Null),
+            notification("exit", &Value::Null),
+            request(4, "shutdown", &Value::Null), // Never read
+        ] {
+            write_message(&mut input, &message).unwrap();
+        }
+
+        let mut output = Vec::new();
+        LanguageServer::default()
+            .serve(input.as_slice(), &mut output)
+            .unwrap();
+
+        let mut output = output.as_slice();
+        let mut responses = Vec::new();
+        while let Some(message) = read_message(&mut output).unwrap() {
+            responses.push(serde_json::from_slice::<Value>(&message).unwrap());
+        }
+
+        let ids: Vec<_> = responses.iter().map(|r| r["id"].clone()).collect();
+        assert_eq!(ids, vec![json!(1), json!(2), json!(3)]);
+        assert_eq!(
+            responses[1]["error"]["code"],
+            json!(codes::METHOD_NOT_FOUND)
+        );
+    }
+}
@@ -3,32 +3,53 @@
 //! It mainly draws from `srgn`, the library, for actual implementations. This file then
 //! deals with CLI argument handling, I/O, threading, and more.
 
+use std::borrow::Cow;
+use std::collections::HashSet;
 use std::error::Error;
 use std::fs::{self, File};
-use std::io::{self, stdout, Read, Write};
-use std::path::{Path, PathBuf};
+use std::io::{self, stdout, IsTerminal, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
-use std::{env, fmt};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use std::{env, fmt, mem, panic, thread};
 
 use anyhow::{Context, Result};
 use colored::Colorize;
 use ignore::{WalkBuilder, WalkState};
 use itertools::Itertools;
 use log::{debug, error, info, trace, warn, LevelFilter};
+use memmap2::Mmap;
 use pathdiff::diff_paths;
+use serde::{Deserialize, Serialize};
 #[cfg(feature = "german")]
 use srgn::actions::German;
 use srgn::actions::{
-    Action, ActionError, Deletion, Lower, Normalization, Replacement, Style, Titlecase, Upper,
+    Action, ActionError, Actions, CaptureReplacements, Deletion, Lower, Normalization, Replacement,
+    Style, Titlecase, Upper,
 };
 #[cfg(feature = "symbols")]
 use srgn::actions::{Symbols, SymbolsInversion};
 use srgn::iterext::ParallelZipExt;
+use srgn::lsp::{self, LanguageServer};
+use srgn::newlines::{self, LineEnding};
+use srgn::ranges::Ranges;
+use srgn::scoping::columns::Columns;
+use srgn::scoping::combinators::{Difference, Intersection, Union};
+use srgn::scoping::indentation::Indentation;
+use srgn::scoping::langs::external::GrammarError;
 use srgn::scoping::langs::LanguageScoper;
 use srgn::scoping::literal::{Literal, LiteralError};
+use srgn::scoping::markers::IgnoreMarkers;
 use srgn::scoping::regex::{Regex, RegexError};
-use srgn::scoping::view::ScopedViewBuilder;
+use srgn::scoping::scope::Scope::{In, Out};
+use srgn::scoping::scope::{ROScope, RWScope, RWScopes, RangesWithContext};
+use srgn::scoping::view::{Located, ScopedView, ScopedViewBuilder};
 use srgn::scoping::Scoper;
+use srgn::server::Server;
+use srgn::{RegexPattern, GLOBAL_SCOPE};
 use tree_sitter::QueryError as TSQueryError;
 
 // We have `LanguageScoper: Scoper`, but we cannot upcast
@@ -39,7 +60,8 @@ type ScoperList = Vec<Box<dyn LanguageScoper>>;
 #[allow(clippy::too_many_lines)] // Only slightly above.
 #[allow(clippy::cognitive_complexity)]
 fn main() -> Result<()> {
-    let args = cli::Args::init();
+    let start = Instant::now();
+    let mut args = cli::Args::init();
 
     let level_filter = level_filter_from_env_and_verbosity(args.options.additional_verbosity);
     env_logger::Builder::new()
@@ -49,15 +71,24 @@ fn main() -> Result<()> {
 
     info!("Launching app with args: {:?}", args);
 
+    // All stages but the last one only transform its input. The last one is processed
+    // in full, as if it were the only one, so it takes the global options.
+    let mut stage_args = Vec::new();
+    for mut stage in mem::take(&mut args.stages) {
+        args.swap_stage(&mut stage);
+        stage_args.push(stage);
+    }
+
     let cli::Args {
         scope,
         shell,
-        composable_actions,
+        mut composable_actions,
         standalone_actions,
         mut options,
         languages_scopes,
         #[cfg(feature = "german")]
         german_options,
+        stages: _,
     } = args;
 
     if let Some(shell) = shell {
@@ -68,6 +99,37 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    if options.server {
+        info!("Serving requests on stdin.");
+        Server::default().serve(io::stdin().lock(), stdout().lock())?;
+
+        return Ok(());
+    }
+
+    if let Some(config) = &options.lsp {
+        let config = match config {
+            Some(path) => {
+                let json = fs::read_to_string(path)
+                    .with_context(|| format!("Failed reading config file {}", path.display()))?;
+                lsp::Config::from_json(&json).context("Failed parsing config file")?
+            }
+            None => lsp::Config::default(),
+        };
+
+        info!("Serving language server protocol on stdin.");
+        LanguageServer::new(config)
+            .context("Failed building pipelines")?
+            .serve(io::stdin().lock(), stdout().lock())?;
+
+        return Ok(());
+    }
+
+    if composable_actions.expand_env {
+        for value in composable_actions.replacement_values_mut() {
+            *value = expand_env_vars(value);
+        }
+    }
+
     let standalone_action = standalone_actions.into();
 
     debug!("Assembling scopers.");
@@ -76,49 +138,134 @@ fn main() -> Result<()> {
     // outlive the main one. Scoped threads would work here, `ignore` uses them
     // internally even, but we have no access here.
 
-    let language_scopers = languages_scopes
-        .compile_query_sources_to_scopes()?
-        .map(Arc::new);
+    let language = languages_scopes.language();
+    let language_scopers =
+        compile_language_scopers(languages_scopes, &composable_actions, &options)?.map(Arc::new);
     debug!("Done assembling scopers.");
 
-    let mut actions = {
-        debug!("Assembling actions.");
-        let mut actions = assemble_common_actions(&composable_actions, standalone_action)?;
+    if options.verify_syntax && language_scopers.is_none() {
+        cli::Args::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "Verifying syntax requires a language scope.",
+            )
+            .exit();
+    }
 
-        #[cfg(feature = "symbols")]
-        if composable_actions.symbols {
-            if options.invert {
-                actions.push(Box::<SymbolsInversion>::default());
-                debug!("Loaded action: SymbolsInversion");
-            } else {
-                actions.push(Box::<Symbols>::default());
-                debug!("Loaded action: Symbols");
-            }
-        }
+    if options.stream && !matches!(options.format, cli::OutputFormat::Default) {
+        cli::Args::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "Cannot stream line by line when reporting match locations or patches.",
+            )
+            .exit();
+    }
+
+    if matches!(options.format, cli::OutputFormat::Patch)
+        && (options.dry_run || options.check || options.stdout)
+    {
+        cli::Args::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "Patches are printed instead of modifying files already.",
+            )
+            .exit();
+    }
+
+    if options.stream && language_scopers.is_some() {
+        cli::Args::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "Cannot stream line by line when using language scopes.",
+            )
+            .exit();
+    }
 
+    let mut actions = assemble_actions(
+        &composable_actions,
+        standalone_action,
+        #[cfg(feature = "symbols")]
+        &options,
         #[cfg(feature = "german")]
-        if composable_actions.german {
-            actions.push(Box::new(German::new(
-                // Smell? Bug if bools swapped.
-                german_options.german_prefer_original,
-                german_options.german_naive,
-            )));
-            debug!("Loaded action: German");
-        }
+        &german_options,
+    )?;
 
-        debug!("Done assembling actions.");
-        actions
-    };
+    let earlier_stages = stage_args
+        .into_iter()
+        .map(Stage::compile)
+        .collect::<Result<Vec<_>>>()?;
+    if !earlier_stages.is_empty()
+        && (options.dry_run
+            || options.check
+            || options.stream
+            || options.rg_json
+            || !options.lines.is_empty()
+            || options.blame().is_some())
+    {
+        cli::Args::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "Stages cannot be combined with dry runs, streaming, or scoping by position in the input.",
+            )
+            .exit();
+    }
+
+    let or_without_language = (options.or && language_scopers.is_none())
+        || earlier_stages
+            .iter()
+            .any(|stage| stage.options.or && stage.language_scopers.is_empty());
+    if or_without_language {
+        cli::Args::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "Joining the scope with language scopes (--or) requires a language scope.",
+            )
+            .exit();
+    }
+
+    // Files to walk are those of the language scoped, in the last stage scoping any.
+    let (language, path_scopers) = language_scopers.as_ref().map_or_else(
+        || {
+            earlier_stages
+                .iter()
+                .rev()
+                .find(|stage| !stage.language_scopers.is_empty())
+                .map_or((None, None), |stage| {
+                    (stage.language, Some(Arc::clone(&stage.language_scopers)))
+                })
+        },
+        |language_scopers| (language, Some(Arc::clone(language_scopers))),
+    );
 
-    let is_readable_stdin = grep_cli::is_readable_stdin();
+    // When reviewing, stdin is reserved for answering prompts.
+    let is_readable_stdin = !options.review && grep_cli::is_readable_stdin();
     info!("Detected stdin as readable: {is_readable_stdin}.");
 
     // See where we're reading from
     let input = match (
         options.stdin_override_to.unwrap_or(is_readable_stdin),
-        options.glob.clone(),
-        &language_scopers,
+        if options.glob.is_empty() {
+            options.iglob.clone().map(|pattern| (vec![pattern], false))
+        } else {
+            Some((options.glob.clone(), true))
+        },
+        &path_scopers,
     ) {
+        (_, _, path_scopers) if options.rg_json => {
+            let mut json = String::new();
+            io::stdin().lock().read_to_string(&mut json)?;
+
+            // ripgrep searches all kinds of files, not only those of the language.
+            let validator: Validator = match path_scopers {
+                Some(language_scopers) => language_validator(
+                    Arc::clone(language_scopers),
+                    language,
+                    options.map_ext.clone(),
+                ),
+                None => Box::new(|_| true),
+            };
+            Input::Prescoped(parse_rg_json(&json)?, validator)
+        }
         // stdin considered viable: always use it.
         (true, None, _)
         // Nothing explicitly available: this should open an interactive stdin prompt.
@@ -130,53 +277,114 @@ fn main() -> Result<()> {
         }
 
         // When a pattern is specified, it takes precedence.
-        (false, Some(pattern), _) => Input::WalkOn(Box::new(move |path| {
-            let res = pattern.matches_path(path);
+        (false, Some((patterns, case_sensitive)), _) => Input::WalkOn(Box::new(move |path| {
+            let res = patterns.iter().any(|pattern| {
+                pattern.matches_path_with(
+                    path,
+                    glob::MatchOptions {
+                        case_sensitive,
+                        ..Default::default()
+                    },
+                )
+            });
             trace!("Path '{}' matches: {}.", path.display(), res);
             res
         })),
 
         // If pattern wasn't manually overridden, consult the language scoper itself, if
         // any.
-        (false, None, Some(language_scopers)) => {
-            let language_scopers = Arc::clone(language_scopers);
-            Input::WalkOn(Box::new(move |path| {
-                // TODO: perform this work only once (it's super fast but in the hot
-                // path).
-                let res = language_scopers
-                    .iter()
-                    .map(|s| s.is_valid_path(path))
-                    .all_equal_value()
-                    .expect("all language scopers to agree on path validity");
+        (false, None, Some(language_scopers)) => Input::WalkOn(language_validator(
+            Arc::clone(language_scopers),
+            language,
+            options.map_ext.clone(),
+        )),
+    };
 
-                trace!(
-                    "Language scoper considers path '{}' valid: {}",
-                    path.display(),
-                    res
-                );
-                res
-            }))
-        },
+    // Narrow down further, to files touched according to git.
+    let input = match (input, options.changed_files()) {
+        (input, None) => input,
+        (Input::Stdin, Some(..)) if options.stdin_override_to.unwrap_or(is_readable_stdin) => {
+            error!(
+                "Detected stdin, and request for changed files: will use stdin and ignore files."
+            );
+            Input::Stdin
+        }
+        (input, Some((reference, staged))) => {
+            let root = env::current_dir()?;
+            let changed = changed_files(&reference, staged, &root)?;
+            info!("Files changed relative to {reference}: {changed:?}");
+
+            // Paths are walked, or given by ripgrep, in all kinds of shapes.
+            let is_changed = move |path: &Path| changed.contains(&normalize_path(path, &root));
+            match input {
+                Input::Stdin => Input::WalkOn(Box::new(is_changed)),
+                Input::Prescoped(files, validator) => Input::Prescoped(
+                    files
+                        .into_iter()
+                        .filter(|(path, _)| is_changed(path))
+                        .collect(),
+                    validator,
+                ),
+                Input::WalkOn(validator) => {
+                    Input::WalkOn(Box::new(move |path| is_changed(path) && validator(path)))
+                }
+            }
+        }
     };
 
+    if options.blame().is_some() && matches!(input, Input::Stdin) {
+        cli::Args::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "Scoping via git blame requires working on files.",
+            )
+            .exit();
+    }
+
+    if options.review && matches!(input, Input::Stdin) {
+        cli::Args::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "Reviewing changes requires working on files.",
+            )
+            .exit();
+    }
+
+    if options.check {
+        options.dry_run = true;
+    }
+
+    if options.review {
+        // One prompt at a time.
+        options.sorted = true;
+    }
+
     // Only have this kick in if a language scoper is in play; otherwise, we'd just be a
     // poor imitation of ripgrep itself. Plus, this retains the `tr`-like behavior,
     // setting it apart from other utilities.
-    let search_mode = actions.is_empty() && language_scopers.is_some() || options.dry_run;
+    let search_mode = actions.is_empty()
+        && (language_scopers.is_some() || options.rg_json)
+        && !matches!(options.format, cli::OutputFormat::Patch)
+        || options.dry_run
+        || options.explain
+        || options.only_in_scope
+        || matches!(options.format, cli::OutputFormat::Vimgrep);
 
     if search_mode {
         info!("Will use search mode."); // Modelled after ripgrep!
 
-        let style = if options.dry_run {
-            Style::green_bold() // "Would change to this", like git diff
-        } else {
-            Style::red_bold() // "Found!", like ripgrep
-        };
-        actions.push(Box::new(style));
+        actions.push(Box::new(search_mode_style(&options)));
 
         options.only_matching = true;
-        options.line_numbers = true;
-        options.fail_none = true;
+        // Occurrences read from stdin need no location, but from files they do.
+        options.line_numbers = !(options.only_in_scope && matches!(input, Input::Stdin));
+
+        // Failing when nothing is found is the ripgrep-like default, but when asked to
+        // fail if *anything* is found, finding nothing is the passing case (CI gates).
+        // Same when checking, where only changes count.
+        if !options.fail_any && !options.check {
+            options.fail_none = true;
+        }
     }
 
     if actions.is_empty() && !search_mode {
@@ -194,20 +402,66 @@ fn main() -> Result<()> {
         vec![actions]
     };
 
-    let pipeline: Vec<&[Box<dyn Action>]> = pipeline.iter().map(Vec::as_slice).collect();
-    let language_scopers = language_scopers.unwrap_or_default();
+    // Workers processing files might be abandoned past `--timeout-per-file`, running
+    // on in the background while everything else moves on. What they use is hence
+    // needed up until exit; it is leaked instead of being torn down just before.
+    let pipeline: &'static [Vec<Box<dyn Action>>] = pipeline.leak();
+    let pipeline: Pipeline<'static> = pipeline.iter().map(Vec::as_slice).collect_vec().leak();
+    let general_scoper: &'static _ = Box::leak(Box::new(general_scoper));
+    let language_scopers: &'static [Box<dyn LanguageScoper>] =
+        Box::leak(Box::new(language_scopers.unwrap_or_default()));
+    let earlier_stages: &'static [Stage] = earlier_stages.leak();
+
+    let summary = Summary {
+        progress: match (&input, options.progress) {
+            (Input::WalkOn(validator), true) => {
+                let n_total = count_files(&env::current_dir()?, &options, validator);
+                Some(Progress::new(n_total))
+            }
+            _ => None,
+        },
+        files: options.report_json.is_some().then(Mutex::default),
+        ..Default::default()
+    };
 
     // Now write out
-    match (input, options.sorted) {
+    let res = match (input, options.sorted) {
+        (Input::Stdin, _ /* no effect */) if options.stream => {
+            info!("Will stream stdin to stdout line by line, applying actions.");
+            handle_actions_on_stdin_streaming(
+                &options,
+                standalone_action,
+                general_scoper,
+                pipeline,
+                &summary,
+            )
+        }
         (Input::Stdin, _ /* no effect */) => {
             info!("Will read from stdin and write to stdout, applying actions.");
             handle_actions_on_stdin(
                 &options,
                 standalone_action,
-                &general_scoper,
-                &language_scopers,
-                &pipeline,
-            )?;
+                general_scoper,
+                language_scopers,
+                earlier_stages,
+                pipeline,
+                search_mode,
+                &summary,
+            )
+        }
+        (Input::Prescoped(files, validator), _ /* always sorted */) => {
+            info!("Will work on files given upfront, applying actions.");
+            handle_actions_on_prescoped_files(
+                &options,
+                standalone_action,
+                &files,
+                &validator,
+                general_scoper,
+                language_scopers,
+                pipeline,
+                search_mode,
+                &summary,
+            )
         }
         (Input::WalkOn(validator), false) => {
             info!("Will walk file tree, applying actions.");
@@ -215,15 +469,17 @@ fn main() -> Result<()> {
                 &options,
                 standalone_action,
                 &validator,
-                &general_scoper,
-                &language_scopers,
-                &pipeline,
+                general_scoper,
+                language_scopers,
+                earlier_stages,
+                pipeline,
                 search_mode,
                 options.threads.map_or_else(
-                    || std::thread::available_parallelism().map_or(1, std::num::NonZero::get),
+                    || thread::available_parallelism().map_or(1, std::num::NonZero::get),
                     std::num::NonZero::get,
                 ),
-            )?;
+                &summary,
+            )
         }
         (Input::WalkOn(validator), true) => {
             info!("Will walk file tree, applying actions.");
@@ -231,22 +487,333 @@ fn main() -> Result<()> {
                 &options,
                 standalone_action,
                 &validator,
-                &general_scoper,
-                &language_scopers,
-                &pipeline,
+                general_scoper,
+                language_scopers,
+                earlier_stages,
+                pipeline,
                 search_mode,
-            )?;
+                &summary,
+            )
         }
     };
 
+    if let Some(progress) = &summary.progress {
+        progress.finish();
+    }
+    if options.summary {
+        summary.report(&mut io::stderr().lock(), start.elapsed(), search_mode)?;
+    }
+    if let Some(path) = &options.report_json {
+        summary
+            .report_json(
+                &mut io::BufWriter::new(File::create(path)?),
+                start.elapsed(),
+                res.as_ref().err().map(ToString::to_string),
+            )
+            .with_context(|| format!("Failed writing report to {}", path.display()))?;
+    }
+    res?;
+
+    if options.check && summary.scopes_changed.load(Ordering::Relaxed) > 0 {
+        return Err(ProgramError::SomethingWouldChange.into());
+    }
+
     info!("Done, exiting");
     Ok(())
 }
 
+/// Expands references to environment variables (`$VAR`, `${VAR}`) in `input`.
+///
+/// References to unset variables are left alone, such that they may still refer to
+/// capture groups later on. So is `$$`.
+fn expand_env_vars(input: &str) -> String {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        let (name, len) = rest.strip_prefix("${").map_or_else(
+            || {
+                let name = rest[1..]
+                    .find(|c| !is_name_char(c))
+                    .map_or(&rest[1..], |end| &rest[1..=end]);
+                (name, name.len() + 1)
+            },
+            |braced| {
+                braced
+                    .find('}')
+                    .map_or(("", 0), |end| (&braced[..end], end + "${}".len()))
+            },
+        );
+
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(is_name_char);
+        let (expansion, consumed) = valid.then(|| env::var(name).ok()).flatten().map_or_else(
+            || {
+                // Not expanded: keep as is, `$$` included.
+                let keep = if rest.starts_with("$$") { 2 } else { 1 };
+                (Cow::Borrowed(&rest[..keep]), keep)
+            },
+            |value| {
+                trace!("Expanding environment variable '{name}'");
+                (Cow::Owned(value), len)
+            },
+        );
+        out.push_str(&expansion);
+        rest = &rest[consumed..];
+    }
+    out.push_str(rest);
+
+    out
+}
+
+/// The style to render results in, in search mode.
+fn search_mode_style(global_options: &cli::GlobalOptions) -> Style {
+    if global_options.dry_run {
+        Style::green_bold() // "Would change to this", like git diff
+    } else {
+        Style::red_bold() // "Found!", like ripgrep
+    }
+}
+
+/// Counters collected over the course of a run, reported via `--summary`.
+///
+/// Shared across threads, hence atomic.
+#[derive(Debug, Default)]
+struct Summary {
+    /// Files seen, i.e. valid and attempted to be processed.
+    files_seen: AtomicUsize,
+    /// Files written to, as their contents changed.
+    files_changed: AtomicUsize,
+    /// Scopes found to be in scope.
+    scopes_matched: AtomicUsize,
+    /// In-scope items whose contents were changed by actions.
+    scopes_changed: AtomicUsize,
+    /// Live progress, if requested.
+    progress: Option<Progress>,
+    /// Outcomes of individual files, if requested (`--report-json`).
+    files: Option<Mutex<Vec<FileReport>>>,
+}
+
+impl Summary {
+    /// Records that the file at `path` is about to be processed.
+    fn see_file(&self, path: &Path) {
+        self.files_seen.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(progress) = &self.progress {
+            progress.advance(path);
+        }
+    }
+
+    /// Adds the counters of `other` to these.
+    fn add(&self, other: &Self) {
+        for (total, count) in [
+            (&self.files_changed, &other.files_changed),
+            (&self.scopes_matched, &other.scopes_matched),
+            (&self.scopes_changed, &other.scopes_changed),
+        ] {
+            total.fetch_add(count.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+
+    /// Records the outcome `res` of processing the file at `path`, with its counters
+    /// collected in `file_summary`.
+    fn record_file(
+        &self,
+        path: &Path,
+        file_summary: &Self,
+        res: &std::result::Result<(), PathProcessingError>,
+        elapsed: Duration,
+    ) {
+        self.add(file_summary);
+        let load = |counter: &AtomicUsize| counter.load(Ordering::Relaxed);
+
+        let Some(files) = &self.files else {
+            return;
+        };
+
+        let (status, reason) = match res {
+            Ok(()) if load(&file_summary.files_changed) > 0 => (FileStatus::Changed, None),
+            Ok(())
+            | Err(PathProcessingError::ApplicationError(
+                ApplicationError::NoneInScope | ApplicationError::SomeInScope,
+            )) => (FileStatus::Unchanged, None),
+            Err(PathProcessingError::IoError(e, _)) if e.kind() == io::ErrorKind::InvalidData => (
+                FileStatus::Skipped,
+                Some("unreadable data (binary? invalid utf-8?)".to_owned()),
+            ),
+            Err(
+                e @ (PathProcessingError::TooLarge { .. }
+                | PathProcessingError::ApplicationError(
+                    ApplicationError::TimedOut(..) | ApplicationError::SyntaxBroken,
+                )),
+            ) => (FileStatus::Skipped, Some(e.to_string())),
+            Err(e) => (FileStatus::Error, Some(e.to_string())),
+        };
+
+        files.lock().unwrap().push(FileReport {
+            path: path.to_owned(),
+            status,
+            reason,
+            scopes_matched: load(&file_summary.scopes_matched),
+            scopes_changed: load(&file_summary.scopes_changed),
+            elapsed_secs: elapsed.as_secs_f64(),
+        });
+    }
+
+    /// Writes a machine-readable report to `destination`, as JSON.
+    ///
+    /// Files are sorted by path, for stable output regardless of processing order.
+    fn report_json(
+        &self,
+        destination: &mut impl Write,
+        elapsed: Duration,
+        error: Option<String>,
+    ) -> io::Result<()> {
+        let load = |counter: &AtomicUsize| counter.load(Ordering::Relaxed);
+        let mut files = self
+            .files
+            .as_ref()
+            .map(|files| mem::take(&mut *files.lock().unwrap()))
+            .unwrap_or_default();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let report = RunReport {
+            files_seen: load(&self.files_seen),
+            files_changed: load(&self.files_changed),
+            scopes_matched: load(&self.scopes_matched),
+            scopes_changed: load(&self.scopes_changed),
+            elapsed_secs: elapsed.as_secs_f64(),
+            error,
+            files,
+        };
+
+        serde_json::to_writer_pretty(&mut *destination, &report)?;
+        writeln!(destination)
+    }
+
+    /// Writes a human-readable report to `destination`.
+    ///
+    /// In search mode, nothing is ever changed, so those counters are omitted.
+    fn report(
+        &self,
+        destination: &mut impl Write,
+        elapsed: Duration,
+        search_mode: bool,
+    ) -> io::Result<()> {
+        writeln!(destination, "Summary:")?;
+        writeln!(
+            destination,
+            "  Files scanned: {}",
+            self.files_seen.load(Ordering::Relaxed)
+        )?;
+        if !search_mode {
+            writeln!(
+                destination,
+                "  Files changed: {}",
+                self.files_changed.load(Ordering::Relaxed)
+            )?;
+        }
+        writeln!(
+            destination,
+            "  Scopes matched: {}",
+            self.scopes_matched.load(Ordering::Relaxed)
+        )?;
+        if !search_mode {
+            writeln!(
+                destination,
+                "  Replacements made: {}",
+                self.scopes_changed.load(Ordering::Relaxed)
+            )?;
+        }
+        writeln!(destination, "  Elapsed: {:.3}s", elapsed.as_secs_f64())
+    }
+}
+
+/// The report of an entire run, written via `--report-json`.
+#[derive(Debug, Serialize)]
+struct RunReport {
+    files_seen: usize,
+    files_changed: usize,
+    scopes_matched: usize,
+    scopes_changed: usize,
+    elapsed_secs: f64,
+    /// The error the run failed with, if any.
+    error: Option<String>,
+    files: Vec<FileReport>,
+}
+
+/// The report of a single file, see [`RunReport`].
+#[derive(Debug, Serialize)]
+struct FileReport {
+    path: PathBuf,
+    status: FileStatus,
+    /// Why the file was skipped or errored.
+    reason: Option<String>,
+    scopes_matched: usize,
+    scopes_changed: usize,
+    elapsed_secs: f64,
+}
+
+/// The outcome of processing a single file.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum FileStatus {
+    /// Written to, as its contents changed.
+    Changed,
+    /// Processed, but nothing was written.
+    Unchanged,
+    /// Not processed, for example binary files.
+    Skipped,
+    /// Processing failed.
+    Error,
+}
+
 /// Indicates whether a filesystem path is valid according to some criteria (glob
 /// pattern, ...).
 type Validator = Box<dyn Fn(&Path) -> bool + Send + Sync>;
 
+/// A [`Validator`] accepting paths of files `language_scopers` apply to, with
+/// `extension_mappings` overriding which `language` an extension belongs to.
+fn language_validator(
+    language_scopers: Arc<ScoperList>,
+    language: Option<&'static str>,
+    extension_mappings: Vec<cli::ExtensionMapping>,
+) -> Validator {
+    Box::new(move |path| {
+        let mapping = path.extension().and_then(|ext| {
+            // Later mappings take precedence.
+            extension_mappings
+                .iter()
+                .rev()
+                .find(|m| ext == m.extension.as_str())
+        });
+
+        // TODO: perform this work only once (it's super fast but in the hot
+        // path).
+        let res = mapping.map_or_else(
+            || {
+                language_scopers
+                    .iter()
+                    .map(|s| s.is_valid_path(path))
+                    .all_equal_value()
+                    .expect("all language scopers to agree on path validity")
+            },
+            |m| Some(m.language) == language,
+        );
+
+        trace!(
+            "Language scoper considers path '{}' valid: {}",
+            path.display(),
+            res
+        );
+        res
+    })
+}
+
 /// The input to read from.
 enum Input {
     /// Standard input.
@@ -255,6 +822,83 @@ enum Input {
     /// indicates valid filesystem entries. This is similar to globbing, but more
     /// flexible.
     WalkOn(Validator),
+    /// Files given upfront, each alongside the ranges initially in scope, and the
+    /// validator they need to pass still.
+    Prescoped(Vec<(PathBuf, Ranges<usize>)>, Validator),
+}
+
+/// A single message of `rg --json` output, see
+/// <https://docs.rs/grep-printer/0.2.2/grep_printer/struct.JSON.html>.
+///
+/// Only matches are of interest, see [`RgMatch`].
+#[derive(Debug, Deserialize)]
+struct RgMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    data: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct RgMatch {
+    path: RgData,
+    /// Byte offset of the start of the matched line(s) into the file.
+    absolute_offset: usize,
+    submatches: Vec<RgSubmatch>,
+}
+
+/// Arbitrary data, which is only given as `text` if valid UTF-8 (as base64-encoded
+/// `bytes` otherwise).
+#[derive(Debug, Deserialize)]
+struct RgData {
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RgSubmatch {
+    /// Byte offset into the matched line(s).
+    start: usize,
+    end: usize,
+}
+
+/// Parses `rg --json` output into the files it reports, alongside the byte ranges
+/// matched in each, in order.
+fn parse_rg_json(input: &str) -> Result<Vec<(PathBuf, Ranges<usize>)>> {
+    let mut files: Vec<(PathBuf, Vec<std::ops::Range<usize>>)> = Vec::new();
+
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let context = || format!("Failed parsing ripgrep JSON on line {}", i + 1);
+        let message: RgMessage = serde_json::from_str(line).with_context(context)?;
+        if message.kind != "match" {
+            continue;
+        }
+        let m: RgMatch = serde_json::from_value(message.data).with_context(context)?;
+
+        let Some(path) = m.path.text else {
+            warn!("Skipping match in file with non-UTF-8 path.");
+            continue;
+        };
+        let path = PathBuf::from(path);
+        let ranges = m
+            .submatches
+            .iter()
+            .map(|sm| m.absolute_offset + sm.start..m.absolute_offset + sm.end);
+
+        // Matches are grouped by file.
+        match files.last_mut() {
+            Some((last, existing)) if *last == path => existing.extend(ranges),
+            _ => files.push((path, ranges.collect())),
+        }
+    }
+
+    debug!("Parsed ripgrep JSON into: {files:?}");
+    Ok(files
+        .into_iter()
+        .map(|(path, ranges)| (path, ranges.into_iter().collect()))
+        .collect())
 }
 
 /// A standalone action to perform on the results of applying a scope.
@@ -280,29 +924,125 @@ type Pipeline<'a> = &'a [&'a [Box<dyn Action>]];
 
 /// Main entrypoint for simple `stdin` -> `stdout` processing.
 #[allow(clippy::borrowed_box)] // Used throughout, not much of a pain
+#[allow(clippy::too_many_arguments)]
 fn handle_actions_on_stdin(
     global_options: &cli::GlobalOptions,
     standalone_action: StandaloneAction,
-    general_scoper: &Box<dyn Scoper>,
-    language_scopers: &[Box<dyn LanguageScoper>],
-    pipeline: Pipeline<'_>,
+    general_scoper: &'static Box<dyn Scoper>,
+    language_scopers: &'static [Box<dyn LanguageScoper>],
+    stages: &'static [Stage],
+    pipeline: Pipeline<'static>,
+    search_mode: bool,
+    summary: &Summary,
 ) -> Result<(), ProgramError> {
     info!("Will use stdin to stdout.");
     let mut source = String::new();
     io::stdin().lock().read_to_string(&mut source)?;
     let mut destination = String::with_capacity(source.len());
 
-    apply(
+    let changed = apply(
         global_options,
         standalone_action,
         &source,
         &mut destination,
         general_scoper,
         language_scopers,
+        stages,
+        &[],
         pipeline,
+        summary,
     )?;
 
-    stdout().lock().write_all(destination.as_bytes())?;
+    if global_options.verify_syntax && changed && !search_mode {
+        verify_syntax(&source, &destination, language_scopers)?;
+    }
+
+    let mut stdout = stdout().lock();
+    match global_options.format {
+        cli::OutputFormat::Default => stdout.write_all(destination.as_bytes())?,
+        cli::OutputFormat::Patch => {
+            if changed {
+                write_patch(Path::new("<stdin>"), &source, &destination, &mut stdout)?;
+            }
+        }
+        cli::OutputFormat::Vimgrep => {
+            // Like ripgrep, so editors parsing this have *some* file name to go by.
+            for line in destination.lines() {
+                writeln!(stdout, "<stdin>:{line}")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Entrypoint for `stdin` -> `stdout` processing, line by line.
+///
+/// Each line is processed and written out on its own, so memory use is bounded by the
+/// longest line instead of the entire input.
+#[allow(clippy::borrowed_box)] // Used throughout, not much of a pain
+fn handle_actions_on_stdin_streaming(
+    global_options: &cli::GlobalOptions,
+    standalone_action: StandaloneAction,
+    general_scoper: &'static Box<dyn Scoper>,
+    pipeline: Pipeline<'static>,
+    summary: &Summary,
+) -> Result<(), ProgramError> {
+    info!("Will stream stdin to stdout.");
+
+    // Whether *anything* was in scope can only be known once all lines are through.
+    let line_options = cli::GlobalOptions {
+        fail_none: false,
+        line_mode: true,
+        ..global_options.clone()
+    };
+
+    let stdin = io::stdin();
+    let mut stdout = stdout().lock();
+
+    let mut source = String::new();
+    let mut destination = String::new();
+    let mut any_in_scope = false;
+    loop {
+        source.clear();
+        if stdin.read_line(&mut source)? == 0 {
+            break;
+        }
+
+        destination.clear();
+        apply(
+            &line_options,
+            standalone_action,
+            &source,
+            &mut destination,
+            general_scoper,
+            &[],
+            &[],
+            &[],
+            pipeline,
+            summary,
+        )?;
+
+        if global_options.fail_none && !any_in_scope {
+            // Re-scoping is wasted work, but only until the first hit.
+            any_in_scope = build_view(
+                &line_options,
+                &source,
+                general_scoper,
+                &[],
+                &[],
+                &mut |_, _, _| (),
+            )
+            .has_any_in_scope();
+        }
+
+        stdout.write_all(destination.as_bytes())?;
+        stdout.flush()?;
+    }
+
+    if global_options.fail_none && !any_in_scope {
+        return Err(ApplicationError::NoneInScope.into());
+    }
 
     Ok(())
 }
@@ -316,14 +1056,18 @@ fn handle_actions_on_stdin(
 /// [ripgrep]:
 ///     https://github.com/BurntSushi/ripgrep/blob/71d71d2d98964653cdfcfa315802f518664759d7/GUIDE.md#L1016-L1017
 #[allow(clippy::borrowed_box)] // Used throughout, not much of a pain
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::too_many_lines)] // Only slightly above.
 fn handle_actions_on_many_files_sorted(
     global_options: &cli::GlobalOptions,
     standalone_action: StandaloneAction,
     validator: &Validator,
-    general_scoper: &Box<dyn Scoper>,
-    language_scopers: &[Box<dyn LanguageScoper>],
-    pipeline: Pipeline<'_>,
+    general_scoper: &'static Box<dyn Scoper>,
+    language_scopers: &'static [Box<dyn LanguageScoper>],
+    stages: &'static [Stage],
+    pipeline: Pipeline<'static>,
     search_mode: bool,
+    summary: &Summary,
 ) -> Result<(), ProgramError> {
     let root = env::current_dir()?;
     info!(
@@ -333,9 +1077,7 @@ fn handle_actions_on_many_files_sorted(
 
     let mut n_files_processed: usize = 0;
     let mut n_files_seen: usize = 0;
-    for entry in WalkBuilder::new(&root)
-        .hidden(!global_options.hidden)
-        .git_ignore(!global_options.gitignored)
+    for entry in new_walk_builder(&root, global_options)
         .sort_by_file_path(Ord::cmp)
         .build()
     {
@@ -350,8 +1092,11 @@ fn handle_actions_on_many_files_sorted(
                     validator,
                     general_scoper,
                     language_scopers,
+                    stages,
+                    None,
                     pipeline,
                     search_mode,
+                    summary,
                 );
 
                 n_files_seen += match res {
@@ -391,6 +1136,24 @@ fn handle_actions_on_many_files_sorted(
                         warn!("File contains unreadable data (binary? invalid utf-8?), skipped: {}", path.display());
                         0
                     }
+                    Err(
+                        e @ (PathProcessingError::TooLarge { .. }
+                        | PathProcessingError::ApplicationError(
+                            ApplicationError::TimedOut(..) | ApplicationError::SyntaxBroken,
+                        )),
+                    ) => {
+                        warn!("{e}, skipped: {}", path.display());
+                        0
+                    }
+                    // Nothing more is getting done, search mode or not.
+                    Err(
+                        e @ PathProcessingError::ApplicationError(
+                            ApplicationError::TooManyTimedOut(..),
+                        ),
+                    ) => {
+                        error!("Aborting walk at {} due to: {}", path.display(), e);
+                        return Err(e.into());
+                    }
 
                     // Hard errors we should do something about:
                     Err(
@@ -432,6 +1195,91 @@ fn handle_actions_on_many_files_sorted(
     }
 }
 
+/// Entrypoint for processing files given upfront, alongside the ranges initially in
+/// scope for each (see `--rg-json`).
+///
+/// Files are processed sequentially, in the order given.
+#[allow(clippy::borrowed_box)] // Used throughout, not much of a pain
+#[allow(clippy::too_many_arguments)]
+fn handle_actions_on_prescoped_files(
+    global_options: &cli::GlobalOptions,
+    standalone_action: StandaloneAction,
+    files: &[(PathBuf, Ranges<usize>)],
+    validator: &Validator,
+    general_scoper: &'static Box<dyn Scoper>,
+    language_scopers: &'static [Box<dyn LanguageScoper>],
+    pipeline: Pipeline<'static>,
+    search_mode: bool,
+    summary: &Summary,
+) -> Result<(), ProgramError> {
+    let root = env::current_dir()?;
+
+    let mut n_files_processed: usize = 0;
+    for (path, ranges) in files {
+        let res = process_path(
+            global_options,
+            standalone_action,
+            &root.join(path),
+            &root,
+            validator,
+            general_scoper,
+            language_scopers,
+            &[],
+            Some(ranges),
+            pipeline,
+            search_mode,
+            summary,
+        );
+
+        match res {
+            Ok(()) => n_files_processed += 1,
+            Err(PathProcessingError::NotAFile) => {
+                warn!("Skipping {}, which is not a file.", path.display());
+            }
+            Err(PathProcessingError::InvalidFile) => {
+                debug!("Skipping {}, which is not of the language.", path.display());
+            }
+            Err(PathProcessingError::ApplicationError(ApplicationError::SomeInScope))
+                if global_options.fail_any =>
+            {
+                info!("Match at {}, exiting early", path.display());
+                return Err(ProgramError::SomethingProcessed);
+            }
+            Err(PathProcessingError::ApplicationError(
+                ApplicationError::NoneInScope | ApplicationError::SomeInScope,
+            )) => {}
+            Err(PathProcessingError::IoError(e, _))
+                if e.kind() == io::ErrorKind::BrokenPipe && search_mode =>
+            {
+                trace!("Detected broken pipe, stopping search.");
+                break;
+            }
+            Err(
+                e @ (PathProcessingError::TooLarge { .. }
+                | PathProcessingError::ApplicationError(
+                    ApplicationError::TimedOut(..) | ApplicationError::SyntaxBroken,
+                )),
+            ) => {
+                warn!("{e}, skipped: {}", path.display());
+            }
+            Err(e) => {
+                error!("Aborting at {} due to: {}", path.display(), e);
+                return Err(e.into());
+            }
+        }
+    }
+
+    info!("Processed {} files", n_files_processed);
+
+    if files.is_empty() && global_options.fail_no_files {
+        Err(ProgramError::NoFilesFound)
+    } else if n_files_processed == 0 && global_options.fail_none {
+        Err(ProgramError::NothingProcessed)
+    } else {
+        Ok(())
+    }
+}
+
 /// Main entrypoint for processing using at least 1 thread.
 #[allow(clippy::borrowed_box)] // Used throughout, not much of a pain
 #[allow(clippy::too_many_lines)]
@@ -440,11 +1288,13 @@ fn handle_actions_on_many_files_threaded(
     global_options: &cli::GlobalOptions,
     standalone_action: StandaloneAction,
     validator: &Validator,
-    general_scoper: &Box<dyn Scoper>,
-    language_scopers: &[Box<dyn LanguageScoper>],
-    pipeline: Pipeline<'_>,
+    general_scoper: &'static Box<dyn Scoper>,
+    language_scopers: &'static [Box<dyn LanguageScoper>],
+    stages: &'static [Stage],
+    pipeline: Pipeline<'static>,
     search_mode: bool,
     n_threads: usize,
+    summary: &Summary,
 ) -> Result<(), ProgramError> {
     let root = env::current_dir()?;
     info!(
@@ -457,13 +1307,11 @@ fn handle_actions_on_many_files_threaded(
     let n_files_seen = Arc::new(Mutex::new(0usize));
     let err: Arc<Mutex<Option<ProgramError>>> = Arc::new(Mutex::new(None));
 
-    WalkBuilder::new(&root)
+    new_walk_builder(&root, global_options)
         .threads(
             // https://github.com/BurntSushi/ripgrep/issues/2854
             n_threads,
         )
-        .hidden(!global_options.hidden)
-        .git_ignore(!global_options.gitignored)
         .build_parallel()
         .run(|| {
             Box::new(|entry| match entry {
@@ -477,8 +1325,11 @@ fn handle_actions_on_many_files_threaded(
                         validator,
                         general_scoper,
                         language_scopers,
+                        stages,
+                        None,
                         pipeline,
                         search_mode,
+                        summary,
                     );
 
                     match res {
@@ -524,6 +1375,25 @@ fn handle_actions_on_many_files_threaded(
                             warn!("File contains unreadable data (binary? invalid utf-8?), skipped: {}", path.display());
                             WalkState::Continue
                         }
+                        Err(
+                            e @ (PathProcessingError::TooLarge { .. }
+                            | PathProcessingError::ApplicationError(
+                                ApplicationError::TimedOut(..) | ApplicationError::SyntaxBroken,
+                            )),
+                        ) => {
+                            warn!("{e}, skipped: {}", path.display());
+                            WalkState::Continue
+                        }
+                        // Nothing more is getting done, search mode or not.
+                        Err(
+                            e @ PathProcessingError::ApplicationError(
+                                ApplicationError::TooManyTimedOut(..),
+                            ),
+                        ) => {
+                            error!("Aborting walk at {} due to: {}", path.display(), e);
+                            *err.lock().unwrap() = Some(e.into());
+                            WalkState::Quit
+                        }
 
                         // Hard errors we should do something about:
                         Err(
@@ -576,6 +1446,227 @@ fn handle_actions_on_many_files_threaded(
     }
 }
 
+/// Collects files changed relative to the given git `reference`, or only those
+/// changes which are `staged`.
+///
+/// Paths are relative to the current directory, and limited to files below it. Deleted
+/// files are not included.
+fn changed_files(reference: &str, staged: bool, root: &Path) -> Result<HashSet<PathBuf>> {
+    let mut cmd = std::process::Command::new("git");
+    // Unquoted, NUL-separated names, relative to the current directory.
+    cmd.args(["diff", "--name-only", "-z", "--relative", "--diff-filter=d"]);
+    if staged {
+        cmd.arg("--cached");
+    }
+    cmd.args([reference, "--"]);
+
+    debug!("Listing changed files via: {cmd:?}");
+    let output = cmd.output().context("Failed running git")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed listing changed files via git: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8(output.stdout)
+        .context("git returned non-UTF-8 file names")?
+        .split_terminator('\0')
+        .map(|path| normalize_path(Path::new(path), root))
+        .collect())
+}
+
+/// Normalizes `path` for comparison with paths from other sources (walking, ripgrep,
+/// git, ...): relative to `root` if absolute, and without any `.` components.
+fn normalize_path(path: &Path, root: &Path) -> PathBuf {
+    let path = if path.is_absolute() {
+        diff_paths(path, root).unwrap_or_else(|| path.to_owned())
+    } else {
+        path.to_owned()
+    };
+
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect()
+}
+
+/// Collects the (1-based) numbers of lines in the file at `path` last touched by an
+/// `author` matching the given pattern, and/or after the given `since` date, according
+/// to `git blame`.
+///
+/// Authors are matched against `Name <email>`, as in `git log --author`. Dates are
+/// anything git understands, such as `2024-01-31` or `2.weeks`. Lines not committed
+/// yet count as touched by `Not Committed Yet`, just now.
+fn blamed_lines(
+    path: &Path,
+    author: Option<&RegexPattern>,
+    since: Option<&str>,
+) -> io::Result<HashSet<usize>> {
+    let mut cmd = std::process::Command::new("git");
+    cmd.args(["blame", "--line-porcelain"]);
+    if let Some(since) = since {
+        cmd.arg(format!("--since={since}"));
+    }
+    cmd.arg("--").arg(path);
+
+    debug!("Blaming via: {cmd:?}");
+    let output = cmd.output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "failed running git blame: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let mut lines = HashSet::new();
+    let mut line_number = 0;
+    let mut name = "";
+    let mut mail = "";
+    let mut boundary = false;
+    // Commit metadata is not necessarily UTF-8, and neither is content.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for line in stdout.lines() {
+        if line.starts_with('\t') {
+            // Content of the line: all its metadata is in.
+            let is_author = author.map_or(true, |author| {
+                // Backtracking limit exceeded: conservatively leave out.
+                author.is_match(&format!("{name} {mail}")).unwrap_or(false)
+            });
+            // Lines before `since` are attributed to 'boundary' commits.
+            let is_recent = since.is_none() || !boundary;
+
+            if is_author && is_recent {
+                lines.insert(line_number);
+            }
+        } else if let Some(value) = line.strip_prefix("author ") {
+            name = value;
+        } else if let Some(value) = line.strip_prefix("author-mail ") {
+            mail = value;
+        } else if line == "boundary" {
+            boundary = true;
+        } else if let Some((sha, rest)) = line.split_once(' ') {
+            if sha.len() >= 40 && sha.bytes().all(|b| b.is_ascii_hexdigit()) {
+                // Header of a new line: `<sha> <original line> <final line> ...`
+                line_number = rest
+                    .split(' ')
+                    .nth(1)
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or_default();
+                boundary = false;
+            }
+        }
+    }
+
+    trace!("Blamed lines of {}: {:?}", path.display(), lines);
+    Ok(lines)
+}
+
+/// Live progress over a file walk, printed to stderr.
+///
+/// On a terminal, a single line is kept updating. Otherwise, such as when redirected
+/// to a log file, each file gets a plain line of its own.
+#[derive(Debug)]
+struct Progress {
+    /// Total number of files expected to be processed.
+    n_total: usize,
+    /// Number of files processed so far. Also serves as a lock, so concurrent updates
+    /// do not garble the output.
+    n_done: Mutex<usize>,
+    /// Whether stderr is a terminal, so the progress line can be updated in place.
+    interactive: bool,
+}
+
+impl Progress {
+    fn new(n_total: usize) -> Self {
+        Self {
+            n_total,
+            n_done: Mutex::new(0),
+            interactive: io::stderr().is_terminal(),
+        }
+    }
+
+    /// Advance by one file, displaying its `path`.
+    #[allow(clippy::significant_drop_tightening)] // Held on purpose while writing
+    fn advance(&self, path: &Path) {
+        let mut n_done = self.n_done.lock().unwrap();
+        *n_done += 1;
+
+        let mut stderr = io::stderr().lock();
+        let res = if self.interactive {
+            // Clear the line, then return to its start: keeps a single, updating line.
+            write!(
+                stderr,
+                "\x1b[2K\r[{}/{}] {}",
+                *n_done,
+                self.n_total,
+                path.display()
+            )
+        } else {
+            writeln!(stderr, "[{}/{}] {}", *n_done, self.n_total, path.display())
+        };
+
+        if let Err(e) = res {
+            trace!("Failed to report progress: {e}");
+        }
+    }
+
+    /// Finish up, moving off the progress line (if any was printed).
+    fn finish(&self) {
+        if !self.interactive || *self.n_done.lock().unwrap() == 0 {
+            return;
+        }
+
+        if let Err(e) = writeln!(io::stderr().lock()) {
+            trace!("Failed to report progress: {e}");
+        }
+    }
+}
+
+/// Counts the files a walk would process, for progress reporting.
+///
+/// This walks the tree once upfront, which is cheap compared to processing it.
+fn count_files(root: &Path, global_options: &cli::GlobalOptions, validator: &Validator) -> usize {
+    new_walk_builder(root, global_options)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| diff_paths(entry.path(), root))
+        .filter(|path| validator(path))
+        .count()
+}
+
+/// Creates a directory walker starting at `root`, configured according to the passed
+/// options (hidden files, depth, exclusions, ...).
+fn new_walk_builder(root: &Path, global_options: &cli::GlobalOptions) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .hidden(!global_options.hidden)
+        .git_ignore(!global_options.gitignored)
+        .max_depth(global_options.max_depth);
+
+    if !global_options.exclude.is_empty() {
+        let root = root.to_owned();
+        let patterns = global_options.exclude.clone();
+
+        builder.filter_entry(move |entry| {
+            let Some(path) = diff_paths(entry.path(), &root) else {
+                return true;
+            };
+
+            let excluded = patterns.iter().any(|pattern| pattern.matches_path(&path));
+            if excluded {
+                trace!("Path '{}' excluded, skipping.", path.display());
+            }
+
+            !excluded
+        });
+    }
+
+    builder
+}
+
 #[allow(clippy::too_many_arguments)]
 #[allow(clippy::borrowed_box)] // Used throughout, not much of a pain
 fn process_path(
@@ -584,10 +1675,13 @@ fn process_path(
     path: &Path,
     root: &Path,
     validator: &Validator,
-    general_scoper: &Box<dyn Scoper>,
-    language_scopers: &[Box<dyn LanguageScoper>],
-    pipeline: Pipeline<'_>,
+    general_scoper: &'static Box<dyn Scoper>,
+    language_scopers: &'static [Box<dyn LanguageScoper>],
+    stages: &'static [Stage],
+    pre_scope: Option<&Ranges<usize>>,
+    pipeline: Pipeline<'static>,
     search_mode: bool,
+    summary: &Summary,
 ) -> std::result::Result<(), PathProcessingError> {
     if !path.is_file() {
         trace!("Skipping path (not a file): {:?}", path);
@@ -602,14 +1696,62 @@ fn process_path(
     }
 
     debug!("Processing path: {:?}", path);
+    summary.see_file(&path);
+
+    let start = Instant::now();
+    let file_summary = Summary::default();
+    let res = process_file(
+        global_options,
+        standalone_action,
+        &path,
+        general_scoper,
+        language_scopers,
+        stages,
+        pre_scope,
+        pipeline,
+        search_mode,
+        &file_summary,
+    );
+    summary.record_file(&path, &file_summary, &res, start.elapsed());
 
-    let (new_contents, filesize, changed) = {
-        let mut file = File::open(&path)?;
+    res
+}
 
-        let filesize = file.metadata().map_or(0, |m| m.len());
-        let mut source =
-            String::with_capacity(filesize.try_into().unwrap_or(/* no perf gains for you */ 0));
-        file.read_to_string(&mut source)?;
+/// Processes the single, valid file at `path`, see [`process_path`].
+///
+/// Counters are recorded into `summary`, which is specific to this file.
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::too_many_lines)] // Only slightly above.
+#[allow(clippy::borrowed_box)] // Used throughout, not much of a pain
+fn process_file(
+    global_options: &cli::GlobalOptions,
+    standalone_action: StandaloneAction,
+    path: &Path,
+    general_scoper: &'static Box<dyn Scoper>,
+    language_scopers: &'static [Box<dyn LanguageScoper>],
+    stages: &'static [Stage],
+    pre_scope: Option<&Ranges<usize>>,
+    pipeline: Pipeline<'static>,
+    search_mode: bool,
+    summary: &Summary,
+) -> std::result::Result<(), PathProcessingError> {
+    let (source, new_contents, filesize, metadata, changed) = {
+        let file = File::open(path)?;
+
+        let metadata = file.metadata().ok();
+        let filesize = metadata.as_ref().map_or(0, fs::Metadata::len);
+        if let Some(cli::ByteSize(limit)) = global_options.max_file_size {
+            if filesize > limit {
+                return Err(PathProcessingError::TooLarge {
+                    size: filesize,
+                    limit,
+                });
+            }
+        }
+
+        let source = FileSource::read(&file, filesize, global_options.mmap)?;
+
+        let pre_scopes = file_pre_scopes(global_options, path, &source, pre_scope)?;
 
         let mut destination = String::with_capacity(source.len());
 
@@ -620,17 +1762,30 @@ fn process_path(
             &mut destination,
             general_scoper,
             language_scopers,
+            stages,
+            &pre_scopes,
             pipeline,
+            summary,
         )?;
 
-        (destination, filesize, changed)
+        if global_options.verify_syntax && changed && !search_mode {
+            verify_syntax(&source, &destination, language_scopers)?;
+        }
+
+        (source, destination, filesize, metadata, changed)
     };
 
     // Hold the lock so results aren't intertwined
     let mut stdout = stdout().lock();
 
     if search_mode {
-        if !new_contents.is_empty() {
+        if global_options.only_in_scope
+            || matches!(global_options.format, cli::OutputFormat::Vimgrep)
+        {
+            for line in new_contents.lines() {
+                writeln!(stdout, "{}:{}", path.display(), line)?;
+            }
+        } else if !new_contents.is_empty() {
             writeln!(
                 stdout,
                 "{}\n{}",
@@ -638,7 +1793,28 @@ fn process_path(
                 &new_contents
             )?;
         }
+    } else if matches!(global_options.format, cli::OutputFormat::Patch) {
+        if changed {
+            write_patch(path, &source, &new_contents, &mut stdout)?;
+        }
+    } else if global_options.stdout {
+        let heading = (!global_options.no_heading).then_some(path);
+        write_file_to_stdout(heading, &new_contents, &mut stdout)?;
     } else {
+        let (new_contents, changed) = if global_options.review && changed {
+            let reviewed = review_changes(
+                path,
+                &source,
+                &new_contents,
+                &mut io::stdin().lock(),
+                &mut stdout,
+            )?;
+            let changed = *reviewed != *source;
+            (reviewed, changed)
+        } else {
+            (new_contents, changed)
+        };
+
         if filesize > 0 && new_contents.is_empty() {
             error!(
                     "Failsafe triggered: file {} is nonempty ({} bytes), but new contents are empty. Will not wipe file.",
@@ -660,7 +1836,14 @@ fn process_path(
                 // extra safety.
                 "Dry running, but attempted to write file!"
             );
-            fs::write(&path, new_contents.as_bytes())?;
+            // Unmap (if mapped) before overwriting the very file the map is backed by.
+            drop(source);
+            fs::write(path, new_contents.as_bytes())?;
+
+            if let Some(metadata) = metadata {
+                restore_metadata(path, &metadata, global_options.keep_mtime)?;
+            }
+            summary.files_changed.fetch_add(1, Ordering::Relaxed);
 
             // Confirm after successful processing.
             writeln!(stdout, "{}", path.display())?;
@@ -671,107 +1854,1059 @@ fn process_path(
             );
         }
 
-        debug!("Done processing file: {:?}", path);
-    };
+        debug!("Done processing file: {:?}", path);
+    };
+
+    Ok(())
+}
+
+/// Files of at least this size are memory-mapped instead of read if requested, see
+/// [`FileSource`].
+const MMAP_THRESHOLD: u64 = 1024 * 1024;
+
+/// The contents of a file, valid UTF-8.
+///
+/// Files are read into memory by default. With `--mmap`, large files are memory-mapped
+/// instead, so that huge (generated, ...) files are not held in memory twice (as read,
+/// and as transformed). If mapping fails, they are read like all others.
+enum FileSource {
+    Mapped(Mmap),
+    Read(String),
+}
+
+impl FileSource {
+    /// Reads `file`, of size `filesize`, memory-mapping it if large and `map_large`.
+    ///
+    /// Invalid UTF-8 is reported as [`io::ErrorKind::InvalidData`], like for
+    /// [`Read::read_to_string`].
+    fn read(mut file: &File, filesize: u64, map_large: bool) -> io::Result<Self> {
+        if map_large && filesize >= MMAP_THRESHOLD {
+            // SAFETY: the file must not be modified for as long as it is mapped, which
+            // cannot be enforced. Truncating it crashes the process (`SIGBUS`), and
+            // other modifications might invalidate the UTF-8 validated below. Users opt
+            // into this hazard explicitly, see `--mmap`.
+            #[allow(unsafe_code)]
+            let map = unsafe { Mmap::map(file) };
+
+            match map {
+                Ok(map) => {
+                    std::str::from_utf8(&map)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    trace!("Memory-mapped file of {filesize} bytes");
+                    return Ok(Self::Mapped(map));
+                }
+                Err(e) => debug!("Failed to memory-map file, reading instead: {e}"),
+            }
+        }
+
+        let mut source =
+            String::with_capacity(filesize.try_into().unwrap_or(/* no perf gains for you */ 0));
+        file.read_to_string(&mut source)?;
+
+        Ok(Self::Read(source))
+    }
+}
+
+impl std::ops::Deref for FileSource {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            // SAFETY: validated as UTF-8 when mapped, and unmodified since as per the
+            // contract of `--mmap`, see `read`.
+            #[allow(unsafe_code)]
+            Self::Mapped(map) => unsafe { std::str::from_utf8_unchecked(map) },
+            Self::Read(source) => source,
+        }
+    }
+}
+
+/// Interactively reviews the change of the file at `path` from `old` to `new`, hunk by
+/// hunk, returning contents with only the accepted hunks applied.
+///
+/// Hunks are shown on `destination`, answers read from `answers`, like `git add -p`
+/// does. Running out of answers rejects all remaining hunks.
+fn review_changes(
+    path: &Path,
+    old: &str,
+    new: &str,
+    answers: &mut impl io::BufRead,
+    destination: &mut impl Write,
+) -> io::Result<String> {
+    const HELP: &str = "y - apply this hunk
+n - do not apply this hunk
+a - apply this hunk and all later hunks in the file
+d - do not apply this hunk or any of the later hunks in the file";
+
+    let diff = similar::TextDiff::from_lines(old, new);
+
+    writeln!(destination, "{}", path.display().to_string().magenta())?;
+
+    // For each change, in order, whether it is accepted.
+    let mut decisions = Vec::new();
+    let mut remaining = None;
+    for hunk in diff.unified_diff().iter_hunks() {
+        writeln!(destination, "{}", hunk.header().to_string().cyan())?;
+        for change in hunk.iter_changes() {
+            let line = format!("{}{}", change.tag(), change.value());
+            let line = line.trim_end_matches(['\r', '\n']);
+            match change.tag() {
+                similar::ChangeTag::Delete => writeln!(destination, "{}", line.red())?,
+                similar::ChangeTag::Insert => writeln!(destination, "{}", line.green())?,
+                similar::ChangeTag::Equal => writeln!(destination, "{line}")?,
+            }
+        }
+
+        let accept = loop {
+            if let Some(accept) = remaining {
+                break accept;
+            }
+
+            write!(destination, "Apply this hunk [y,n,a,d,?]? ")?;
+            destination.flush()?;
+
+            let mut answer = String::new();
+            if answers.read_line(&mut answer)? == 0 {
+                writeln!(destination)?;
+                remaining = Some(false);
+                continue;
+            }
+
+            match answer.trim() {
+                "y" => break true,
+                "n" => break false,
+                "a" => remaining = Some(true),
+                "d" => remaining = Some(false),
+                _ => writeln!(destination, "{HELP}")?,
+            }
+        };
+
+        let n_changes = hunk
+            .ops()
+            .iter()
+            .filter(|op| op.tag() != similar::DiffTag::Equal)
+            .count();
+        decisions.extend(std::iter::repeat(accept).take(n_changes));
+    }
+
+    let mut decisions = decisions.into_iter();
+    let mut result = String::with_capacity(new.len());
+    for op in diff.ops() {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        let accepted = tag != similar::DiffTag::Equal && decisions.next().unwrap_or(false);
+        let lines = if accepted {
+            &diff.new_slices()[new_range]
+        } else {
+            &diff.old_slices()[old_range]
+        };
+        result.extend(lines.iter().copied());
+    }
+
+    Ok(result)
+}
+
+/// Writes the change of the file at `path` from `old` to `new` to `destination`, as a
+/// unified diff which `git apply` and `patch -p1` understand.
+fn write_patch(path: &Path, old: &str, new: &str, destination: &mut impl Write) -> io::Result<()> {
+    // Patches always use forward slashes.
+    let path = path.to_string_lossy().replace('\\', "/");
+
+    write!(
+        destination,
+        "{}",
+        similar::TextDiff::from_lines(old, new)
+            .unified_diff()
+            .header(&format!("a/{path}"), &format!("b/{path}"))
+    )
+}
+
+/// Writes the full `contents` of a file to `destination`, below a `==> path <==`
+/// header if a `heading` is given.
+///
+/// Contents are terminated by a newline, such that subsequent headers start on a line
+/// of their own.
+fn write_file_to_stdout(
+    heading: Option<&Path>,
+    contents: &str,
+    destination: &mut impl Write,
+) -> io::Result<()> {
+    if let Some(path) = heading {
+        writeln!(destination, "==> {} <==", path.display())?;
+    }
+
+    write!(destination, "{contents}")?;
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        writeln!(destination)?;
+    }
+
+    Ok(())
+}
+
+/// Collects all scopes given upfront for the file at `path` with the given `source`:
+/// the `pre_scope`, if any, and lines selected via `git blame`, if requested.
+fn file_pre_scopes(
+    global_options: &cli::GlobalOptions,
+    path: &Path,
+    source: &str,
+    pre_scope: Option<&Ranges<usize>>,
+) -> io::Result<Vec<(ScopingStage, Ranges<usize>)>> {
+    let mut pre_scopes = Vec::new();
+
+    if let Some(ranges) = pre_scope {
+        // Contents might have changed since the pre-scope was determined.
+        let ranges = ranges
+            .iter()
+            .filter(|range| {
+                let valid =
+                    source.is_char_boundary(range.start) && source.is_char_boundary(range.end);
+                if !valid {
+                    warn!("Ignoring invalid range {range:?} in {}", path.display());
+                }
+                valid
+            })
+            .cloned()
+            .collect();
+        pre_scopes.push((ScopingStage::PreScope, ranges));
+    }
+
+    if let Some((author, since)) = global_options.blame() {
+        let lines = blamed_lines(path, author, since)?;
+        pre_scopes.push((
+            ScopingStage::Blame,
+            line_ranges(source, |line| lines.contains(&line)),
+        ));
+    }
+
+    Ok(pre_scopes)
+}
+
+/// Checks that `new`, transformed from `old`, parses without syntax errors for all
+/// `language_scopers`.
+///
+/// Errors already present in `old` are not held against the transformation: only
+/// languages `old` parsed cleanly in are checked.
+fn verify_syntax(
+    old: &str,
+    new: &str,
+    language_scopers: &[Box<dyn LanguageScoper>],
+) -> std::result::Result<(), ApplicationError> {
+    for scoper in language_scopers {
+        if scoper.has_syntax_errors(new) && !scoper.has_syntax_errors(old) {
+            return Err(ApplicationError::SyntaxBroken);
+        }
+    }
+
+    Ok(())
+}
+
+/// Restores metadata of a file after it was rewritten in place.
+///
+/// Writing truncates the existing file instead of replacing it, so ownership is
+/// retained as is. Permissions (incl. the executable bit) are restored explicitly,
+/// and the modification time only if requested.
+fn restore_metadata(path: &Path, metadata: &fs::Metadata, keep_mtime: bool) -> io::Result<()> {
+    fs::set_permissions(path, metadata.permissions())?;
+
+    if keep_mtime {
+        let mtime = metadata.modified()?;
+        trace!(
+            "Restoring modification time of {}: {:?}",
+            path.display(),
+            mtime
+        );
+        File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(mtime)?;
+    }
+
+    Ok(())
+}
+
+/// Workers abandoned past `--timeout-per-file`, some of which might still be running.
+static ABANDONED_WORKERS: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+/// How many abandoned workers may still be running before no more work is taken on.
+///
+/// Each one likely keeps a CPU busy (a regex backtracking catastrophically, ...), so
+/// past this many, everything else would crawl along anyway.
+const MAX_ABANDONED_WORKERS: usize = 16;
+
+/// Runs the actual core processing, returning whether anything changed in the output
+/// compared to the input.
+///
+/// With `--timeout-per-file`, processing happens on a worker thread of its own. Once
+/// the limit has passed, that worker is abandoned: it runs on in the background, but
+/// whatever it produces is discarded. A single runaway stage (scoping, an action, ...)
+/// can hence not hold up the caller. Abandoned workers pile up though, so once there
+/// are [too many][MAX_ABANDONED_WORKERS] still running, this fails right away.
+///
+/// TODO: The way this interacts with [`process_path`] etc. is just **awful** spaghetti
+/// of the most imperative, procedural kind. Refactor needed.
+#[allow(clippy::borrowed_box)] // Used throughout, not much of a pain
+#[allow(clippy::too_many_arguments)]
+fn apply(
+    global_options: &cli::GlobalOptions,
+    standalone_action: StandaloneAction,
+    source: &str,
+    // Use a string to avoid repeated and unnecessary bytes -> utf8 conversions and
+    // corresponding checks.
+    destination: &mut String,
+    general_scoper: &'static Box<dyn Scoper>,
+    language_scopers: &'static [Box<dyn LanguageScoper>],
+    stages: &'static [Stage],
+    pre_scopes: &[(ScopingStage, Ranges<usize>)],
+    pipeline: Pipeline<'static>,
+    summary: &Summary,
+) -> std::result::Result<bool, ApplicationError> {
+    let Some(limit) = global_options.timeout_per_file else {
+        return apply_unbounded(
+            global_options,
+            standalone_action,
+            source,
+            destination,
+            general_scoper,
+            language_scopers,
+            stages,
+            pre_scopes,
+            pipeline,
+            summary,
+        );
+    };
+
+    {
+        let mut abandoned = ABANDONED_WORKERS.lock().unwrap();
+        abandoned.retain(|worker| !worker.is_finished());
+        if abandoned.len() >= MAX_ABANDONED_WORKERS {
+            return Err(ApplicationError::TooManyTimedOut(abandoned.len()));
+        }
+    }
+
+    // The worker might outlive this call, so cannot borrow anything short-lived.
+    let global_options = global_options.clone();
+    let source = source.to_owned();
+    let pre_scopes = pre_scopes.to_vec();
+
+    let start = Instant::now();
+    let (sender, receiver) = mpsc::channel();
+    let worker = thread::spawn(move || {
+        let mut destination = String::with_capacity(source.len());
+        // Counters of an abandoned worker must not end up in the totals.
+        let summary = Summary::default();
+        let res = apply_unbounded(
+            &global_options,
+            standalone_action,
+            &source,
+            &mut destination,
+            general_scoper,
+            language_scopers,
+            stages,
+            &pre_scopes,
+            pipeline,
+            &summary,
+        );
+
+        // Fails if the worker was abandoned already, in which case nobody cares.
+        sender.send((res, destination, summary)).ok();
+    });
+
+    match receiver.recv_timeout(limit) {
+        // Only just missing the deadline is missing it all the same.
+        Ok((res, output, worker_summary)) if start.elapsed() <= limit => {
+            destination.push_str(&output);
+            summary.add(&worker_summary);
+            res
+        }
+        Ok(..) | Err(RecvTimeoutError::Timeout) => {
+            debug!("Abandoning worker after {limit:?}.");
+            ABANDONED_WORKERS.lock().unwrap().push(worker);
+            Err(ApplicationError::TimedOut(limit))
+        }
+        Err(RecvTimeoutError::Disconnected) => match worker.join() {
+            Err(panic) => panic::resume_unwind(panic),
+            Ok(()) => unreachable!("worker sends its result before finishing"),
+        },
+    }
+}
+
+/// Does the work of [`apply`], taking as long as it takes.
+#[allow(clippy::borrowed_box)] // Used throughout, not much of a pain
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::too_many_lines)] // Only slightly above.
+fn apply_unbounded(
+    global_options: &cli::GlobalOptions,
+    standalone_action: StandaloneAction,
+    source: &str,
+    destination: &mut String,
+    general_scoper: &Box<dyn Scoper>,
+    language_scopers: &[Box<dyn LanguageScoper>],
+    stages: &[Stage],
+    pre_scopes: &[(ScopingStage, Ranges<usize>)],
+    pipeline: Pipeline<'_>,
+    summary: &Summary,
+) -> std::result::Result<bool, ApplicationError> {
+    let transformed = (!stages.is_empty())
+        .then(|| run_stages(stages, source))
+        .transpose()?;
+    let (original, source) = (source, transformed.as_deref().unwrap_or(source));
+
+    debug!("Building view.");
+    if global_options.explain {
+        // Debugging aid: neither fail nor act, just report.
+        write_explanation(
+            global_options,
+            source,
+            general_scoper,
+            language_scopers,
+            pre_scopes,
+            destination,
+        );
+        return Ok(false);
+    }
+
+    let mut view = build_view(
+        global_options,
+        source,
+        general_scoper,
+        language_scopers,
+        pre_scopes,
+        &mut |_, _, _| (),
+    );
+    debug!("Done building view: {view:?}");
+
+    if global_options.fail_none && !view.has_any_in_scope() {
+        return Err(ApplicationError::NoneInScope);
+    }
+
+    if global_options.fail_any && view.has_any_in_scope() {
+        return Err(ApplicationError::SomeInScope);
+    };
+
+    if matches!(global_options.format, cli::OutputFormat::Vimgrep) {
+        // Locations refer to the original input, so actions are of no interest.
+        let n_matched = write_vimgrep(source, &view, destination);
+        summary
+            .scopes_matched
+            .fetch_add(n_matched, Ordering::Relaxed);
+        return Ok(false);
+    }
+
+    debug!("Applying actions to view.");
+    if matches!(standalone_action, StandaloneAction::Squeeze) {
+        view.squeeze();
+    }
+
+    let line_ending = global_options.newline.resolve(source);
+
+    // Only needed for bookkeeping; spare the clone otherwise.
+    let original_view = (global_options.summary
+        || global_options.report_json.is_some()
+        || global_options.check
+        || global_options.only_in_scope)
+        .then(|| view.clone());
+
+    // Give each pipeline its own fresh view
+    let mut views = vec![view; pipeline.len()];
+
+    for (actions, view) in pipeline.iter().zip_eq(&mut views) {
+        if actions.is_empty() {
+            continue;
+        }
+
+        let before = view.clone();
+        view.map_with_context(&Actions::new(actions))?;
+        view.convert_line_endings(line_ending, &before);
+    }
+
+    if let (Some(original_view), Some(result)) = (original_view, views.last()) {
+        if global_options.only_in_scope {
+            write_only_in_scope(
+                source,
+                &original_view,
+                result,
+                global_options.line_numbers,
+                destination,
+            );
+        }
+
+        count_changes(global_options, original_view, result, line_ending, summary);
+    }
+
+    if global_options.only_in_scope {
+        return Ok(false);
+    }
+
+    // Views know cheaply whether they changed; only then is comparing worth it.
+    let any_changed = views.iter().any(ScopedView::changed) || source != original;
+
+    debug!("Writing to destination.");
+    let line_based = global_options.only_matching || global_options.line_numbers;
+    if line_based {
+        let line_based_views = views.iter().map(|v| v.lines().into_iter()).collect_vec();
+
+        for (i, lines) in line_based_views.into_iter().parallel_zip().enumerate() {
+            let i = i + 1;
+            for line in lines {
+                if !global_options.only_matching || line.has_any_in_scope() {
+                    if global_options.line_numbers {
+                        // `ColoredString` needs to be 'evaluated' to do anything; make sure
+                        // to not forget even if this is moved outside of `format!`.
+                        #[allow(clippy::to_string_in_format_args)]
+                        destination.push_str(&format!("{}:", i.to_string().green().to_string()));
+                    }
+
+                    destination.push_str(&line.to_string());
+                }
+            }
+        }
+    } else {
+        assert_eq!(
+            views.len(),
+            1,
+            // Multiple views are useful for e.g. diffing, which works line-based (see
+            // `dry_run`). When not line-based, they *currently* do not make sense, as
+            // there's neither any code path where there *would* be multiple views at
+            // this point, *nor* a valid use case. Printing multiple views here would
+            // probably wreak havoc.
+            "Multiple views at this stage make no sense."
+        );
+
+        for view in views {
+            destination.push_str(&view.to_string());
+        }
+    };
+    debug!("Done writing to destination.");
+
+    // Line-based output is filtered or decorated, so can differ regardless.
+    Ok((any_changed || line_based) && original != *destination)
+}
+
+/// Records how many scopes were matched, and how many of those changed from
+/// `original` to `result`, in `summary`.
+fn count_changes(
+    global_options: &cli::GlobalOptions,
+    mut original: ScopedView<'_>,
+    result: &ScopedView<'_>,
+    line_ending: LineEnding,
+    summary: &Summary,
+) {
+    let is_in = |scope: &&RWScope<'_>| matches!(scope, RWScope(In(..)));
+
+    let n_matched = original.scopes().0.iter().filter(is_in).count();
+    if global_options.check {
+        // Results are styled for display: compare like with like.
+        let before = original.clone();
+        original.map_without_context(&search_mode_style(global_options));
+        original.convert_line_endings(line_ending, &before);
+    }
+    let n_changed = original
+        .scopes()
+        .0
+        .iter()
+        .zip_eq(&result.scopes().0)
+        .filter(|(before, after)| is_in(before) && before != after)
+        .count();
+
+    summary
+        .scopes_matched
+        .fetch_add(n_matched, Ordering::Relaxed);
+    summary
+        .scopes_changed
+        .fetch_add(n_changed, Ordering::Relaxed);
+}
+
+/// Builds a view of `source`, according to all given scopers and scoping options.
+///
+/// Any `pre_scopes`, as well as any `--lines`, are intersected with the result of
+/// scoping; their ranges refer to the entire `source`.
+///
+/// `observe` is called with the view under construction after each scoping stage,
+/// alongside the offset of that view into `source`. In line mode, each line is its own
+/// view.
+#[allow(clippy::borrowed_box)] // Used throughout, not much of a pain
+fn build_view<'viewee>(
+    global_options: &cli::GlobalOptions,
+    source: &'viewee str,
+    general_scoper: &Box<dyn Scoper>,
+    language_scopers: &[Box<dyn LanguageScoper>],
+    pre_scopes: &[(ScopingStage, Ranges<usize>)],
+    observe: &mut dyn FnMut(ScopingStage, usize, &ScopedViewBuilder<'viewee>),
+) -> ScopedView<'viewee> {
+    if !global_options.line_mode {
+        let mut pre_scopes = pre_scopes.to_vec();
+        if !global_options.lines.is_empty() {
+            pre_scopes.push((
+                ScopingStage::LineRanges,
+                line_ranges(source, |line| {
+                    global_options.lines.iter().any(|r| r.contains(line))
+                }),
+            ));
+        }
+
+        return build_buffer_view(
+            global_options,
+            source,
+            general_scoper,
+            language_scopers,
+            &pre_scopes,
+            global_options.max_count,
+            &mut |stage, builder| observe(stage, 0, builder),
+        );
+    }
+
+    assert!(
+        pre_scopes.is_empty(),
+        "Pre-scopes refer to the entire input, not individual lines."
+    );
+
+    // Markers span lines, so need to be found in the input as a whole.
+    let unmarked = (!global_options.no_ignore_markers).then(|| unmarked_ranges(source));
+
+    let mut scopes = Vec::new();
+    let mut offset = 0;
+    let mut max_count = global_options.max_count;
+    // Keep line endings out of it, so that `$` etc. work as expected.
+    for (i, (content, ending)) in newlines::lines(source).enumerate() {
+        observe(
+            ScopingStage::Lines,
+            offset,
+            &ScopedViewBuilder::new(content),
+        );
+
+        let in_line_ranges = global_options.lines.is_empty()
+            || global_options.lines.iter().any(|r| r.contains(i + 1));
+        let is_unmarked = unmarked.as_ref().map_or(true, |ranges| {
+            ranges.iter().any(|range| range.contains(&offset))
+        });
+        let view = if in_line_ranges && is_unmarked {
+            build_buffer_view(
+                global_options,
+                content,
+                general_scoper,
+                language_scopers,
+                &[],
+                max_count,
+                &mut |stage, builder| observe(stage, offset, builder),
+            )
+        } else {
+            let mut builder = ScopedViewBuilder::new(content);
+            builder.restrict(&Ranges::default());
+            let stage = if in_line_ranges {
+                ScopingStage::IgnoreMarkers
+            } else {
+                ScopingStage::LineRanges
+            };
+            observe(stage, offset, &builder);
+            builder.build()
+        };
+
+        // The limit is per input, not per line.
+        if let Some(n) = &mut max_count {
+            let n_in_scope = view
+                .scopes()
+                .0
+                .iter()
+                .filter(|RWScope(scope)| matches!(scope, In(..)))
+                .count();
+            *n -= n_in_scope;
+        }
+
+        scopes.extend(view.scopes().0.iter().cloned());
+        if !ending.is_empty() {
+            scopes.push(RWScope(Out(ending)));
+        }
+        offset += content.len() + ending.len();
+    }
+
+    ScopedView::new(RWScopes(scopes))
+}
+
+/// Builds a view of `source` as a whole, see [`build_view`].
+#[allow(clippy::borrowed_box)] // Used throughout, not much of a pain
+fn build_buffer_view<'viewee>(
+    global_options: &cli::GlobalOptions,
+    source: &'viewee str,
+    general_scoper: &Box<dyn Scoper>,
+    language_scopers: &[Box<dyn LanguageScoper>],
+    pre_scopes: &[(ScopingStage, Ranges<usize>)],
+    max_count: Option<usize>,
+    observe: &mut dyn FnMut(ScopingStage, &ScopedViewBuilder<'viewee>),
+) -> ScopedView<'viewee> {
+    let mut builder = ScopedViewBuilder::new(source);
+
+    match global_options.scope_operator() {
+        ScopeOperator::And => {
+            explode_language_scopes(
+                &mut builder,
+                language_scopers,
+                global_options.join_language_scopes,
+                observe,
+            );
+            // Before the general scope, so the contexts it produces (capture groups)
+            // stay accurate.
+            restrict_to_pre_scopes(&mut builder, pre_scopes, observe);
+            builder.explode(general_scoper);
+            observe(ScopingStage::Scope, &builder);
+        }
+        operator => {
+            builder.explode(&CombinedScoper {
+                language_scopers,
+                join_language_scopes: global_options.join_language_scopes
+                    || matches!(operator, ScopeOperator::Or),
+                general_scoper: general_scoper.as_ref(),
+                operator,
+            });
+            observe(ScopingStage::Combined(operator), &builder);
+            restrict_to_pre_scopes(&mut builder, pre_scopes, observe);
+        }
+    }
+
+    if global_options.invert_scope {
+        builder.invert();
+        observe(ScopingStage::Inversion, &builder);
+    }
+
+    if !global_options.no_ignore_markers {
+        builder.restrict(&unmarked_ranges(source));
+        observe(ScopingStage::IgnoreMarkers, &builder);
+    }
+
+    if let Some(n) = max_count {
+        builder.take(n);
+        observe(ScopingStage::MaxCount, &builder);
+    }
+
+    builder.build()
+}
+
+/// Narrows `builder` down to the given language scopes.
+///
+/// `observe` is called with the view under construction after each scoping stage.
+fn explode_language_scopes<'viewee>(
+    builder: &mut ScopedViewBuilder<'viewee>,
+    language_scopers: &[Box<dyn LanguageScoper>],
+    join: bool,
+    observe: &mut dyn FnMut(ScopingStage, &ScopedViewBuilder<'viewee>),
+) {
+    if language_scopers.is_empty() {
+        return;
+    }
+
+    if join {
+        // All at once, as a slice: hits a specific, 'joining' `impl`
+        builder.explode(&language_scopers);
+        observe(ScopingStage::LanguageScopes, builder);
+    } else {
+        // One by one: hits a different, 'intersecting' `impl`
+        for (i, scoper) in language_scopers.iter().enumerate() {
+            builder.explode(scoper);
+            observe(ScopingStage::LanguageScope(i + 1), builder);
+        }
+    }
+}
+
+/// Narrows `builder` down to each of the given `pre_scopes` in turn.
+///
+/// `observe` is called with the view under construction after each of them.
+fn restrict_to_pre_scopes<'viewee>(
+    builder: &mut ScopedViewBuilder<'viewee>,
+    pre_scopes: &[(ScopingStage, Ranges<usize>)],
+    observe: &mut dyn FnMut(ScopingStage, &ScopedViewBuilder<'viewee>),
+) {
+    for (stage, ranges) in pre_scopes {
+        builder.restrict(ranges);
+        observe(*stage, builder);
+    }
+}
+
+/// The byte ranges of all lines in `source` whose (1-based) line number is
+/// `selected`, line endings included.
+fn line_ranges(source: &str, selected: impl Fn(usize) -> bool) -> Ranges<usize> {
+    let mut offset = 0;
+    source
+        .split_inclusive('\n')
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let range = offset..offset + line.len();
+            offset = range.end;
+            selected(i + 1).then_some(range)
+        })
+        .collect()
+}
+
+/// The ranges of `source` not excluded via inline markers, see [`IgnoreMarkers`].
+fn unmarked_ranges(source: &str) -> Ranges<usize> {
+    IgnoreMarkers
+        .scope_raw(source)
+        .into_iter()
+        .map(|(range, _)| range)
+        .collect()
+}
+
+/// The ranges of everything [`In`] scope in the given view under construction.
+fn in_ranges(builder: ScopedViewBuilder<'_>) -> Ranges<usize> {
+    let mut start = 0;
+    builder
+        .into_iter()
+        .filter_map(|ROScope(scope)| {
+            let (s, is_in) = match scope {
+                In(s, _) => (s, true),
+                Out(s) => (s, false),
+            };
+            let range = start..start + s.len();
+            start = range.end;
+            is_in.then_some(range)
+        })
+        .collect()
+}
+
+/// A single step of scoping down input, see [`build_view`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(variant_size_differences)] // Tiny either way
+enum ScopingStage {
+    /// Splitting into lines, in line mode.
+    Lines,
+    /// The (1-based) n-th language scope, intersected with previous ones.
+    LanguageScope(usize),
+    /// All language scopes, joined.
+    LanguageScopes,
+    /// The general scope (regex pattern etc.).
+    Scope,
+    /// Language scopes and the general scope, combined.
+    Combined(ScopeOperator),
+    /// Scope given upfront, such as via `--rg-json`.
+    PreScope,
+    /// Ranges of lines, via `--lines`.
+    LineRanges,
+    /// Lines selected via `git blame`.
+    Blame,
+    /// Inversion of everything before.
+    Inversion,
+    /// Lines excluded via inline markers.
+    IgnoreMarkers,
+    /// Limiting to the first few occurrences.
+    MaxCount,
+}
 
-    Ok(())
+impl fmt::Display for ScopingStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lines => write!(f, "--line-mode"),
+            Self::LanguageScope(i) => write!(f, "language scope #{i}"),
+            Self::LanguageScopes => write!(f, "language scopes (joined)"),
+            Self::Scope => write!(f, "scope"),
+            Self::Combined(ScopeOperator::And) => write!(f, "scope (--and)"),
+            Self::Combined(ScopeOperator::Or) => write!(f, "scope (--or)"),
+            Self::Combined(ScopeOperator::Not) => write!(f, "scope (--not)"),
+            Self::PreScope => write!(f, "--rg-json"),
+            Self::LineRanges => write!(f, "--lines"),
+            Self::Blame => write!(f, "--blame-author/--blame-since"),
+            Self::Inversion => write!(f, "--invert-scope"),
+            Self::IgnoreMarkers => write!(f, "inline markers"),
+            Self::MaxCount => write!(f, "--max-count"),
+        }
+    }
 }
 
-/// Runs the actual core processing, returning whether anything changed in the output
-/// compared to the input.
+/// Writes the final segmentation of `source` into scopes to `destination`, one line
+/// per scope: its byte offsets, whether it is in or out of scope, the scoping stage
+/// last flipping it, and its contents.
 ///
-/// TODO: The way this interacts with [`process_path`] etc. is just **awful** spaghetti
-/// of the most imperative, procedural kind. Refactor needed.
+/// Scopes never flipped are attributed to the entire input, which starts out in
+/// scope.
 #[allow(clippy::borrowed_box)] // Used throughout, not much of a pain
-fn apply(
+fn write_explanation(
     global_options: &cli::GlobalOptions,
-    standalone_action: StandaloneAction,
     source: &str,
-    // Use a string to avoid repeated and unnecessary bytes -> utf8 conversions and
-    // corresponding checks.
-    destination: &mut String,
     general_scoper: &Box<dyn Scoper>,
     language_scopers: &[Box<dyn LanguageScoper>],
-    pipeline: Pipeline<'_>,
-) -> std::result::Result<bool, ApplicationError> {
-    debug!("Building view.");
-    let mut builder = ScopedViewBuilder::new(source);
+    pre_scopes: &[(ScopingStage, Ranges<usize>)],
+    destination: &mut String,
+) {
+    use std::fmt::Write as _;
 
-    if global_options.join_language_scopes {
-        // All at once, as a slice: hits a specific, 'joining' `impl`
-        builder.explode(&language_scopers);
-    } else {
-        // One by one: hits a different, 'intersecting' `impl`
-        for scoper in language_scopers {
-            builder.explode(scoper);
+    let mut stages: Vec<(ScopingStage, Ranges<usize>)> = Vec::new();
+    let view = build_view(
+        global_options,
+        source,
+        general_scoper,
+        language_scopers,
+        pre_scopes,
+        &mut |stage, offset, builder| {
+            let ranges = in_ranges(builder.clone())
+                .into_iter()
+                .map(|r| r.start + offset..r.end + offset);
+
+            // In line mode, stages repeat for each line; collect them all up.
+            match stages.iter_mut().find(|(s, _)| *s == stage) {
+                Some((_, existing)) => *existing = existing.iter().cloned().chain(ranges).collect(),
+                None => stages.push((stage, ranges.collect())),
+            }
+        },
+    );
+
+    let mut start = 0;
+    for RWScope(scope) in &view.scopes().0 {
+        let (s, is_in) = match scope {
+            In(s, _) => (s.as_ref(), true),
+            Out(s) => (*s, false),
+        };
+        let range = start..start + s.len();
+        start = range.end;
+
+        let was_in_at = |ranges: &Ranges<usize>| ranges.iter().any(|r| r.contains(&range.start));
+        let mut flipped_by = None;
+        let mut was_in = true; // Everything starts out in scope
+        for (stage, ranges) in &stages {
+            let now_in = was_in_at(ranges);
+            if now_in != was_in {
+                flipped_by = Some(stage);
+            }
+            was_in = now_in;
         }
+
+        writeln!(
+            destination,
+            "{:?} {} [{}] {:?}",
+            range,
+            if is_in { "in" } else { "out" },
+            flipped_by.map_or_else(|| "input".to_owned(), ToString::to_string),
+            s
+        )
+        .expect("writing to string is infallible");
     }
+}
 
-    builder.explode(general_scoper);
-    let mut view = builder.build();
-    debug!("Done building view: {view:?}");
+/// How to combine the general scope with language scopes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScopeOperator {
+    /// Narrow language scopes down further (the default).
+    And,
+    /// Either language scopes or the general scope.
+    Or,
+    /// Language scopes, minus the general scope.
+    Not,
+}
 
-    if global_options.fail_none && !view.has_any_in_scope() {
-        return Err(ApplicationError::NoneInScope);
-    }
+/// Combines language scopes with the general scope using a non-narrowing
+/// [`ScopeOperator`].
+///
+/// Exploding a view can only ever narrow it, so this works on the entire input at once
+/// instead.
+struct CombinedScoper<'a> {
+    language_scopers: &'a [Box<dyn LanguageScoper>],
+    join_language_scopes: bool,
+    general_scoper: &'a dyn Scoper,
+    operator: ScopeOperator,
+}
 
-    if global_options.fail_any && view.has_any_in_scope() {
-        return Err(ApplicationError::SomeInScope);
-    };
+impl Scoper for CombinedScoper<'_> {
+    fn scope_raw<'viewee>(&self, input: &'viewee str) -> RangesWithContext<'viewee> {
+        let language = LanguageScopers {
+            language_scopers: self.language_scopers,
+            join_language_scopes: self.join_language_scopes,
+        };
 
-    debug!("Applying actions to view.");
-    if matches!(standalone_action, StandaloneAction::Squeeze) {
-        view.squeeze();
+        match self.operator {
+            ScopeOperator::And => unreachable!("intersecting is handled by exploding"),
+            // Keep general scopes whole, as they might carry context (capture groups
+            // etc.); only fill in around them.
+            ScopeOperator::Or => Union(self.general_scoper, language).scope_raw(input),
+            ScopeOperator::Not => Difference(language, self.general_scoper).scope_raw(input),
+        }
     }
+}
 
-    // Give each pipeline its own fresh view
-    let mut views = vec![view; pipeline.len()];
+/// All language scopes, applied as they would be on their own.
+struct LanguageScopers<'a> {
+    language_scopers: &'a [Box<dyn LanguageScoper>],
+    join_language_scopes: bool,
+}
 
-    for (actions, view) in pipeline.iter().zip_eq(&mut views) {
-        for action in *actions {
-            view.map_with_context(action)?;
-        }
+impl Scoper for LanguageScopers<'_> {
+    fn scope_raw<'viewee>(&self, input: &'viewee str) -> RangesWithContext<'viewee> {
+        let mut builder = ScopedViewBuilder::new(input);
+        explode_language_scopes(
+            &mut builder,
+            self.language_scopers,
+            self.join_language_scopes,
+            &mut |_, _| (),
+        );
+
+        in_ranges(builder).into()
     }
+}
 
-    debug!("Writing to destination.");
-    let line_based = global_options.only_matching || global_options.line_numbers;
-    if line_based {
-        let line_based_views = views.iter().map(|v| v.lines().into_iter()).collect_vec();
+/// Writes a `line:col:text` entry for each in-scope item of `view` to `destination`,
+/// returning the number of entries written.
+///
+/// Lines and columns are 1-based, with columns counted in bytes (as Vim expects).
+/// `text` is the entire line the item starts on.
+fn write_vimgrep(source: &str, view: &ScopedView<'_>, destination: &mut String) -> usize {
+    use std::fmt::Write as _;
+
+    let mut n_written = 0;
+
+    for Located { range, start, .. } in view.located() {
+        let line_start = range.start - (start.column - 1);
+        let text = source[line_start..]
+            .split('\n')
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches('\r');
+
+        writeln!(destination, "{}:{}:{}", start.line, start.column, text)
+            .expect("writing to string is infallible");
+        n_written += 1;
+    }
 
-        for (i, lines) in line_based_views.into_iter().parallel_zip().enumerate() {
-            let i = i + 1;
-            for line in lines {
-                if !global_options.only_matching || line.has_any_in_scope() {
-                    if global_options.line_numbers {
-                        // `ColoredString` needs to be 'evaluated' to do anything; make sure
-                        // to not forget even if this is moved outside of `format!`.
-                        #[allow(clippy::to_string_in_format_args)]
-                        destination.push_str(&format!("{}:", i.to_string().green().to_string()));
-                    }
+    n_written
+}
 
-                    destination.push_str(&line.to_string());
+/// Writes everything [`In`] scope of `result` to `destination`, one occurrence per
+/// line. If `line_numbers`, each line is prefixed by its number in `source`, counting
+/// on from the line an occurrence starts on for occurrences spanning several lines.
+///
+/// `original` is the view before actions were applied, such that locations refer to
+/// `source`.
+fn write_only_in_scope(
+    source: &str,
+    original: &ScopedView<'_>,
+    result: &ScopedView<'_>,
+    line_numbers: bool,
+    destination: &mut String,
+) {
+    use std::fmt::Write as _;
+
+    let mut offset = 0;
+    let mut line = 1;
+
+    for (RWScope(before), RWScope(after)) in original.scopes().0.iter().zip_eq(&result.scopes().0) {
+        let len = match before {
+            In(s, _) => s.len(),
+            Out(s) => s.len(),
+        };
+
+        if let In(s, _) = after {
+            // Occurrences spanning lines are split up, so that each line of output
+            // carries its own location and can be parsed on its own.
+            let parts = if s.is_empty() {
+                vec![""]
+            } else {
+                s.lines().collect()
+            };
+            for (i, part) in parts.into_iter().enumerate() {
+                if line_numbers {
+                    write!(destination, "{}:", line + i).expect("writing to string is infallible");
                 }
+                destination.push_str(part);
+                destination.push('\n');
             }
         }
-    } else {
-        assert_eq!(
-            views.len(),
-            1,
-            // Multiple views are useful for e.g. diffing, which works line-based (see
-            // `dry_run`). When not line-based, they *currently* do not make sense, as
-            // there's neither any code path where there *would* be multiple views at
-            // this point, *nor* a valid use case. Printing multiple views here would
-            // probably wreak havoc.
-            "Multiple views at this stage make no sense."
-        );
-
-        for view in views {
-            destination.push_str(&view.to_string());
-        }
-    };
-    debug!("Done writing to destination.");
 
-    Ok(source != *destination)
+        line += source[offset..offset + len].matches('\n').count();
+        offset += len;
+    }
 }
 
 /// Top-level, user-facing errors, affecting and possibly terminating program execution
@@ -788,12 +2923,16 @@ enum ProgramError {
     NothingProcessed,
     /// Files were found but some input ended up being processed, unexpectedly.
     SomethingProcessed,
+    /// Some input would be changed, when checking.
+    SomethingWouldChange,
     /// I/O error.
     IoError(io::Error),
     /// Error while processing files for walking.
     IgnoreError(ignore::Error),
     /// The given query failed to parse
     QueryError(TSQueryError),
+    /// The given grammar failed to load.
+    GrammarError(GrammarError),
 }
 
 impl fmt::Display for ProgramError {
@@ -804,11 +2943,13 @@ impl fmt::Display for ProgramError {
             Self::NoFilesFound => write!(f, "No files found"),
             Self::NothingProcessed => write!(f, "No input was in scope"),
             Self::SomethingProcessed => write!(f, "Some input was in scope"),
+            Self::SomethingWouldChange => write!(f, "Some input would be changed"),
             Self::IoError(e) => write!(f, "I/O error: {e}"),
             Self::IgnoreError(e) => write!(f, "Error walking files: {e}"),
             Self::QueryError(e) => {
                 write!(f, "Error occurred while creating a tree-sitter query: {e}")
             }
+            Self::GrammarError(e) => write!(f, "Error loading tree-sitter grammar: {e}"),
         }
     }
 }
@@ -843,6 +2984,12 @@ impl From<TSQueryError> for ProgramError {
     }
 }
 
+impl From<GrammarError> for ProgramError {
+    fn from(err: GrammarError) -> Self {
+        Self::GrammarError(err)
+    }
+}
+
 impl Error for ProgramError {}
 
 /// Errors when applying actions to scoped views.
@@ -854,6 +3001,13 @@ enum ApplicationError {
     NoneInScope,
     /// Error with an [`Action`].
     ActionError(ActionError),
+    /// Processing took longer than the given limit, and was abandoned.
+    TimedOut(Duration),
+    /// Processing was not even started, as this many abandoned workers (see
+    /// [`ApplicationError::TimedOut`]) are still running.
+    TooManyTimedOut(usize),
+    /// Transformed contents no longer parse, see [`verify_syntax`].
+    SyntaxBroken,
 }
 
 impl fmt::Display for ApplicationError {
@@ -862,6 +3016,12 @@ impl fmt::Display for ApplicationError {
             Self::SomeInScope => write!(f, "Some input was in scope"),
             Self::NoneInScope => write!(f, "No input was in scope"),
             Self::ActionError(e) => write!(f, "Error in an action: {e}"),
+            Self::TimedOut(limit) => write!(f, "Processing took longer than {limit:?}"),
+            Self::TooManyTimedOut(n) => write!(
+                f,
+                "Processing of {n} timed out files is still running in the background, giving up"
+            ),
+            Self::SyntaxBroken => write!(f, "Transformed contents contain syntax errors"),
         }
     }
 }
@@ -883,6 +3043,8 @@ enum PathProcessingError {
     NotAFile,
     /// Item is a file but is unsuitable for processing.
     InvalidFile,
+    /// Item is a file but exceeds the size limit.
+    TooLarge { size: u64, limit: u64 },
     /// Error when applying.
     ApplicationError(ApplicationError),
 }
@@ -894,6 +3056,12 @@ impl fmt::Display for PathProcessingError {
             Self::IoError(e, Some(path)) => write!(f, "I/O error at path {}: {e}", path.display()),
             Self::NotAFile => write!(f, "Item is not a file"),
             Self::InvalidFile => write!(f, "Item is not a valid file"),
+            Self::TooLarge { size, limit } => {
+                write!(
+                    f,
+                    "File size of {size} bytes exceeds limit of {limit} bytes"
+                )
+            }
             Self::ApplicationError(e) => write!(f, "Error applying: {e}"),
         }
     }
@@ -943,18 +3111,230 @@ impl fmt::Display for ScoperBuildError {
 impl Error for ScoperBuildError {}
 
 fn get_general_scoper(options: &cli::GlobalOptions, scope: String) -> Result<Box<dyn Scoper>> {
-    Ok(if options.literal_string {
-        Box::new(Literal::try_from(scope).context("Failed building literal string")?)
+    if let Some(preset) = options.preset {
+        // Secrets can span lines, which the global scope would needlessly split up.
+        if !options.literal_string && scope == GLOBAL_SCOPE {
+            return Ok(Box::new(preset));
+        }
+    }
+
+    let scoper: Box<dyn Scoper> = if options.columns {
+        Box::new(
+            scope
+                .parse::<Columns>()
+                .context("Failed building column ranges")?,
+        )
+    } else if options.indentation {
+        Box::new(
+            scope
+                .parse::<Indentation>()
+                .context("Failed building indentation depths")?,
+        )
+    } else if options.literal_string {
+        let literal = Literal::try_from(scope).context("Failed building literal string")?;
+        Box::new(if options.word {
+            literal.with_word_boundaries()
+        } else {
+            literal
+        })
     } else {
-        Box::new(Regex::try_from(scope).context("Failed building regex")?)
+        Box::new(
+            Regex::builder(&scope)
+                .case_insensitive(options.ignore_case)
+                .multi_line(options.multi_line)
+                .dot_matches_new_line(options.dot_matches_new_line)
+                .group(options.group.clone())
+                .build()
+                .context("Failed building regex")?,
+        )
+    };
+
+    Ok(match options.preset {
+        Some(preset) => Box::new(Intersection(preset, scoper)),
+        None => scoper,
     })
 }
 
+/// Assembles all actions given on the command line, in the order they apply.
+fn assemble_actions(
+    composable_actions: &cli::ComposableActions,
+    standalone_action: StandaloneAction,
+    #[cfg(feature = "symbols")] options: &cli::GlobalOptions,
+    #[cfg(feature = "german")] german_options: &cli::GermanOptions,
+) -> Result<Vec<Box<dyn Action>>> {
+    debug!("Assembling actions.");
+    let mut actions = assemble_common_actions(composable_actions, standalone_action)?;
+
+    #[cfg(feature = "symbols")]
+    if composable_actions.symbols {
+        if options.invert {
+            actions.push(Box::<SymbolsInversion>::default());
+            debug!("Loaded action: SymbolsInversion");
+        } else {
+            actions.push(Box::<Symbols>::default());
+            debug!("Loaded action: Symbols");
+        }
+    }
+
+    #[cfg(feature = "german")]
+    if composable_actions.german {
+        actions.push(Box::new(German::new(
+            // Smell? Bug if bools swapped.
+            german_options.german_prefer_original,
+            german_options.german_naive,
+        )));
+        debug!("Loaded action: German");
+    }
+
+    let locale = composable_actions.locale.unwrap_or_default();
+    for ordered_action in &composable_actions.ordered_actions {
+        let action: Box<dyn Action> = match ordered_action {
+            cli::OrderedAction::Replace(replacement) => Box::new(
+                Replacement::try_from(replacement.clone())
+                    .context("Failed building replacement string")?,
+            ),
+            cli::OrderedAction::Upper => Box::new(Upper::new(locale)),
+            cli::OrderedAction::Lower => Box::new(Lower::new(locale)),
+            cli::OrderedAction::Titlecase => Box::new(Titlecase::new(locale)),
+            cli::OrderedAction::Normalize => Box::<Normalization>::default(),
+            #[cfg(feature = "symbols")]
+            cli::OrderedAction::Symbols if options.invert => Box::<SymbolsInversion>::default(),
+            #[cfg(feature = "symbols")]
+            cli::OrderedAction::Symbols => Box::<Symbols>::default(),
+            #[cfg(feature = "german")]
+            cli::OrderedAction::German => Box::new(German::new(
+                german_options.german_prefer_original,
+                german_options.german_naive,
+            )),
+        };
+
+        actions.push(action);
+        debug!("Loaded ordered action: {ordered_action:?}");
+    }
+
+    debug!("Done assembling actions.");
+    Ok(actions)
+}
+
+/// Compiles the language scopers given on the command line, if any.
+fn compile_language_scopers(
+    languages_scopes: cli::LanguageScopes,
+    composable_actions: &cli::ComposableActions,
+    options: &cli::GlobalOptions,
+) -> Result<Option<ScoperList>> {
+    let capture_contexts = !composable_actions.capture_replacements.is_empty();
+    let captures = if capture_contexts {
+        // Unmapped captures would otherwise be in scope, unaffected by any action.
+        composable_actions
+            .capture_replacements
+            .iter()
+            .map(|cr| cr.capture.clone())
+            .collect()
+    } else {
+        options.capture.clone()
+    };
+    let language_scopers =
+        languages_scopes.compile_query_sources_to_scopes(&captures, capture_contexts)?;
+
+    if capture_contexts && language_scopers.is_none() {
+        cli::Args::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "Replacing per capture requires a custom language query.",
+            )
+            .exit();
+    }
+
+    Ok(language_scopers)
+}
+
+/// A stage given before a `--then`, transforming the input of the stages after it.
+struct Stage {
+    /// Options as given for this stage; only the ones applying per stage are set.
+    options: cli::GlobalOptions,
+    standalone_action: StandaloneAction,
+    general_scoper: Box<dyn Scoper>,
+    /// Name of the language scoped, if any.
+    language: Option<&'static str>,
+    language_scopers: Arc<ScoperList>,
+    actions: Vec<Box<dyn Action>>,
+}
+
+impl Stage {
+    fn compile(args: cli::Args) -> Result<Self> {
+        let cli::Args {
+            scope,
+            mut composable_actions,
+            standalone_actions,
+            options,
+            languages_scopes,
+            #[cfg(feature = "german")]
+            german_options,
+            ..
+        } = args;
+
+        if composable_actions.expand_env {
+            for value in composable_actions.replacement_values_mut() {
+                *value = expand_env_vars(value);
+            }
+        }
+
+        let standalone_action = standalone_actions.into();
+        let actions = assemble_actions(
+            &composable_actions,
+            standalone_action,
+            #[cfg(feature = "symbols")]
+            &options,
+            #[cfg(feature = "german")]
+            &german_options,
+        )?;
+
+        Ok(Self {
+            general_scoper: get_general_scoper(&options, scope)?,
+            language: languages_scopes.language(),
+            language_scopers: Arc::new(
+                compile_language_scopers(languages_scopes, &composable_actions, &options)?
+                    .unwrap_or_default(),
+            ),
+            options,
+            standalone_action,
+            actions,
+        })
+    }
+}
+
+/// Runs all `stages` on `source` in turn, returning the output of the last one.
+fn run_stages(stages: &[Stage], source: &str) -> std::result::Result<String, ApplicationError> {
+    let mut output = source.to_owned();
+    for (i, stage) in stages.iter().enumerate() {
+        debug!("Running stage {}.", i + 1);
+
+        let mut view = build_view(
+            &stage.options,
+            &output,
+            &stage.general_scoper,
+            &stage.language_scopers,
+            &[],
+            &mut |_, _, _| (),
+        );
+
+        if matches!(stage.standalone_action, StandaloneAction::Squeeze) {
+            view.squeeze();
+        }
+        view.map_with_context(&Actions::new(&stage.actions))?;
+
+        output = view.to_string();
+    }
+
+    Ok(output)
+}
+
 fn assemble_common_actions(
     composable_actions: &cli::ComposableActions,
     standalone_actions: StandaloneAction,
 ) -> Result<Vec<Box<dyn Action>>> {
     let mut actions: Vec<Box<dyn Action>> = Vec::new();
+    let locale = composable_actions.locale.unwrap_or_default();
 
     if let Some(replacement) = composable_actions.replace.clone() {
         actions.push(Box::new(
@@ -963,23 +3343,36 @@ fn assemble_common_actions(
         debug!("Loaded action: Replacement");
     }
 
+    if !composable_actions.capture_replacements.is_empty() {
+        let replacements = composable_actions
+            .capture_replacements
+            .iter()
+            .map(|cr| {
+                Replacement::try_from(cr.value.clone()).map(|value| (cr.capture.clone(), value))
+            })
+            .collect::<Result<CaptureReplacements, _>>()
+            .context("Failed building replacement string")?;
+        actions.push(Box::new(replacements));
+        debug!("Loaded action: CaptureReplacements");
+    }
+
     if matches!(standalone_actions, StandaloneAction::Delete) {
         actions.push(Box::<Deletion>::default());
         debug!("Loaded action: Deletion");
     }
 
     if composable_actions.upper {
-        actions.push(Box::<Upper>::default());
+        actions.push(Box::new(Upper::new(locale)));
         debug!("Loaded action: Upper");
     }
 
     if composable_actions.lower {
-        actions.push(Box::<Lower>::default());
+        actions.push(Box::new(Lower::new(locale)));
         debug!("Loaded action: Lower");
     }
 
     if composable_actions.titlecase {
-        actions.push(Box::<Titlecase>::default());
+        actions.push(Box::new(Titlecase::new(locale)));
         debug!("Loaded action: Titlecase");
     }
 
@@ -1015,21 +3408,32 @@ fn level_filter_from_env_and_verbosity(additional_verbosity: u8) -> LevelFilter
 }
 
 mod cli {
+    use std::ffi::OsString;
     use std::num::NonZero;
+    use std::ops::RangeInclusive;
     use std::path::PathBuf;
-    use std::{fs, io};
+    use std::str::FromStr;
+    use std::time::Duration;
+    use std::{env, fs, io, iter, mem};
 
     use clap::builder::ArgPredicate;
-    use clap::{ArgAction, Command, CommandFactory, Parser};
+    use clap::error::ErrorKind;
+    use clap::parser::ValueSource;
+    use clap::{ArgAction, Command, CommandFactory, FromArgMatches, Parser, ValueEnum, ValueHint};
     use clap_complete::{generate, Generator, Shell};
     use log::info;
+    use srgn::actions::Locale;
+    use srgn::newlines::LineEnding;
+    use srgn::ranges::{parse_range, ParseRangeError};
     use srgn::scoping::langs::{
-        c, csharp, go, hcl, python, rust, typescript, LanguageScoper, QuerySource,
+        c, cpp, csharp, external, go, hcl, python, rust, typescript, LanguageScoper, QuerySource,
     };
-    use srgn::GLOBAL_SCOPE;
+    use srgn::scoping::presets::Preset;
+    use srgn::scoping::regex::CaptureGroup;
+    use srgn::{RegexPattern, GLOBAL_SCOPE};
     use tree_sitter::QueryError as TSQueryError;
 
-    use crate::{ProgramError, StandaloneAction};
+    use crate::{ProgramError, ScopeOperator, StandaloneAction};
 
     /// Main CLI entrypoint.
     ///
@@ -1051,7 +3455,8 @@ mod cli {
         /// Scope to apply to, as a regular expression pattern.
         ///
         /// If string literal mode is requested, will be interpreted as a literal
-        /// string.
+        /// string. If column or indentation mode is requested, will be interpreted as
+        /// column ranges or indentation depths, respectively.
         ///
         /// Actions will apply their transformations within this scope only.
         ///
@@ -1062,7 +3467,11 @@ mod cli {
             value_name = "SCOPE",
             default_value = GLOBAL_SCOPE,
             verbatim_doc_comment,
-            default_value_if("literal_string", ArgPredicate::IsPresent, None)
+            default_value_ifs([
+                ("literal_string", ArgPredicate::IsPresent, None),
+                ("columns", ArgPredicate::IsPresent, None),
+                ("indentation", ArgPredicate::IsPresent, None),
+            ])
         )]
         pub(super) scope: String,
 
@@ -1088,6 +3497,46 @@ mod cli {
         #[cfg(feature = "german")]
         #[command(flatten)]
         pub(super) german_options: GermanOptions,
+
+        /// Stages given after the first `--then`, each separately.
+        #[arg(skip)]
+        pub(super) stages: Vec<Self>,
+    }
+
+    /// Splits `args` into the arguments of each stage, at each `--then`. Arguments
+    /// after a `--` are positional, so are never split at.
+    fn split_stages(args: impl IntoIterator<Item = OsString>) -> Vec<Vec<OsString>> {
+        let mut segments = vec![Vec::new()];
+        let mut positional = false;
+
+        for arg in args {
+            if arg == "--then" && !positional {
+                segments.push(Vec::new());
+                continue;
+            }
+
+            positional |= arg == "--";
+            segments
+                .last_mut()
+                .expect("at least one segment at all times")
+                .push(arg);
+        }
+
+        segments
+    }
+
+    /// Whether the argument of `id` applies to all stages, so must not be given per
+    /// stage.
+    fn is_global_arg(id: &str) -> bool {
+        if STAGE_OPTIONS.contains(&id) {
+            return false;
+        }
+
+        id == "shell"
+            || Args::command()
+                .get_groups()
+                .filter(|group| group.get_id() == "GlobalOptions")
+                .any(|group| group.get_args().any(|arg| arg == id))
     }
 
     /// <https://github.com/clap-rs/clap/blob/f65d421607ba16c3175ffe76a20820f123b6c4cb/clap_complete/examples/completion-derive.rs#L69>
@@ -1100,12 +3549,13 @@ mod cli {
         );
     }
 
-    #[derive(Parser, Debug)]
+    #[derive(Parser, Debug, Clone)]
     #[group(required = false, multiple = true)]
     #[command(next_help_heading = "Options (global)")]
     #[allow(clippy::struct_excessive_bools)]
     pub struct GlobalOptions {
-        /// Glob of files to work on (instead of reading stdin).
+        /// Glob of files to work on (instead of reading stdin). Can be given multiple
+        /// times, to work on files matching any of them.
         ///
         /// If actions are applied, they overwrite files in-place.
         ///
@@ -1114,7 +3564,86 @@ mod cli {
         ///
         /// Names of processed files are written to stdout.
         #[arg(short('G'), long, verbatim_doc_comment, alias = "files")]
-        pub glob: Option<glob::Pattern>,
+        pub glob: Vec<glob::Pattern>,
+        /// Only work on files changed relative to this git reference (default: `HEAD`),
+        /// given as `--changed=REF`.
+        ///
+        /// Useful to apply actions only to code actually being touched, e.g. before
+        /// merging. Can be combined with `--glob` and language scopes, which narrow
+        /// down further.
+        #[arg(
+            long,
+            value_name = "REF",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "HEAD",
+            verbatim_doc_comment
+        )]
+        pub changed: Option<String>,
+        /// Only work on files with staged changes (see `--changed`).
+        #[arg(long, verbatim_doc_comment)]
+        pub staged: bool,
+        /// Restrict scoping to lines last touched by an author matching this pattern,
+        /// according to `git blame`.
+        ///
+        /// The pattern is matched against `Name <email>`, as in `git log --author`.
+        /// Useful to clean up only one's own code. Requires working on files.
+        #[arg(
+            long,
+            value_name = "PATTERN",
+            verbatim_doc_comment,
+            conflicts_with_all = ["stream", "line_mode"]
+        )]
+        pub blame_author: Option<RegexPattern>,
+        /// Restrict scoping to lines last touched after this date, according to `git
+        /// blame`.
+        ///
+        /// Takes anything git understands, such as `2024-01-31` or `2.weeks`. Useful to
+        /// act only on recently introduced lines. Lines not committed yet are always
+        /// recent. Requires working on files.
+        #[arg(
+            long,
+            value_name = "DATE",
+            verbatim_doc_comment,
+            conflicts_with_all = ["stream", "line_mode"]
+        )]
+        pub blame_since: Option<String>,
+        /// Same as `--glob`, but matches case-insensitively.
+        #[arg(long, verbatim_doc_comment, conflicts_with = "glob")]
+        pub iglob: Option<glob::Pattern>,
+        /// Read `rg --json` output from stdin, and work on the files and matches it
+        /// reports.
+        ///
+        /// Matches reported by ripgrep are the initial scope, which any other scopes
+        /// narrow down further. This combines the speed of ripgrep with syntax-aware
+        /// scoping, for example `rg --json TODO | srgn --rg-json --python comments`.
+        /// If no actions are given, search mode is used.
+        #[arg(
+            long,
+            verbatim_doc_comment,
+            conflicts_with_all = ["glob", "iglob", "stream", "line_mode"]
+        )]
+        pub rg_json: bool,
+        /// Glob of files and directories to exclude when walking. Can be given
+        /// multiple times.
+        ///
+        /// Paths are matched relative to the working directory, for example
+        /// `--exclude 'modules/vendor/**'`.
+        #[arg(long, value_name = "GLOB", verbatim_doc_comment)]
+        pub exclude: Vec<glob::Pattern>,
+        /// Treat files with extension `EXT` as language `LANG` when walking with
+        /// language scopes. Can be given multiple times.
+        ///
+        /// For files with nonstandard extensions, for example `--map-ext tfvars=hcl`
+        /// or `--map-ext pyi=python`. Overrides the default mapping: with `--map-ext
+        /// tf=go`, Terraform files are no longer considered HCL.
+        #[arg(long, value_name = "EXT=LANG", verbatim_doc_comment)]
+        pub map_ext: Vec<ExtensionMapping>,
+        /// Maximum depth to descend into directories when walking.
+        ///
+        /// A depth of 1 only considers files directly inside the working directory.
+        #[arg(long, verbatim_doc_comment)]
+        pub max_depth: Option<usize>,
         /// Fail if working on files (e.g. globbing is requested) but none are found.
         ///
         /// Processing no files is not an error condition in itself, but might be an
@@ -1128,6 +3657,13 @@ mod cli {
         /// It is similar to git diff with word diffing enabled.
         #[arg(long, verbatim_doc_comment)]
         pub dry_run: bool,
+        /// Like `--dry-run`, but fail if any change would be performed.
+        ///
+        /// Useful to enforce invariants as a pre-commit hook or in CI: `srgn --check
+        /// --python doc-strings --titlecase` fails if any Python docstring is not
+        /// titlecased yet. Finding nothing in scope is not a failure.
+        #[arg(long, verbatim_doc_comment, conflicts_with = "fail_none")]
+        pub check: bool,
         /// Undo the effects of passed actions, where applicable.
         ///
         /// Requires a 1:1 mapping between replacements and original, which is currently
@@ -1151,29 +3687,204 @@ mod cli {
         /// string. Will require a scope to be passed.
         #[arg(short('L'), long, env, verbatim_doc_comment)]
         pub literal_string: bool,
+        /// Only match the literal string where bounded by word boundaries, so `id` does
+        /// not match inside of `identifier`. Requires `--literal-string`.
+        #[arg(short('w'), long, requires = "literal_string", verbatim_doc_comment)]
+        pub word: bool,
+        /// Do not interpret the scope as a regex. Instead, interpret it as fixed column
+        /// ranges of each line, for fixed-width data. Will require a scope to be
+        /// passed.
+        ///
+        /// Ranges are comma-separated, with columns counted in characters starting at
+        /// 1, as for `cut -c`. Like for `--lines`, ranges include both ends: `N` is
+        /// column N only, `N..` from column N to the end of the line, `..M` up to
+        /// column M and `N..M` columns N through M. Line endings are never in scope.
+        /// For example, `--columns 8..72` scopes what follows the sequence number area
+        /// of fixed-format COBOL.
+        #[arg(
+            long,
+            env,
+            conflicts_with_all = ["literal_string", "ignore_case", "multi_line", "dot_matches_new_line"],
+            verbatim_doc_comment
+        )]
+        pub columns: bool,
+        /// Do not interpret the scope as a regex. Instead, interpret it as a range of
+        /// indentation depths, scoping entire lines indented that deeply. Useful for
+        /// whitespace-significant formats, or for nested blocks where there is no
+        /// language grammar to go by. Will require a scope to be passed.
+        ///
+        /// Depth is the width of leading whitespace, with tabs advancing to the next
+        /// multiple of 8. Like for `--lines`, the range includes both ends, and is one
+        /// of `N` (exactly N, so `0` is top-level only), `N..` (N or deeper), `..M` (M
+        /// or shallower) or `N..M`. Blank lines are in scope if the lines surrounding
+        /// them are.
+        #[arg(
+            long,
+            env,
+            conflicts_with_all = ["literal_string", "columns", "ignore_case", "multi_line", "dot_matches_new_line"],
+            verbatim_doc_comment
+        )]
+        pub indentation: bool,
+        /// Match the scope regex case-insensitively, same as starting it with `(?i)`.
+        #[arg(long, conflicts_with = "literal_string", verbatim_doc_comment)]
+        pub ignore_case: bool,
+        /// Have `^` and `$` of the scope regex match at the start and end of each line,
+        /// same as starting it with `(?m)`.
+        #[arg(long, conflicts_with = "literal_string", verbatim_doc_comment)]
+        pub multi_line: bool,
+        /// Have `.` of the scope regex match newlines, same as starting it with `(?s)`.
+        #[arg(long, conflicts_with = "literal_string", verbatim_doc_comment)]
+        pub dot_matches_new_line: bool,
+        /// Only scope the given capture group of each match of the scope regex, instead
+        /// of the entire match.
+        ///
+        /// The rest of the pattern still has to match, anchoring the group, but is left
+        /// alone. Groups are given by number or name, as in the pattern. For example,
+        /// `--group v 'version = (?<v>[\d.]+)' '2.0'` replaces version numbers only.
+        /// Matches in which the group does not participate are skipped.
+        #[arg(
+            long,
+            value_name = "GROUP",
+            conflicts_with_all = ["literal_string", "columns", "indentation"],
+            verbatim_doc_comment
+        )]
+        pub group: Option<CaptureGroup>,
+        /// Restrict scoping to secrets of a curated, named format.
+        ///
+        /// The scope then applies within each secret found; the default scope covers
+        /// them entirely. Meant to be combined with replacement or deletion, for
+        /// example `--preset jwt '.*' '[REDACTED]'` redacts all JSON Web Tokens, so
+        /// logs and fixtures can be sanitized in one go. Presets match well-known
+        /// formats only, not anything which might be a secret.
+        #[arg(long, value_enum, verbatim_doc_comment)]
+        pub preset: Option<Preset>,
         /// If anything at all is found to be in scope, fail.
         ///
-        /// The default is to continue processing normally.
+        /// The default is to continue processing normally. Useful as a gate (in CI
+        /// etc.): finding nothing is then a success, including in search mode.
         #[arg(long, verbatim_doc_comment)]
         pub fail_any: bool,
         /// If nothing is found to be in scope, fail.
         ///
         /// The default is to return the input unchanged (without failure).
         #[arg(long, verbatim_doc_comment)]
-        pub fail_none: bool,
-        /// Join (logical 'OR') multiple language scopes, instead of intersecting them.
+        pub fail_none: bool,
+        /// Join (logical 'OR') multiple language scopes, instead of intersecting them.
+        ///
+        /// The default when multiple language scopes are given is to intersect their
+        /// scopes, left to right. For example, `--go func --go strings` will first
+        /// scope down to `func` bodies, then look for strings only within those. This
+        /// flag instead joins (in the set logic sense) all scopes. The example would
+        /// then scope any `func` bodies, and any strings, anywhere. Language scopers
+        /// can then also be given in any order.
+        ///
+        /// No effect if only a single language scope is given. Also does not affect
+        /// non-language scopers (regex pattern etc.), which intersect unless `--or` or
+        /// `--not` is given.
+        #[arg(short('j'), long, verbatim_doc_comment)]
+        pub join_language_scopes: bool,
+        /// Intersect (logical 'AND') the scope with language scopes.
+        ///
+        /// This is the default: `--python strings --and password` scopes occurrences of
+        /// `password` inside of Python strings.
+        #[arg(long, verbatim_doc_comment, conflicts_with_all = ["or", "not"])]
+        pub and: bool,
+        /// Only scope to captures of this name in custom language queries.
+        ///
+        /// Can be given multiple times. By default, all captures of a query are in
+        /// scope. For example, `--python-query '(function_definition name: (identifier)
+        /// @name body: (block) @body)' --capture name` scopes function names only. It
+        /// is an error if a custom query lacks a capture of any of the given names.
+        /// Prepared queries are not affected.
+        #[arg(long, value_name = "NAME", verbatim_doc_comment)]
+        pub capture: Vec<String>,
+        /// Join (logical 'OR') the scope with language scopes.
+        ///
+        /// Anything matched by either is in scope: `--python comments --or TODO`
+        /// scopes all Python comments, and any `TODO` anywhere else. Multiple language
+        /// scopes are joined as well, as with `--join-language-scopes`. Requires a
+        /// language scope.
+        #[arg(long, verbatim_doc_comment, conflicts_with = "not")]
+        pub or: bool,
+        /// Subtract (logical 'AND NOT') the scope from language scopes.
+        ///
+        /// Anything matched by language scopes, but not by the scope, is in scope:
+        /// `--python strings --not '^test'` scopes Python strings, except those parts
+        /// matching the pattern. Without language scopes, everything not matching the
+        /// scope is in scope.
+        #[arg(long, verbatim_doc_comment)]
+        pub not: bool,
+        /// Invert the scope once all scoping is done, flipping in and out of scope.
+        ///
+        /// Actions then apply to everything *except* the matched parts, for example
+        /// `--python strings --invert-scope --upper` uppercases everything outside of
+        /// Python strings.
+        #[arg(long, verbatim_doc_comment)]
+        pub invert_scope: bool,
+        /// Start another stage, acting on the output of the stages before it.
+        ///
+        /// Each stage takes its own scope and actions, and scopes its input afresh, so
+        /// it sees the effects of earlier stages: `--python comments --delete --then
+        /// --python function-names --titlecase` deletes comments, then titlecases
+        /// function names of the result. Scoping options like `--literal-string`,
+        /// `--ignore-case` or `--or` apply per stage; all other options only go before
+        /// the first `--then`, and apply to the last stage. Stages cannot be combined
+        /// with dry runs, streaming, or scoping by position in the input.
+        #[arg(long, verbatim_doc_comment)]
+        // Split off before parsing, see `Args::init`; only here for help and completions.
+        pub then: bool,
+        /// Act on at most this many occurrences in scope per file (or stdin), the first
+        /// ones found.
+        ///
+        /// For example, `--max-count 1` replaces only the first occurrence. Applies
+        /// after all other scoping, including `--invert-scope`.
+        #[arg(short('m'), long, value_name = "N", verbatim_doc_comment)]
+        pub max_count: Option<usize>,
+        /// Restrict all scoping to the given range of lines, for example `20..80`.
+        ///
+        /// Lines are 1-based, and ranges include both ends. Either end may be left
+        /// open (`20..`, `..80`), and a single number selects a single line. Can be
+        /// given multiple times; lines in any of the ranges are then in scope. Useful
+        /// for acting on diff hunks or lines flagged in code review only.
+        #[arg(long, value_name = "RANGE", verbatim_doc_comment)]
+        pub lines: Vec<LineRange>,
+        /// Process lines excluded by inline markers, too.
+        ///
+        /// By default, lines from a `srgn:off` marker up to and including a `srgn:on`
+        /// one are out of scope, as is the line following a `srgn:ignore-next-line`
+        /// marker. Lines carrying markers are out of scope themselves. Markers usually
+        /// go into comments, but are found anywhere on a line. Applies after
+        /// `--invert-scope`, so excluded lines stay untouched either way. Useful to
+        /// protect generated blocks and intentional exceptions from tree-wide changes.
+        #[arg(long, verbatim_doc_comment)]
+        pub no_ignore_markers: bool,
+        /// Line ending to use for newlines introduced by actions.
         ///
-        /// The default when multiple language scopes are given is to intersect their
-        /// scopes, left to right. For example, `--go func --go strings` will first
-        /// scope down to `func` bodies, then look for strings only within those. This
-        /// flag instead joins (in the set logic sense) all scopes. The example would
-        /// then scope any `func` bodies, and any strings, anywhere. Language scopers
-        /// can then also be given in any order.
+        /// Actions can introduce newlines, for example replacements containing `\n`.
+        /// By default, the dominant line ending of each input is detected and used, so
+        /// inputs with DOS-style line endings do not end up with mixed ones. Line
+        /// endings already present in the input are left as they are.
+        #[arg(long, value_enum, default_value_t, verbatim_doc_comment)]
+        pub newline: Newline,
+        /// Print how the input is segmented into scopes, instead of acting on it.
         ///
-        /// No effect if only a single language scope is given. Also does not affect
-        /// non-language scopers (regex pattern etc.), which always intersect.
-        #[arg(short('j'), long, verbatim_doc_comment)]
-        pub join_language_scopes: bool,
+        /// Prints one line per scope: its byte offsets, whether it is in or out of
+        /// scope, which scoper last flipped it (e.g. `language scope #1`, `scope`), and
+        /// its contents. Useful for debugging why something matched or did not.
+        /// Implies search mode: no files are modified.
+        #[arg(long, verbatim_doc_comment, conflicts_with = "format")]
+        pub explain: bool,
+        /// Format to print results in.
+        ///
+        /// 'vimgrep' prints one `file:line:col:text` entry per match, ready for Vim's
+        /// quickfix list (`:cexpr`) and other editors' error parsers. Columns are
+        /// 1-based byte offsets. Implies search mode: no files are modified.
+        ///
+        /// 'patch' prints all changes actions would make as a unified diff, ready for
+        /// `git apply` (or `patch -p1`), for example on another machine or after
+        /// review. No files are modified.
+        #[arg(long, value_enum, default_value_t, verbatim_doc_comment)]
+        pub format: OutputFormat,
         /// Prepend line numbers to output.
         #[arg(long, hide(true), verbatim_doc_comment)]
         // Hidden: internal use. Not really useful to expose.
@@ -1182,6 +3893,89 @@ mod cli {
         #[arg(long, hide(true), verbatim_doc_comment)]
         // Hidden: internal use. Not really useful to expose.
         pub only_matching: bool,
+        /// Print only what is in scope, one occurrence per line.
+        ///
+        /// Turns srgn into an extraction tool, for example `srgn -o --python strings`
+        /// prints all Python string literals. When working on files, occurrences are
+        /// prefixed by `path:line:`. Actions apply to printed occurrences. Implies
+        /// search mode: no files are modified.
+        #[arg(
+            short('o'),
+            long,
+            verbatim_doc_comment,
+            conflicts_with_all = ["dry_run", "check", "explain", "format"]
+        )]
+        pub only_in_scope: bool,
+        /// Show progress (files processed out of total, current file) on stderr when
+        /// working on files.
+        ///
+        /// On a terminal, a single line keeps updating. Otherwise, each file is
+        /// reported on a line of its own, so logs stay free of escape codes.
+        #[arg(long, verbatim_doc_comment)]
+        pub progress: bool,
+        /// Print a summary of the run to stderr once done.
+        ///
+        /// Includes the number of files scanned and changed, scopes matched,
+        /// replacements made, and elapsed time.
+        #[arg(long, verbatim_doc_comment)]
+        pub summary: bool,
+        /// Write a report of the run to this file, as JSON.
+        ///
+        /// Includes overall counts and elapsed time, as well as the outcome of each
+        /// file processed: whether it was changed, skipped (and why) or errored, its
+        /// counts and processing time. Written even if the run fails.
+        #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath, verbatim_doc_comment)]
+        pub report_json: Option<PathBuf>,
+        /// Keep the modification time of files changed in place.
+        ///
+        /// Permissions and ownership are always preserved. Keeping the modification
+        /// time as well can avoid needlessly invalidating build system caches, but
+        /// hides changes from tools relying on it.
+        #[arg(long, verbatim_doc_comment)]
+        pub keep_mtime: bool,
+        /// Leave files unchanged if their transformed contents no longer parse.
+        ///
+        /// Transformed contents are parsed again, using the grammar of each given
+        /// language scope. Files which parsed cleanly before, but contain syntax errors
+        /// after, are reported and skipped (left unchanged), and do not fail the run.
+        /// When reading stdin, the run fails instead. A safety net for large automated
+        /// refactors. Requires a language scope.
+        #[arg(long, verbatim_doc_comment)]
+        pub verify_syntax: bool,
+        /// Print processed files to stdout instead of writing them in place.
+        ///
+        /// Each file's full (transformed) contents are printed below a `==> path <==`
+        /// header, like `head` and `tail` do for multiple files. Combine with
+        /// `--sorted` for deterministic output order. No files are modified.
+        #[arg(
+            long,
+            verbatim_doc_comment,
+            conflicts_with_all = ["dry_run", "check", "only_in_scope", "keep_mtime"]
+        )]
+        pub stdout: bool,
+        /// Review changes before writing them, hunk by hunk, like `git add -p`.
+        ///
+        /// For each file, every hunk of changes is shown, and can be applied or not.
+        /// Only applied hunks are written. Answers are read from stdin, so input has to
+        /// come from files. Implies `--sorted`.
+        #[arg(
+            long,
+            verbatim_doc_comment,
+            conflicts_with_all = [
+                "dry_run",
+                "check",
+                "explain",
+                "only_in_scope",
+                "format",
+                "stdout",
+                "stream",
+                "rg_json",
+            ]
+        )]
+        pub review: bool,
+        /// Do not print `==> path <==` headers when printing files to stdout.
+        #[arg(long, verbatim_doc_comment, requires = "stdout")]
+        pub no_heading: bool,
         /// Do not ignore hidden files and directories.
         #[arg(short('H'), long, verbatim_doc_comment)]
         pub hidden: bool,
@@ -1196,6 +3990,70 @@ mod cli {
         /// Sorted processing disables parallel processing.
         #[arg(long, verbatim_doc_comment)]
         pub sorted: bool,
+        /// Memory-map large files (1 MiB and up) instead of reading them into memory.
+        ///
+        /// Spares holding huge (generated, ...) files in memory twice, as read and as
+        /// transformed. Only safe if no other process modifies files while srgn works
+        /// on them: a file truncated meanwhile crashes srgn (`SIGBUS`), and one
+        /// modified otherwise might be seen in a state that is not valid UTF-8,
+        /// resulting in undefined behavior.
+        #[arg(long, verbatim_doc_comment)]
+        pub mmap: bool,
+        /// Skip files larger than this, for example `512K` or `10M`.
+        ///
+        /// Accepts a number of bytes, optionally suffixed with `K`, `M` or `G` (powers
+        /// of 1024). Skipped files are reported, and do not fail the run. Useful to
+        /// steer clear of giant generated or minified files in tree-wide runs.
+        #[arg(long, value_name = "SIZE", verbatim_doc_comment)]
+        pub max_file_size: Option<ByteSize>,
+        /// Give up on files taking longer than this many seconds to process, for
+        /// example `0.5`.
+        ///
+        /// Such files are reported and skipped (left unchanged), and do not fail the
+        /// run. The limit is enforced even if a single stage runs away (for example, a
+        /// regex backtracking catastrophically): the file is given up on, and its
+        /// processing keeps a thread busy in the background until it completes or srgn
+        /// exits. Once 16 of those are still busy, the run fails instead of taking on
+        /// more files.
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            value_parser = parse_seconds,
+            verbatim_doc_comment
+        )]
+        pub timeout_per_file: Option<Duration>,
+        /// Process each line of input on its own, as if it were a separate document.
+        ///
+        /// Regex anchors like `^` and `$` then match at the start and end of each line,
+        /// and language scopes parse each line in isolation. Scopes never span
+        /// multiple lines, and line endings themselves are never in scope. Implied by
+        /// `--stream`.
+        ///
+        /// By default, input is processed as a single document: regex anchors match at
+        /// the start and end of the entire input only (unless the `(?m)` flag is set),
+        /// and language scopes see the entire document.
+        #[arg(long, verbatim_doc_comment)]
+        pub line_mode: bool,
+        /// Process stdin line by line, writing out each line as soon as it is done.
+        ///
+        /// Memory use no longer grows with input size, which suits long-running
+        /// pipelines (tailing logs, ...). Scopes can then never span multiple lines,
+        /// so language scopes and patterns matching across newlines do not work.
+        #[arg(
+            long,
+            verbatim_doc_comment,
+            conflicts_with_all = [
+                "dry_run",
+                "check",
+                "explain",
+                "line_numbers",
+                "only_matching",
+                "only_in_scope",
+                "max_count",
+                "lines",
+            ]
+        )]
+        pub stream: bool,
         /// Override detection heuristics for stdin readability, and force to value.
         ///
         /// `true` will always attempt to read from stdin. `false` will never read from
@@ -1209,6 +4067,38 @@ mod cli {
         /// sequential, deterministic (but not sorted) output.
         #[arg(long, verbatim_doc_comment)]
         pub threads: Option<NonZero<usize>>,
+        /// Run as a server, answering JSON-RPC 2.0 requests to scope and act on stdin,
+        /// one per line, until stdin is closed.
+        ///
+        /// Grammars and compiled queries are kept around across requests, sparing
+        /// editor plugins and build tools calling into srgn many times the startup
+        /// cost of each invocation. Methods are `apply`, with params `{"input": ...,
+        /// "scopes": [...], "actions": [...]}`, and `scope`, which returns what is in
+        /// scope. Scopes and actions are named after their command line options, for
+        /// example `[{"python": "comments"}, {"regex": "\w+"}]` and `["upper"]`.
+        #[arg(long, exclusive = true, verbatim_doc_comment)]
+        pub server: bool,
+        /// Run as a language server on stdin and stdout, offering pipelines of scopes
+        /// and actions to editors as code actions and commands.
+        ///
+        /// Pipelines are read from the given JSON file, and can also be passed by the
+        /// editor as `initializationOptions`, for example:
+        ///
+        ///     {"pipelines": [{"name": "redact", "scopes": [{"regex": "secret"}],
+        ///       "actions": [{"replace": "***"}], "languages": ["python"]}]}
+        ///
+        /// Scopes and actions are given as for `--server`. Code actions act on the
+        /// selection only, if there is one.
+        #[arg(
+            long,
+            value_name = "CONFIG",
+            num_args = 0..=1,
+            require_equals = true,
+            exclusive = true,
+            verbatim_doc_comment
+        )]
+        #[allow(clippy::option_option)] // Flag given without config vs. not at all
+        pub lsp: Option<Option<PathBuf>>,
         /// Increase log verbosity level.
         ///
         /// The base log level to use is read from the `RUST_LOG` environment variable
@@ -1223,6 +4113,41 @@ mod cli {
         pub additional_verbosity: u8,
     }
 
+    /// Line ending to use, see [`GlobalOptions::newline`].
+    #[derive(Debug, Clone, Copy, Default, ValueEnum)]
+    pub enum Newline {
+        /// Detect the dominant line ending of each input.
+        #[default]
+        Auto,
+        /// Unix-style line endings.
+        Lf,
+        /// DOS-style line endings.
+        Crlf,
+    }
+
+    impl Newline {
+        /// The line ending to use for the given `source`.
+        pub fn resolve(self, source: &str) -> LineEnding {
+            match self {
+                Self::Auto => LineEnding::detect(source),
+                Self::Lf => LineEnding::Lf,
+                Self::Crlf => LineEnding::CrLf,
+            }
+        }
+    }
+
+    /// Output format, see [`GlobalOptions::format`].
+    #[derive(Debug, Clone, Copy, Default, ValueEnum)]
+    pub enum OutputFormat {
+        /// Human-readable output.
+        #[default]
+        Default,
+        /// One `file:line:col:text` entry per match.
+        Vimgrep,
+        /// A unified diff of all changes, for `git apply`.
+        Patch,
+    }
+
     #[derive(Parser, Debug)]
     #[group(required = false, multiple = true)]
     #[command(next_help_heading = "Composable Actions")]
@@ -1241,6 +4166,33 @@ mod cli {
         /// If given, will run before any other action.
         #[arg(value_name = "REPLACEMENT", env, verbatim_doc_comment)]
         pub replace: Option<String>,
+        /// Replace anything in scope stemming from custom query capture `CAPTURE` with
+        /// `VALUE`. Can be given multiple times.
+        ///
+        /// Allows coordinated rewrites of multiple parts of a match in a single pass,
+        /// for example `--python-query '(function_definition name: (identifier) @name
+        /// parameters: (parameters) @params)' --replace name=g --replace 'params=(y)'`.
+        /// Only the given captures are in scope. Where captures overlap, the one
+        /// starting first (and, at equal starts, the longest) wins.
+        ///
+        /// If given, will run before any other action besides `REPLACEMENT`.
+        #[arg(
+            long = "replace",
+            value_name = "CAPTURE=VALUE",
+            verbatim_doc_comment,
+            conflicts_with_all = ["replace", "capture", "or", "not", "join_language_scopes"],
+        )]
+        pub capture_replacements: Vec<CaptureReplacement>,
+        /// Expand environment variables in replacement values, given as `$VAR` or
+        /// `${VAR}`.
+        ///
+        /// Allows injecting values such as versions or build IDs in CI without
+        /// contortions around shell quoting. Only variables set in the environment are
+        /// expanded; anything else, like references to capture groups, is left alone.
+        /// `$$` stays as is, for a literal `$`. Expansion happens before capture group
+        /// variables are substituted, so delimit these as in `${1}$VAR`.
+        #[arg(long, verbatim_doc_comment)]
+        pub expand_env: bool,
         /// Uppercase anything in scope.
         #[arg(short, long, env, verbatim_doc_comment)]
         pub upper: bool,
@@ -1250,6 +4202,15 @@ mod cli {
         /// Titlecase anything in scope.
         #[arg(short, long, env, verbatim_doc_comment)]
         pub titlecase: bool,
+        /// Language whose rules to follow when changing case, as a tag like 'tr' or
+        /// 'nl-BE'.
+        ///
+        /// Supported are Turkish and Azerbaijani ('tr', 'az': dotted and dotless i),
+        /// Lithuanian ('lt': i keeps its dot when accented) and Dutch ('nl': 'ij'
+        /// titlecases as 'IJ'). By default, language-neutral rules apply, and
+        /// titlecasing follows English conventions.
+        #[arg(long, value_name = "LOCALE", env = "SRGN_LOCALE", verbatim_doc_comment)]
+        pub locale: Option<Locale>,
         /// Normalize (Normalization Form D) anything in scope, and throw away marks.
         #[arg(short, long, env, verbatim_doc_comment)]
         pub normalize: bool,
@@ -1280,6 +4241,179 @@ mod cli {
         #[cfg(feature = "symbols")]
         #[arg(short = 'S', long, verbatim_doc_comment)]
         pub symbols: bool,
+        /// Apply an action, in the order given. Can be given multiple times.
+        ///
+        /// Available actions are 'upper', 'lower', 'titlecase', 'normalize',
+        /// 'symbols', 'german', and 'replace=VALUE'. For example, `--action
+        /// replace=x --action upper` first replaces, then uppercases.
+        ///
+        /// Actions given this way run after those given through their dedicated
+        /// flags, which follow a fixed order.
+        #[arg(
+            long = "action",
+            value_name = "ACTION",
+            verbatim_doc_comment,
+            // Doesn't make sense to be used via environment variables.
+        )]
+        pub ordered_actions: Vec<OrderedAction>,
+    }
+
+    /// A file extension to language mapping, as given via `--map-ext`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct ExtensionMapping {
+        /// The extension, without leading dot.
+        pub extension: String,
+        /// The language, one of [`LANGUAGES`].
+        pub language: &'static str,
+    }
+
+    impl FromStr for ExtensionMapping {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let Some((extension, language)) = s.split_once('=') else {
+                return Err(format!("invalid mapping '{s}' (expected 'EXT=LANG')"));
+            };
+
+            let extension = extension.trim_start_matches('.');
+            if extension.is_empty() {
+                return Err(format!("invalid mapping '{s}' (empty extension)"));
+            }
+
+            let language = LANGUAGES.iter().find(|&&l| l == language).ok_or_else(|| {
+                format!(
+                    "unknown language '{language}' (expected one of: {})",
+                    LANGUAGES.join(", ")
+                )
+            })?;
+
+            Ok(Self {
+                extension: extension.to_owned(),
+                language,
+            })
+        }
+    }
+
+    /// A range of lines as given via `--lines`, 1-based and inclusive on both ends.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct LineRange(RangeInclusive<usize>);
+
+    impl LineRange {
+        /// Whether the given (1-based) line number is in range.
+        pub fn contains(&self, line: usize) -> bool {
+            self.0.contains(&line)
+        }
+    }
+
+    impl FromStr for LineRange {
+        type Err = ParseRangeError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            parse_range(s, 1).map(Self)
+        }
+    }
+
+    /// A number of bytes as given via `--max-file-size`, like `512K`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct ByteSize(pub u64);
+
+    impl FromStr for ByteSize {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let (number, factor) = match s.char_indices().last() {
+                Some((i, 'k' | 'K')) => (&s[..i], 1 << 10),
+                Some((i, 'm' | 'M')) => (&s[..i], 1 << 20),
+                Some((i, 'g' | 'G')) => (&s[..i], 1 << 30),
+                _ => (s, 1),
+            };
+
+            number
+                .parse::<u64>()
+                .ok()
+                .and_then(|n| n.checked_mul(factor))
+                .map(Self)
+                .ok_or_else(|| format!("invalid size '{s}' (expected e.g. '4096', '512K', '10M')"))
+        }
+    }
+
+    /// Parses a (fractional, non-negative) number of seconds, as given via
+    /// `--timeout-per-file`.
+    fn parse_seconds(s: &str) -> Result<Duration, String> {
+        s.parse::<f64>()
+            .ok()
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .ok_or_else(|| format!("invalid number of seconds '{s}'"))
+    }
+
+    /// A replacement as given via `--replace`, for a single query capture.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct CaptureReplacement {
+        /// Name of the capture, without leading `@`.
+        pub capture: String,
+        /// The replacement value.
+        pub value: String,
+    }
+
+    impl FromStr for CaptureReplacement {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.split_once('=') {
+                Some((capture, value)) if !capture.is_empty() => Ok(Self {
+                    capture: capture.trim_start_matches('@').to_owned(),
+                    value: value.to_owned(),
+                }),
+                _ => Err(format!(
+                    "invalid capture replacement '{s}' (expected 'CAPTURE=VALUE')"
+                )),
+            }
+        }
+    }
+
+    /// An action as given via `--action`, applied in the order given.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum OrderedAction {
+        /// Replace with the contained value.
+        Replace(String),
+        /// Uppercase.
+        Upper,
+        /// Lowercase.
+        Lower,
+        /// Titlecase.
+        Titlecase,
+        /// Normalize.
+        Normalize,
+        /// Symbols.
+        #[cfg(feature = "symbols")]
+        Symbols,
+        /// German.
+        #[cfg(feature = "german")]
+        German,
+    }
+
+    impl FromStr for OrderedAction {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            if let Some(replacement) = s.strip_prefix("replace=") {
+                return Ok(Self::Replace(replacement.to_owned()));
+            }
+
+            match s {
+                "upper" => Ok(Self::Upper),
+                "lower" => Ok(Self::Lower),
+                "titlecase" => Ok(Self::Titlecase),
+                "normalize" => Ok(Self::Normalize),
+                #[cfg(feature = "symbols")]
+                "symbols" => Ok(Self::Symbols),
+                #[cfg(feature = "german")]
+                "german" => Ok(Self::German),
+                _ => Err(format!(
+                    "unknown action '{s}' (replacement values are given as 'replace=VALUE')"
+                )),
+            }
+        }
     }
 
     #[derive(Parser, Debug)]
@@ -1337,22 +4471,44 @@ mod cli {
                     #[command(flatten)]
                     $lang_flag: Option<$lang_scope>,
                 )+
+
+                #[command(flatten)]
+                grammar: Option<GrammarScope>,
             }
 
+            /// Names of all supported languages, as used for their flags.
+            pub const LANGUAGES: &[&str] = &[$(stringify!($lang_flag),)+];
+
             impl LanguageScopes {
+                /// The name of the language set, if any (see [`LANGUAGES`]).
+                pub(super) const fn language(&self) -> Option<&'static str> {
+                    $(
+                        if self.$lang_flag.is_some() {
+                            return Some(stringify!($lang_flag));
+                        }
+                    )+
+
+                    None
+                }
+
                 /// Finds the first language field set, if any, and compiles the `QuerySourceOrPath`'s into a list of `LanguageScoper`'s.
-                pub(super) fn compile_query_sources_to_scopes(self) -> Result<Option<crate::ScoperList>, ProgramError> {
+                pub(super) fn compile_query_sources_to_scopes(self, captures: &[String], capture_contexts: bool) -> Result<Option<crate::ScoperList>, ProgramError> {
                     assert_exclusive_lang_scope(&[
                         $(self.$lang_flag.is_some(),)+
+                        self.grammar.is_some(),
                     ]);
 
                     $(
                         if let Some(s) = self.$lang_flag {
-                            let s = accumulate_scopes::<$lang_flag::CompiledQuery, _>(s.$lang_flag, s.$lang_query_flag, s.$lang_query_file_flag,)?;
+                            let s = accumulate_scopes::<$lang_flag::CompiledQuery, _>(s.$lang_flag, s.$lang_query_flag, s.$lang_query_file_flag, captures, capture_contexts)?;
                             return Ok(Some(s));
                         }
                     )+
 
+                    if let Some(s) = self.grammar {
+                        return Ok(Some(s.compile(captures, capture_contexts)?));
+                    }
+
                     Ok(None)
                 }
             }
@@ -1361,6 +4517,7 @@ mod cli {
 
     impl_lang_scopes!(
         (c, c_query, c_query_file, CScope),
+        (cpp, cpp_query, cpp_query_file, CppScope),
         (csharp, csharp_query, csharp_query_file, CSharpScope),
         (go, go_query, go_query_file, GoScope),
         (hcl, hcl_query, hcl_query_file, HclScope),
@@ -1383,7 +4540,7 @@ mod cli {
         if set_fields_count > 1 {
             let mut cmd = Args::command();
             cmd.error(
-                clap::error::ErrorKind::ArgumentConflict,
+                ErrorKind::ArgumentConflict,
                 "Can only use one language at a time.",
             )
             .exit();
@@ -1395,6 +4552,8 @@ mod cli {
         prepared_queries: Vec<PQ>,
         literal_queries: Vec<QueryLiteral>,
         file_queries: Vec<PathBuf>,
+        captures: &[String],
+        capture_contexts: bool,
     ) -> Result<super::ScoperList, ProgramError>
     where
         CQ: LanguageScoper + TryFrom<QuerySource, Error = TSQueryError> + 'static,
@@ -1402,19 +4561,22 @@ mod cli {
     {
         let mut scopers: crate::ScoperList = Vec::new();
 
+        let select_captures =
+            |query_source| select_captures(query_source, captures, capture_contexts);
+
         for prepared_query in prepared_queries {
             let compiled_query = prepared_query.into();
             scopers.push(Box::new(compiled_query));
         }
 
         for query_literal in literal_queries {
-            let query_source = query_literal.into();
+            let query_source = select_captures(query_literal.into());
             let compiled_query = CQ::try_from(query_source)?;
             scopers.push(Box::new(compiled_query));
         }
 
         for file_query in file_queries {
-            let query_source = read_query_from_file(file_query)?;
+            let query_source = select_captures(read_query_from_file(file_query)?);
             let compiled_query = CQ::try_from(query_source)?;
             scopers.push(Box::new(compiled_query));
         }
@@ -1422,6 +4584,22 @@ mod cli {
         Ok(scopers)
     }
 
+    /// Narrows `query_source` down to the given `captures` (if any), optionally
+    /// reporting them as context.
+    fn select_captures(
+        mut query_source: QuerySource,
+        captures: &[String],
+        capture_contexts: bool,
+    ) -> QuerySource {
+        if !captures.is_empty() {
+            query_source = query_source.with_captures(captures.to_vec());
+        }
+        if capture_contexts {
+            query_source = query_source.with_capture_contexts();
+        }
+        query_source
+    }
+
     /// Read a literal query as a file.
     fn read_query_from_file(path: PathBuf) -> io::Result<QuerySource> {
         info!("Reading query from file at '{}'", path.display());
@@ -1441,10 +4619,38 @@ mod cli {
         c_query: Vec<QueryLiteral>,
 
         /// Scope C code using a custom tree-sitter query from file.
-        #[arg(long, env, verbatim_doc_comment, value_name = TREE_SITTER_QUERY_FILENAME)]
+        #[arg(
+            long,
+            env,
+            verbatim_doc_comment,
+            value_name = TREE_SITTER_QUERY_FILENAME,
+            value_hint = ValueHint::FilePath
+        )]
         c_query_file: Vec<PathBuf>,
     }
 
+    #[derive(Parser, Debug, Clone)]
+    #[group(required = false, multiple = false)]
+    struct CppScope {
+        /// Scope C++ code using a prepared query.
+        #[arg(long, env, verbatim_doc_comment)]
+        cpp: Vec<cpp::PreparedQuery>,
+
+        /// Scope C++ code using a custom tree-sitter query.
+        #[arg(long, env, verbatim_doc_comment, value_name = TREE_SITTER_QUERY_VALUE)]
+        cpp_query: Vec<QueryLiteral>,
+
+        /// Scope C++ code using a custom tree-sitter query from file.
+        #[arg(
+            long,
+            env,
+            verbatim_doc_comment,
+            value_name = TREE_SITTER_QUERY_FILENAME,
+            value_hint = ValueHint::FilePath
+        )]
+        cpp_query_file: Vec<PathBuf>,
+    }
+
     #[derive(Parser, Debug, Clone)]
     #[group(required = false, multiple = false)]
     struct CSharpScope {
@@ -1457,7 +4663,13 @@ mod cli {
         csharp_query: Vec<QueryLiteral>,
 
         /// Scope C# code using a custom tree-sitter query from file.
-        #[arg(long, env, verbatim_doc_comment, value_name = TREE_SITTER_QUERY_FILENAME)]
+        #[arg(
+            long,
+            env,
+            verbatim_doc_comment,
+            value_name = TREE_SITTER_QUERY_FILENAME,
+            value_hint = ValueHint::FilePath
+        )]
         csharp_query_file: Vec<PathBuf>,
     }
 
@@ -1477,7 +4689,13 @@ mod cli {
         #[allow(clippy::doc_markdown)] // CamelCase detected as 'needs backticks'
         /// Scope HashiCorp Configuration Language code using a custom tree-sitter query
         /// from file.
-        #[arg(long, env, verbatim_doc_comment, value_name = TREE_SITTER_QUERY_FILENAME)]
+        #[arg(
+            long,
+            env,
+            verbatim_doc_comment,
+            value_name = TREE_SITTER_QUERY_FILENAME,
+            value_hint = ValueHint::FilePath
+        )]
         hcl_query_file: Vec<PathBuf>,
     }
 
@@ -1493,7 +4711,13 @@ mod cli {
         go_query: Vec<QueryLiteral>,
 
         /// Scope Go code using a custom tree-sitter query from file.
-        #[arg(long, env, verbatim_doc_comment, value_name = TREE_SITTER_QUERY_FILENAME)]
+        #[arg(
+            long,
+            env,
+            verbatim_doc_comment,
+            value_name = TREE_SITTER_QUERY_FILENAME,
+            value_hint = ValueHint::FilePath
+        )]
         go_query_file: Vec<PathBuf>,
     }
 
@@ -1509,7 +4733,13 @@ mod cli {
         python_query: Vec<QueryLiteral>,
 
         /// Scope Python code using a custom tree-sitter query from file.
-        #[arg(long, env, verbatim_doc_comment, value_name = TREE_SITTER_QUERY_FILENAME)]
+        #[arg(
+            long,
+            env,
+            verbatim_doc_comment,
+            value_name = TREE_SITTER_QUERY_FILENAME,
+            value_hint = ValueHint::FilePath
+        )]
         python_query_file: Vec<PathBuf>,
     }
 
@@ -1525,7 +4755,13 @@ mod cli {
         rust_query: Vec<QueryLiteral>,
 
         /// Scope Rust code using a custom tree-sitter query from file.
-        #[arg(long, env, verbatim_doc_comment, value_name = TREE_SITTER_QUERY_FILENAME)]
+        #[arg(
+            long,
+            env,
+            verbatim_doc_comment,
+            value_name = TREE_SITTER_QUERY_FILENAME,
+            value_hint = ValueHint::FilePath
+        )]
         rust_query_file: Vec<PathBuf>,
     }
 
@@ -1541,10 +4777,117 @@ mod cli {
         typescript_query: Vec<QueryLiteral>,
 
         /// Scope TypeScript code using a custom tree-sitter query from file.
-        #[arg(long, env, verbatim_doc_comment, value_name = TREE_SITTER_QUERY_FILENAME)]
+        #[arg(
+            long,
+            env,
+            verbatim_doc_comment,
+            value_name = TREE_SITTER_QUERY_FILENAME,
+            value_hint = ValueHint::FilePath
+        )]
         typescript_query_file: Vec<PathBuf>,
     }
 
+    #[derive(Parser, Debug, Clone)]
+    #[group(required = false, multiple = true)]
+    struct GrammarScope {
+        /// Scope code of a language not built in, using a tree-sitter grammar loaded
+        /// from the given shared library.
+        ///
+        /// Such libraries are built from a grammar's repository by `tree-sitter
+        /// build`. Requires a custom query, as there are no prepared ones.
+        #[arg(
+            long,
+            env,
+            verbatim_doc_comment,
+            value_name = "LIBRARY",
+            value_hint = ValueHint::FilePath,
+            requires = "grammar_queries"
+        )]
+        grammar: Option<PathBuf>,
+
+        /// Name of the grammar, as in the `tree_sitter_<NAME>` function it exports.
+        ///
+        /// Derived from the library's file name by default, for example `ruby` for
+        /// `libtree-sitter-ruby.so`.
+        #[arg(
+            long,
+            env,
+            verbatim_doc_comment,
+            value_name = "NAME",
+            requires = "grammar"
+        )]
+        grammar_name: Option<String>,
+
+        /// File extension (without leading dot) of the grammar's language, to pick
+        /// files by when searching directories.
+        #[arg(
+            long,
+            env,
+            verbatim_doc_comment,
+            value_name = "EXTENSION",
+            requires = "grammar"
+        )]
+        grammar_extension: Vec<String>,
+
+        /// Scope code of the grammar's language using a custom tree-sitter query.
+        #[arg(
+            long,
+            env,
+            verbatim_doc_comment,
+            value_name = TREE_SITTER_QUERY_VALUE,
+            group = "grammar_queries",
+            requires = "grammar"
+        )]
+        grammar_query: Vec<QueryLiteral>,
+
+        /// Scope code of the grammar's language using a custom tree-sitter query from
+        /// file.
+        #[arg(
+            long,
+            env,
+            verbatim_doc_comment,
+            value_name = TREE_SITTER_QUERY_FILENAME,
+            value_hint = ValueHint::FilePath,
+            group = "grammar_queries",
+            requires = "grammar"
+        )]
+        grammar_query_file: Vec<PathBuf>,
+    }
+
+    impl GrammarScope {
+        /// Loads the grammar and compiles all its queries into scopers.
+        fn compile(
+            self,
+            captures: &[String],
+            capture_contexts: bool,
+        ) -> Result<crate::ScoperList, ProgramError> {
+            let path = self
+                .grammar
+                .expect("required by all other grammar arguments");
+            let grammar = external::Grammar::load(&path, self.grammar_name.as_deref())?
+                .with_extensions(self.grammar_extension);
+
+            let query_sources = self
+                .grammar_query
+                .into_iter()
+                .map(|query_literal| Ok(query_literal.into()))
+                .chain(
+                    self.grammar_query_file
+                        .into_iter()
+                        .map(read_query_from_file),
+                );
+
+            let mut scopers: crate::ScoperList = Vec::new();
+            for query_source in query_sources {
+                let query_source = select_captures(query_source?, captures, capture_contexts);
+                let compiled_query = external::CompiledQuery::new(grammar.clone(), &query_source)?;
+                scopers.push(Box::new(compiled_query));
+            }
+
+            Ok(scopers)
+        }
+    }
+
     #[cfg(feature = "german")]
     #[derive(Parser, Debug)]
     #[group(required = false, multiple = true, id("german-opts"))]
@@ -1584,9 +4927,165 @@ mod cli {
         }
     }
 
+    impl ComposableActions {
+        /// All replacement values given, across actions.
+        pub(super) fn replacement_values_mut(&mut self) -> impl Iterator<Item = &mut String> {
+            self.replace
+                .iter_mut()
+                .chain(self.capture_replacements.iter_mut().map(|cr| &mut cr.value))
+                .chain(
+                    self.ordered_actions
+                        .iter_mut()
+                        .filter_map(|action| match action {
+                            OrderedAction::Replace(value) => Some(value),
+                            _ => None,
+                        }),
+                )
+        }
+    }
+
+    impl GlobalOptions {
+        /// The author pattern and date to restrict scoping to via `git blame`, if
+        /// requested at all.
+        pub(super) fn blame(&self) -> Option<(Option<&RegexPattern>, Option<&str>)> {
+            match (&self.blame_author, &self.blame_since) {
+                (None, None) => None,
+                (author, since) => Some((author.as_ref(), since.as_deref())),
+            }
+        }
+
+        /// The git reference to look for changed files against, and whether to only
+        /// consider staged changes, if requested at all.
+        pub(super) fn changed_files(&self) -> Option<(String, bool)> {
+            match (&self.changed, self.staged) {
+                (Some(reference), staged) => Some((reference.clone(), staged)),
+                (None, true) => Some(("HEAD".into(), true)),
+                (None, false) => None,
+            }
+        }
+
+        /// How to combine the scope with language scopes.
+        pub(super) const fn scope_operator(&self) -> ScopeOperator {
+            if self.or {
+                ScopeOperator::Or
+            } else if self.not {
+                ScopeOperator::Not
+            } else {
+                ScopeOperator::And
+            }
+        }
+    }
+
+    /// Options of [`GlobalOptions`] which may be given per stage, see
+    /// [`GlobalOptions::then`].
+    const STAGE_OPTIONS: &[&str] = &[
+        "invert",
+        "literal_string",
+        "word",
+        "columns",
+        "indentation",
+        "ignore_case",
+        "multi_line",
+        "dot_matches_new_line",
+        "group",
+        "preset",
+        "join_language_scopes",
+        "and",
+        "capture",
+        "or",
+        "not",
+        "invert_scope",
+    ];
+
+    impl GlobalOptions {
+        /// Swaps all options which apply per stage with those of `other`.
+        fn swap_stage_options(&mut self, other: &mut Self) {
+            #[cfg(feature = "symbols")]
+            mem::swap(&mut self.invert, &mut other.invert);
+            mem::swap(&mut self.literal_string, &mut other.literal_string);
+            mem::swap(&mut self.word, &mut other.word);
+            mem::swap(&mut self.columns, &mut other.columns);
+            mem::swap(&mut self.indentation, &mut other.indentation);
+            mem::swap(&mut self.ignore_case, &mut other.ignore_case);
+            mem::swap(&mut self.multi_line, &mut other.multi_line);
+            mem::swap(
+                &mut self.dot_matches_new_line,
+                &mut other.dot_matches_new_line,
+            );
+            mem::swap(&mut self.group, &mut other.group);
+            mem::swap(&mut self.preset, &mut other.preset);
+            mem::swap(
+                &mut self.join_language_scopes,
+                &mut other.join_language_scopes,
+            );
+            mem::swap(&mut self.and, &mut other.and);
+            mem::swap(&mut self.capture, &mut other.capture);
+            mem::swap(&mut self.or, &mut other.or);
+            mem::swap(&mut self.not, &mut other.not);
+            mem::swap(&mut self.invert_scope, &mut other.invert_scope);
+        }
+    }
+
     impl Args {
+        /// Parses the command line, splitting it into stages at each `--then`.
         pub(super) fn init() -> Self {
-            Self::parse()
+            let mut args = env::args_os();
+            let bin = args.next().unwrap_or_else(|| "srgn".into());
+
+            let mut segments = split_stages(args).into_iter();
+            let first = segments.next().unwrap_or_default();
+            let mut parsed = Self::parse_from(iter::once(bin.clone()).chain(first));
+
+            for segment in segments {
+                if segment.is_empty() {
+                    Self::command()
+                        .error(ErrorKind::InvalidValue, "Stages cannot be empty.")
+                        .exit();
+                }
+
+                let matches = Self::command()
+                    .try_get_matches_from(iter::once(bin.clone()).chain(segment))
+                    .unwrap_or_else(|e| e.exit());
+
+                if let Some(id) = matches.ids().find(|id| {
+                    matches.value_source(id.as_str()) == Some(ValueSource::CommandLine)
+                        && is_global_arg(id.as_str())
+                }) {
+                    let mut command = Self::command();
+                    let flag = command
+                        .get_arguments()
+                        .find(|arg| arg.get_id() == id)
+                        .and_then(|arg| arg.get_long())
+                        .map_or_else(|| id.to_string(), |long| format!("--{long}"));
+
+                    command
+                        .error(
+                            ErrorKind::ArgumentConflict,
+                            format!(
+                                "'{flag}' applies to all stages, so can only be given before the first '--then'."
+                            ),
+                        )
+                        .exit();
+                }
+
+                parsed
+                    .stages
+                    .push(Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit()));
+            }
+
+            parsed
+        }
+
+        /// Swaps everything which applies per stage (scopes, actions and their
+        /// options) with `other`.
+        pub(super) fn swap_stage(&mut self, other: &mut Self) {
+            mem::swap(&mut self.scope, &mut other.scope);
+            mem::swap(&mut self.composable_actions, &mut other.composable_actions);
+            mem::swap(&mut self.standalone_actions, &mut other.standalone_actions);
+            mem::swap(&mut self.languages_scopes, &mut other.languages_scopes);
+            #[cfg(feature = "german")]
+            mem::swap(&mut self.german_options, &mut other.german_options);
+            self.options.swap_stage_options(&mut other.options);
         }
 
         pub(super) fn command() -> Command {
@@ -1671,7 +5170,7 @@ mod tests {
             // Sanity check for sequential tests
             let i_am_not_sure_if_this_test_really_runs_sequentially = false;
             if i_am_not_sure_if_this_test_really_runs_sequentially {
-                std::thread::sleep(std::time::Duration::from_secs(2));
+                thread::sleep(Duration::from_secs(2));
             }
 
             let result = level_filter_from_env_and_verbosity(additional_verbosity);
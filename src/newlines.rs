@@ -0,0 +1,184 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use log::trace;
+
+/// A style of line ending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    /// Unix-style, `\n`.
+    #[default]
+    Lf,
+    /// DOS-style, `\r\n`.
+    CrLf,
+}
+
+impl LineEnding {
+    /// Detect the dominant line ending of the given `input`.
+    ///
+    /// Ties, as well as inputs without any line endings, resolve to [`LineEnding::Lf`].
+    #[must_use]
+    pub fn detect(input: &str) -> Self {
+        let mut n_lf: usize = 0;
+        let mut n_crlf: usize = 0;
+
+        for (i, _) in input.match_indices('\n') {
+            if input[..i].ends_with('\r') {
+                n_crlf += 1;
+            } else {
+                n_lf += 1;
+            }
+        }
+
+        trace!("Detected {n_lf} LF and {n_crlf} CRLF line endings.");
+
+        if n_crlf > n_lf {
+            Self::CrLf
+        } else {
+            Self::Lf
+        }
+    }
+
+    /// The raw string representation of this line ending.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+
+    /// Convert all line endings in `input` to this one.
+    ///
+    /// If `preceded_by_cr`, the `input` is taken to directly follow a carriage return,
+    /// such that a leading `\n` is already part of a `\r\n` pair and left alone.
+    ///
+    /// Only allocates if anything needs converting.
+    #[must_use]
+    pub fn convert(self, input: &str, preceded_by_cr: bool) -> Cow<'_, str> {
+        match self {
+            Self::Lf => {
+                if input.contains("\r\n") {
+                    Cow::Owned(input.replace("\r\n", "\n"))
+                } else {
+                    Cow::Borrowed(input)
+                }
+            }
+            Self::CrLf => {
+                let is_bare = |i: usize| {
+                    if i == 0 {
+                        !preceded_by_cr
+                    } else {
+                        !input[..i].ends_with('\r')
+                    }
+                };
+
+                if !input.match_indices('\n').any(|(i, _)| is_bare(i)) {
+                    return Cow::Borrowed(input);
+                }
+
+                let mut res = String::with_capacity(input.len());
+                let mut last = 0;
+                for (i, _) in input.match_indices('\n') {
+                    res.push_str(&input[last..i]);
+                    if is_bare(i) {
+                        res.push('\r');
+                    }
+                    res.push('\n');
+                    last = i + 1;
+                }
+                res.push_str(&input[last..]);
+
+                Cow::Owned(res)
+            }
+        }
+    }
+}
+
+/// Splits `line` into its content and its line ending (`\n` or `\r\n`), which is empty
+/// for a last line without one.
+///
+/// Only a single line ending is split off, so `"a\r\r\n"` has content `"a\r"`.
+#[must_use]
+pub fn split_line_ending(line: &str) -> (&str, &str) {
+    let content = line
+        .strip_suffix('\n')
+        .map_or(line, |l| l.strip_suffix('\r').unwrap_or(l));
+
+    line.split_at(content.len())
+}
+
+/// The lines of `input`, each split into content and line ending, see
+/// [`split_line_ending`].
+pub fn lines(input: &str) -> impl Iterator<Item = (&str, &str)> {
+    input.split_inclusive('\n').map(split_line_ending)
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str().escape_debug())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("", LineEnding::Lf)]
+    #[case("no newlines", LineEnding::Lf)]
+    #[case("a\nb\n", LineEnding::Lf)]
+    #[case("a\r\nb\r\n", LineEnding::CrLf)]
+    #[case("a\r\nb\n", LineEnding::Lf)] // Tie
+    #[case("a\r\nb\r\nc\n", LineEnding::CrLf)]
+    #[case("a\r\nb\nc\n", LineEnding::Lf)]
+    #[case("\r", LineEnding::Lf)]
+    fn test_detect(#[case] input: &str, #[case] expected: LineEnding) {
+        assert_eq!(LineEnding::detect(input), expected);
+    }
+
+    #[rstest]
+    #[case(LineEnding::Lf, "a\nb", false, "a\nb")]
+    #[case(LineEnding::Lf, "a\r\nb", false, "a\nb")]
+    #[case(LineEnding::Lf, "a\rb", false, "a\rb")]
+    #[case(LineEnding::CrLf, "a\nb", false, "a\r\nb")]
+    #[case(LineEnding::CrLf, "a\r\nb", false, "a\r\nb")]
+    #[case(LineEnding::CrLf, "a\n\nb\r\n", false, "a\r\n\r\nb\r\n")]
+    #[case(LineEnding::CrLf, "\nb", false, "\r\nb")]
+    #[case(LineEnding::CrLf, "\nb", true, "\nb")]
+    #[case(LineEnding::CrLf, "\n\nb", true, "\n\r\nb")]
+    #[case(LineEnding::CrLf, "", true, "")]
+    fn test_convert(
+        #[case] ending: LineEnding,
+        #[case] input: &str,
+        #[case] preceded_by_cr: bool,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(ending.convert(input, preceded_by_cr), expected);
+    }
+
+    #[rstest]
+    #[case("", vec![])]
+    #[case("a", vec![("a", "")])]
+    #[case("a\nb", vec![("a", "\n"), ("b", "")])]
+    #[case("a\r\n\n", vec![("a", "\r\n"), ("", "\n")])]
+    #[case("a\r\r\n", vec![("a\r", "\r\n")])]
+    #[case("a\rb", vec![("a\rb", "")])]
+    fn test_lines(#[case] input: &str, #[case] expected: Vec<(&str, &str)>) {
+        assert_eq!(lines(input).collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_convert_does_not_allocate_needlessly() {
+        assert!(matches!(
+            LineEnding::CrLf.convert("a\r\nb", false),
+            Cow::Borrowed(_)
+        ));
+        assert!(matches!(
+            LineEnding::Lf.convert("a\nb", false),
+            Cow::Borrowed(_)
+        ));
+    }
+}
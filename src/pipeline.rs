@@ -0,0 +1,272 @@
+use std::fmt;
+
+use log::debug;
+
+use crate::actions::{Action, ActionError, Actions};
+#[cfg(doc)]
+use crate::scoping::view::ScopedView;
+use crate::scoping::view::ScopedViewBuilder;
+use crate::scoping::Scoper;
+
+/// A chain of [`Scoper`]s and [`Action`]s, assembled at runtime.
+///
+/// Where the concrete types are only known at runtime (for example, when they come
+/// from user configuration), generic combinations of them are of no help. A pipeline
+/// takes trait objects instead.
+///
+/// When [run][`Self::run`], the scopers narrow the input down in order (see
+/// [`ScopedViewBuilder::explode`]), after which the actions apply in order to whatever
+/// is left in scope (see [`ScopedView::map_with_context`]). Without any scopers, all
+/// input is in scope.
+///
+/// ## Example
+///
+/// ```rust
+/// use srgn::actions::{Action, Replacement, Upper};
+/// use srgn::pipeline::Pipeline;
+/// use srgn::scoping::literal::Literal;
+/// use srgn::scoping::regex::Regex;
+/// use srgn::scoping::Scoper;
+///
+/// let scopers: Vec<Box<dyn Scoper>> = vec![Box::new(Regex::try_from(String::from(r"\w+")).unwrap())];
+/// let actions: Vec<Box<dyn Action>> = vec![Box::new(Upper::default())];
+///
+/// let pipeline = Pipeline::new(scopers, actions);
+/// assert_eq!(pipeline.run("hello, world").unwrap(), "HELLO, WORLD");
+///
+/// // Or, piece by piece:
+/// let pipeline = Pipeline::default()
+///     .with_scoper(Literal::try_from(String::from("world")).unwrap())
+///     .with_action(Replacement::try_from(String::from("there")).unwrap());
+/// assert_eq!(pipeline.run("hello, world").unwrap(), "hello, there");
+/// ```
+#[derive(Default)]
+pub struct Pipeline {
+    scopers: Vec<Box<dyn Scoper>>,
+    actions: Vec<Box<dyn Action>>,
+}
+
+impl fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("scopers", &self.scopers.len())
+            .field("actions", &self.actions.len())
+            .finish()
+    }
+}
+
+impl Pipeline {
+    /// Create a new pipeline from the given `scopers` and `actions`, each applied in
+    /// order.
+    #[must_use]
+    pub fn new(scopers: Vec<Box<dyn Scoper>>, actions: Vec<Box<dyn Action>>) -> Self {
+        Self { scopers, actions }
+    }
+
+    /// Append `scoper`, narrowing down the scopes of all scopers before it.
+    #[must_use]
+    pub fn with_scoper(mut self, scoper: impl Scoper + 'static) -> Self {
+        self.scopers.push(Box::new(scoper));
+        self
+    }
+
+    /// Append `action`, applied after all actions before it.
+    #[must_use]
+    pub fn with_action(mut self, action: impl Action + 'static) -> Self {
+        self.actions.push(Box::new(action));
+        self
+    }
+
+    /// Run the pipeline on `input`, returning the result.
+    ///
+    /// # Errors
+    ///
+    /// Errors if any of the actions fails.
+    pub fn run(&self, input: &str) -> Result<String, ActionError> {
+        debug!("Running pipeline: {self:?}");
+
+        let mut builder = ScopedViewBuilder::new(input);
+        for scoper in &self.scopers {
+            builder.explode(scoper);
+        }
+
+        let mut view = builder.build();
+        view.map_with_context(&Actions::new(&self.actions))?;
+
+        Ok(view.to_string())
+    }
+}
+
+/// [`Pipeline`]s run one after the other, each on the output of the one before.
+///
+/// Unlike chaining scopers in a single pipeline, which narrows down the *same* input,
+/// each stage scopes its input afresh. Later stages therefore see the effects of
+/// earlier ones: comments deleted in one stage are gone for the next, and names
+/// changed in one stage can be scoped by their new spelling in the next. This spares
+/// parsing the output of one run as the input of another, as piping between separate
+/// invocations would.
+///
+/// ## Example
+///
+/// ```rust
+/// use srgn::actions::{Deletion, Upper};
+/// use srgn::pipeline::{Pipeline, Stages};
+/// use srgn::scoping::regex::Regex;
+///
+/// let stages = Stages::default()
+///     .with_stage(
+///         Pipeline::default()
+///             .with_scoper(Regex::try_from(String::from(r"\s*#.*")).unwrap())
+///             .with_action(Deletion::default()),
+///     )
+///     .with_stage(
+///         // Without the first stage, the comment would be uppercased, too.
+///         Pipeline::default()
+///             .with_scoper(Regex::try_from(String::from(r"\w+$")).unwrap())
+///             .with_action(Upper::default()),
+///     );
+///
+/// assert_eq!(stages.run("x = y  # note").unwrap(), "x = Y");
+/// ```
+#[derive(Debug, Default)]
+pub struct Stages(Vec<Pipeline>);
+
+impl Stages {
+    /// Create new stages from the given `pipelines`, run in order.
+    #[must_use]
+    pub const fn new(pipelines: Vec<Pipeline>) -> Self {
+        Self(pipelines)
+    }
+
+    /// Append `pipeline` as a stage, run on the output of all stages before it.
+    #[must_use]
+    pub fn with_stage(mut self, pipeline: Pipeline) -> Self {
+        self.0.push(pipeline);
+        self
+    }
+
+    /// Run all stages on `input`, returning the output of the last one. Without any
+    /// stages, that is `input` itself.
+    ///
+    /// # Errors
+    ///
+    /// Errors if any of the stages fails, stopping there.
+    pub fn run(&self, input: &str) -> Result<String, ActionError> {
+        let mut output = input.to_owned();
+        for (i, pipeline) in self.0.iter().enumerate() {
+            debug!("Running stage {}.", i + 1);
+            output = pipeline.run(&output)?;
+        }
+
+        Ok(output)
+    }
+}
+
+impl FromIterator<Pipeline> for Stages {
+    fn from_iter<T: IntoIterator<Item = Pipeline>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::actions::{Deletion, Lower, Upper};
+    use crate::scoping::langs::python::{CompiledQuery, PreparedQuery};
+    use crate::scoping::regex::Regex;
+
+    fn regex(pattern: &str) -> Box<dyn Scoper> {
+        Box::new(Regex::try_from(pattern.to_owned()).unwrap())
+    }
+
+    fn action(action: impl Action + 'static) -> Box<dyn Action> {
+        Box::new(action)
+    }
+
+    #[rstest]
+    #[case(vec![], vec![], "Hello World")]
+    #[case(vec![], vec![action(Upper::default())], "HELLO WORLD")]
+    #[case(vec![regex("o")], vec![action(Deletion::default())], "Hell Wrld")]
+    #[case(vec![regex(r"\w+"), regex("^W")], vec![action(Lower::default())], "Hello world")] // Narrowing down
+    #[case(
+        vec![regex("World")],
+        vec![action(Upper::default()), action(|s: &str| format!("{s}!"))],
+        "Hello WORLD!"
+    )] // In order
+    fn test_run(
+        #[case] scopers: Vec<Box<dyn Scoper>>,
+        #[case] actions: Vec<Box<dyn Action>>,
+        #[case] expected: &str,
+    ) {
+        let pipeline = Pipeline::new(scopers, actions);
+
+        assert_eq!(pipeline.run("Hello World").unwrap(), expected);
+    }
+
+    #[test]
+    fn test_run_with_language_scoper() {
+        let pipeline = Pipeline::default()
+            .with_scoper(CompiledQuery::from(PreparedQuery::Comments))
+            .with_action(Upper::default());
+
+        assert_eq!(pipeline.run("x = 1  # a\n").unwrap(), "x = 1  # A\n");
+    }
+
+    #[test]
+    fn test_run_failing_action() {
+        struct Failing;
+
+        impl Action for Failing {
+            fn act(&self, input: &str) -> String {
+                self.try_act(input).unwrap()
+            }
+
+            fn try_act(&self, _input: &str) -> Result<String, ActionError> {
+                Err(ActionError::Failed("nope".to_owned()))
+            }
+        }
+
+        let pipeline = Pipeline::default()
+            .with_scoper(Regex::try_from("a".to_owned()).unwrap())
+            .with_action(Failing);
+
+        assert_eq!(pipeline.run("b").unwrap(), "b"); // Nothing in scope
+        assert_eq!(
+            pipeline.run("a"),
+            Err(ActionError::Failed("nope".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_stages_run_on_previous_output() {
+        let stages = Stages::default()
+            .with_stage(Pipeline::new(
+                vec![regex("o")],
+                vec![action(|_: &str| String::from("0"))],
+            ))
+            // Scopes what the first stage produced.
+            .with_stage(Pipeline::new(
+                vec![regex(r"\w0")],
+                vec![action(Upper::default())],
+            ));
+
+        assert_eq!(stages.run("Hello World").unwrap(), "HelL0 W0rld");
+    }
+
+    #[test]
+    fn test_stages_empty() {
+        assert_eq!(Stages::default().run("Hello").unwrap(), "Hello");
+    }
+
+    #[test]
+    fn test_stages_from_iter() {
+        let stages: Stages = ["l", "e"]
+            .into_iter()
+            .map(|pattern| Pipeline::new(vec![regex(pattern)], vec![action(Deletion::default())]))
+            .collect();
+
+        assert_eq!(stages.run("Hello").unwrap(), "Ho");
+    }
+}
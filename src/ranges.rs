@@ -1,5 +1,6 @@
-use std::fmt::Debug;
-use std::ops::{Range, Sub};
+use std::error::Error;
+use std::fmt::{self, Debug};
+use std::ops::{Range, RangeInclusive, Sub};
 use std::slice::{Iter, IterMut};
 
 use itertools::Itertools;
@@ -9,6 +10,7 @@ use log::{debug, trace};
 ///
 /// This type implements a couple utility functions to work with collections of ranges.
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
 pub struct Ranges<Idx: Ord + Copy + Debug> {
     inner: Vec<Range<Idx>>,
 }
@@ -298,6 +300,80 @@ impl<Idx: Ord + Copy + Debug> Sub for Ranges<Idx> {
     }
 }
 
+/// Parses an inclusive range of numbers, like `3..5`.
+///
+/// All options taking ranges (`--lines`, `--columns`, `--indentation`) accept them this
+/// way: `N` is just `N`, `N..` is `N` or more, `..M` is `M` or less, and `N..M` is
+/// anything in between.
+///
+/// Numbers start at `min`, which is also where an open start starts. An open end ends
+/// at [`usize::MAX`].
+///
+/// # Errors
+///
+/// Errors if `s` is of none of the above forms, contains numbers below `min`, or ends
+/// before it starts.
+pub fn parse_range(s: &str, min: usize) -> Result<RangeInclusive<usize>, ParseRangeError> {
+    let parse = |n: &str| match n.trim().parse::<usize>() {
+        Ok(n) if n < min => Err(ParseRangeError::BelowMinimum(s.to_owned(), min)),
+        Ok(n) => Ok(n),
+        Err(_) => Err(ParseRangeError::Invalid(s.to_owned())),
+    };
+
+    let (start, end) = match s.split_once("..") {
+        None => {
+            let n = parse(s)?;
+            (n, n)
+        }
+        Some((start, end)) => (
+            if start.trim().is_empty() {
+                min
+            } else {
+                parse(start)?
+            },
+            if end.trim().is_empty() {
+                usize::MAX
+            } else {
+                parse(end)?
+            },
+        ),
+    };
+
+    if end < start {
+        return Err(ParseRangeError::Decreasing(s.to_owned()));
+    }
+
+    Ok(start..=end)
+}
+
+/// An error that can occur when parsing a range, see [`parse_range`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseRangeError {
+    /// The range is not of the form `N`, `N..`, `..M` or `N..M`.
+    Invalid(String),
+    /// The range contains a number below the given minimum.
+    BelowMinimum(String, usize),
+    /// The range ends before it starts.
+    Decreasing(String),
+}
+
+impl fmt::Display for ParseRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(range) => write!(
+                f,
+                "Invalid range '{range}', expected one of 'N', 'N..', '..M' or 'N..M'"
+            ),
+            Self::BelowMinimum(range, min) => {
+                write!(f, "Invalid range '{range}', numbers start at {min}")
+            }
+            Self::Decreasing(range) => write!(f, "Invalid range '{range}', end is before start"),
+        }
+    }
+}
+
+impl Error for ParseRangeError {}
+
 #[cfg(test)]
 mod tests {
     use proptest::prelude::*;
@@ -606,4 +682,28 @@ mod tests {
             prop_assert_eq!(ranges, first);
         }
     }
+
+    #[rstest]
+    #[case("3", 1, Ok(3..=3))]
+    #[case("3..5", 1, Ok(3..=5))]
+    #[case("3..", 1, Ok(3..=usize::MAX))]
+    #[case("..5", 1, Ok(1..=5))]
+    #[case("..5", 0, Ok(0..=5))]
+    #[case("..", 1, Ok(1..=usize::MAX))]
+    #[case(" 7 .. 72 ", 1, Ok(7..=72))]
+    #[case("3..3", 1, Ok(3..=3))]
+    #[case("", 1, Err(ParseRangeError::Invalid(String::new())))]
+    #[case("a..b", 1, Err(ParseRangeError::Invalid(String::from("a..b"))))]
+    #[case("3-5", 1, Err(ParseRangeError::Invalid(String::from("3-5"))))]
+    #[case("3..=5", 1, Err(ParseRangeError::Invalid(String::from("3..=5"))))]
+    #[case("-3", 0, Err(ParseRangeError::Invalid(String::from("-3"))))]
+    #[case("0..3", 1, Err(ParseRangeError::BelowMinimum(String::from("0..3"), 1)))]
+    #[case("5..3", 1, Err(ParseRangeError::Decreasing(String::from("5..3"))))]
+    fn test_parse_range(
+        #[case] input: &str,
+        #[case] min: usize,
+        #[case] expected: Result<RangeInclusive<usize>, ParseRangeError>,
+    ) {
+        assert_eq!(parse_range(input, min), expected);
+    }
 }
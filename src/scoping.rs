@@ -6,12 +6,22 @@ use crate::scoping::scope::ROScopes;
 #[cfg(doc)]
 use crate::scoping::{scope::Scope, view::ScopedView};
 
+/// Create scoped views using fixed column ranges of each line.
+pub mod columns;
+/// Combine [`Scoper`]s using set logic: intersections, unions and more.
+pub mod combinators;
 /// Fixes for DOS-style line endings.
 pub mod dosfix;
+/// Create scoped views using indentation depths of lines.
+pub mod indentation;
 /// Create scoped views using programming language grammar-aware types.
 pub mod langs;
 /// Create scoped views using string literals.
 pub mod literal;
+/// Exclude parts of the input from scoping using inline markers.
+pub mod markers;
+/// Create scoped views using curated, named patterns, such as for secrets.
+pub mod presets;
 /// Create scoped views using regular expressions.
 pub mod regex;
 /// [`Scope`] and its various wrappers.
@@ -44,3 +54,9 @@ impl Scoper for Box<dyn Scoper> {
         self.as_ref().scope_raw(input)
     }
 }
+
+impl Scoper for &dyn Scoper {
+    fn scope_raw<'viewee>(&self, input: &'viewee str) -> RangesWithContext<'viewee> {
+        (*self).scope_raw(input)
+    }
+}
@@ -0,0 +1,168 @@
+use std::ops::Range;
+use std::str::FromStr;
+
+use log::trace;
+
+use super::scope::RangesWithContext;
+use super::Scoper;
+use crate::ranges::{parse_range, ParseRangeError, Ranges};
+
+/// Fixed column ranges of each line, for querying fixed-width data.
+///
+/// Columns are counted in characters, starting at 1, as `cut -c` does. Line endings
+/// are never in scope, and lines too short for a range are scoped only as far as they
+/// reach.
+///
+/// ## Example
+///
+/// ```rust
+/// use srgn::scoping::columns::Columns;
+/// use srgn::scoping::Scoper;
+///
+/// let columns: Columns = "3..4,7..".parse().unwrap();
+/// let ranges: Vec<_> = columns
+///     .scope_raw("abcdefgh\nab\n")
+///     .into_iter()
+///     .map(|(range, _)| range)
+///     .collect();
+///
+/// assert_eq!(ranges, vec![2..4, 6..8]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Columns {
+    /// Sorted, non-overlapping, zero-based and end-exclusive character ranges.
+    ranges: Vec<Range<usize>>,
+}
+
+impl Columns {
+    /// Scopes the given column `ranges`, which are zero-based and end-exclusive.
+    /// Overlapping ranges are merged; empty ones are dropped.
+    #[must_use]
+    pub fn new(ranges: impl IntoIterator<Item = Range<usize>>) -> Self {
+        let mut ranges: Vec<_> = ranges.into_iter().filter(|r| !r.is_empty()).collect();
+        ranges.sort_by_key(|r| r.start);
+
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        Self { ranges: merged }
+    }
+
+    /// Byte ranges of `line` (without its line ending) which are in scope.
+    fn scope_line(&self, line: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+        // Byte offsets of each character's start, plus the end of the line, so that
+        // column `n` spans `offsets[n]..offsets[n + 1]`.
+        let offsets: Vec<usize> = line
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(line.len()))
+            .collect();
+        let n_chars = offsets.len() - 1;
+
+        self.ranges
+            .iter()
+            .take_while(move |r| r.start < n_chars)
+            .map(move |r| offsets[r.start]..offsets[r.end.min(n_chars)])
+    }
+}
+
+impl FromStr for Columns {
+    type Err = ParseRangeError;
+
+    /// Parses a comma-separated list of one-based, inclusive ranges, as per
+    /// [`parse_range`]: `N` is column `N` only, `N..` from column `N` to the end of the
+    /// line, `..M` from the start of the line to column `M`, and `N..M` from column `N`
+    /// to `M`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ranges = s
+            .split(',')
+            // One-based and inclusive to zero-based and exclusive.
+            .map(|range| parse_range(range, 1).map(|r| *r.start() - 1..*r.end()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self::new(ranges))
+    }
+}
+
+impl Scoper for Columns {
+    fn scope_raw<'viewee>(&self, input: &'viewee str) -> RangesWithContext<'viewee> {
+        let mut offset = 0;
+        let ranges: Ranges<usize> = input
+            .split_inclusive('\n')
+            .flat_map(|line| {
+                let start = offset;
+                offset += line.len();
+
+                let content = line.strip_suffix('\n').unwrap_or(line);
+                let content = content.strip_suffix('\r').unwrap_or(content);
+
+                self.scope_line(content)
+                    .map(move |r| r.start + start..r.end + start)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        trace!("Ranges in scope for {:?}: {:?}", self, ranges);
+
+        ranges.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("1", Ok(vec![0..1]))]
+    #[case("3..5", Ok(vec![2..5]))]
+    #[case("3..", Ok(vec![2..usize::MAX]))]
+    #[case("..3", Ok(vec![0..3]))]
+    #[case(" 7 .. 72 ", Ok(vec![6..72]))]
+    #[case("5..8,1..2", Ok(vec![0..2, 4..8]))] // Sorted
+    #[case("1..4,3..6,7", Ok(vec![0..7]))] // Overlapping and bordering are merged
+    #[case("", Err(ParseRangeError::Invalid(String::new())))]
+    #[case("a..b", Err(ParseRangeError::Invalid(String::from("a..b"))))]
+    #[case("1-3", Err(ParseRangeError::Invalid(String::from("1-3"))))]
+    #[case("1,", Err(ParseRangeError::Invalid(String::new())))]
+    #[case("0..3", Err(ParseRangeError::BelowMinimum(String::from("0..3"), 1)))]
+    #[case("5..3", Err(ParseRangeError::Decreasing(String::from("5..3"))))]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_columns_parsing(
+        #[case] input: &str,
+        #[case] expected: Result<Vec<Range<usize>>, ParseRangeError>,
+    ) {
+        assert_eq!(input.parse::<Columns>(), expected.map(Columns::new));
+    }
+
+    #[rstest]
+    #[case("abcdef", "2..3", vec![1..3])]
+    #[case("abcdef", "5..10", vec![4..6])] // Clamped to the line
+    #[case("abcdef", "7..", vec![])] // Line too short
+    #[case("abc\ndef\n", "2", vec![1..2, 5..6])]
+    #[case("abc\r\ndef", "2..", vec![1..3, 6..8])] // Line endings are not in scope
+    #[case("ab\n\nabcd\n", "3..", vec![6..8])]
+    #[case("äöü\nxyz", "2", vec![2..4, 8..9])] // Characters, not bytes
+    #[case("abcdef", "1,3,5", vec![0..1, 2..3, 4..5])]
+    #[case("", "1..", vec![])]
+    fn test_columns_scoping(
+        #[case] input: &str,
+        #[case] columns: &str,
+        #[case] expected: Vec<Range<usize>>,
+    ) {
+        let columns: Columns = columns.parse().unwrap();
+
+        let ranges: Vec<_> = columns
+            .scope_raw(input)
+            .into_iter()
+            .map(|(r, _)| r)
+            .collect();
+        assert_eq!(ranges, expected);
+    }
+}
@@ -0,0 +1,199 @@
+use log::trace;
+
+use super::scope::{RangesWithContext, ScopeContext};
+use super::Scoper;
+use crate::ranges::Ranges;
+
+/// Scopes parts matched by *both* scopers.
+///
+/// The right-hand scoper runs *within* each part the left-hand one scoped, not on the
+/// entire input, exactly as successive [`ScopedViewBuilder::explode`] calls would.
+/// Anchors like `^` in a regex on the right hence match at the start of each such
+/// part. Context of the right-hand scoper is kept, except that
+/// [`ScopeContext::QueryCapture`] of the left-hand scoper is inherited.
+///
+/// [`ScopedViewBuilder::explode`]: crate::scoping::view::ScopedViewBuilder::explode
+#[derive(Debug, Clone)]
+pub struct Intersection<L, R>(pub L, pub R);
+
+impl<L: Scoper, R: Scoper> Scoper for Intersection<L, R> {
+    fn scope_raw<'viewee>(&self, input: &'viewee str) -> RangesWithContext<'viewee> {
+        let mut ranges = Vec::new();
+
+        for (outer, ctx) in self.0.scope_raw(input) {
+            let inherited = ctx.filter(|ctx| matches!(ctx, ScopeContext::QueryCapture(_)));
+
+            for (inner, inner_ctx) in self.1.scope_raw(&input[outer.clone()]) {
+                let range = outer.start + inner.start..outer.start + inner.end;
+                ranges.push((range, inherited.clone().or(inner_ctx)));
+            }
+        }
+
+        trace!("Intersected ranges: {ranges:?}");
+        ranges
+    }
+}
+
+/// Scopes parts matched by *either* scoper.
+///
+/// Parts scoped by the left-hand scoper are kept whole, including their context (like
+/// capture groups). The right-hand scoper only fills in around those, without context.
+#[derive(Debug, Clone)]
+pub struct Union<L, R>(pub L, pub R);
+
+impl<L: Scoper, R: Scoper> Scoper for Union<L, R> {
+    fn scope_raw<'viewee>(&self, input: &'viewee str) -> RangesWithContext<'viewee> {
+        let mut ranges = self.0.scope_raw(input);
+        let left: Ranges<usize> = ranges.iter().map(|(range, _)| range.clone()).collect();
+        let right = plain_ranges(self.1.scope_raw(input));
+
+        ranges.extend(RangesWithContext::from(right - left));
+        // Either side might come first, but scopers return ranges in order.
+        ranges.sort_by_key(|(range, _)| range.start);
+
+        trace!("Joined ranges: {ranges:?}");
+        ranges
+    }
+}
+
+/// Scopes parts matched by the left-hand scoper, but *not* by the right-hand one.
+///
+/// Unlike for [`Intersection`], both scopers run on the entire input. For example,
+/// strings except those in test functions are the difference of a scoper for strings
+/// and one for test functions. As parts may be split, context is dropped.
+#[derive(Debug, Clone)]
+pub struct Difference<L, R>(pub L, pub R);
+
+impl<L: Scoper, R: Scoper> Scoper for Difference<L, R> {
+    fn scope_raw<'viewee>(&self, input: &'viewee str) -> RangesWithContext<'viewee> {
+        let left = plain_ranges(self.0.scope_raw(input));
+        let right = plain_ranges(self.1.scope_raw(input));
+
+        let ranges = left - right;
+        trace!("Subtracted ranges: {ranges:?}");
+        ranges.into()
+    }
+}
+
+/// Scopes everything the wrapped scoper does *not* scope.
+#[derive(Debug, Clone)]
+pub struct Negation<S>(pub S);
+
+impl<S: Scoper> Scoper for Negation<S> {
+    fn scope_raw<'viewee>(&self, input: &'viewee str) -> RangesWithContext<'viewee> {
+        let everything: Ranges<usize> = (!input.is_empty())
+            .then_some(0..input.len())
+            .into_iter()
+            .collect();
+
+        let ranges = everything - plain_ranges(self.0.scope_raw(input));
+        trace!("Negated ranges: {ranges:?}");
+        ranges.into()
+    }
+}
+
+/// Drops all context, keeping only the ranges.
+fn plain_ranges(ranges: RangesWithContext<'_>) -> Ranges<usize> {
+    ranges.into_iter().map(|(range, _)| range).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use rstest::rstest;
+
+    use super::*;
+    use crate::scoping::literal::Literal;
+    use crate::scoping::regex::Regex;
+
+    fn regex(pattern: &str) -> Regex {
+        Regex::try_from(pattern.to_owned()).unwrap()
+    }
+
+    fn literal(literal: &str) -> Literal {
+        Literal::try_from(literal.to_owned()).unwrap()
+    }
+
+    /// The raw ranges of `scoper`, as returned, without sorting or merging them.
+    fn ranges(scoper: &impl Scoper, input: &str) -> Vec<Range<usize>> {
+        scoper
+            .scope_raw(input)
+            .into_iter()
+            .map(|(range, _)| range)
+            .collect()
+    }
+
+    #[rstest]
+    #[case("", "a", vec![])]
+    #[case("a b a", "a", vec![0..1, 4..5])]
+    #[case("ab ba", "a", vec![0..1, 4..5])]
+    #[case("xx yy", "a", vec![])]
+    #[case("abc abc", "^a", vec![0..1, 4..5])] // Anchored to each part
+    fn test_intersection(
+        #[case] input: &str,
+        #[case] inner: &str,
+        #[case] expected: Vec<Range<usize>>,
+    ) {
+        let scoper = Intersection(regex(r"\w+"), regex(inner));
+
+        assert_eq!(ranges(&scoper, input), expected);
+    }
+
+    #[rstest]
+    #[case("", vec![])]
+    #[case("a b", vec![0..1, 2..3])]
+    #[case("ab", vec![0..2])]
+    #[case("ba", vec![0..1, 1..2])] // Bordering, not merged
+    #[case("abba", vec![0..2, 2..3, 3..4])] // Left kept whole
+    #[case("bab", vec![0..1, 1..3])] // Right before left, still in order
+    #[case("xyz", vec![])]
+    fn test_union(#[case] input: &str, #[case] expected: Vec<Range<usize>>) {
+        let scoper = Union(literal("ab"), regex("[ab]"));
+
+        assert_eq!(ranges(&scoper, input), expected);
+    }
+
+    #[test]
+    fn test_union_keeps_left_context() {
+        let scoper = Union(regex("a"), literal("b"));
+
+        let ranges = scoper.scope_raw("ab");
+        assert!(ranges[0].1.is_some());
+        assert!(ranges[1].1.is_none());
+    }
+
+    #[rstest]
+    #[case("", vec![])]
+    #[case("abc", vec![0..3])]
+    #[case("a_b", vec![0..1, 2..3])]
+    #[case("abc_test_abc", vec![0..3, 9..12])]
+    #[case("test", vec![])]
+    fn test_difference(#[case] input: &str, #[case] expected: Vec<Range<usize>>) {
+        let scoper = Difference(regex(r"\w+"), regex("_?test_?|_"));
+
+        assert_eq!(ranges(&scoper, input), expected);
+    }
+
+    #[rstest]
+    #[case("", vec![])]
+    #[case("a", vec![])]
+    #[case("b", vec![0..1])]
+    #[case("bab", vec![0..1, 2..3])]
+    #[case("aba", vec![1..2])]
+    fn test_negation(#[case] input: &str, #[case] expected: Vec<Range<usize>>) {
+        let scoper = Negation(literal("a"));
+
+        assert_eq!(ranges(&scoper, input), expected);
+    }
+
+    #[rstest]
+    #[case("a 1 b 2")]
+    #[case("")]
+    #[case("12 3")]
+    fn test_double_negation_is_identity(#[case] input: &str) {
+        let scoper = Negation(Negation(regex(r"\d+")));
+
+        assert_eq!(ranges(&scoper, input), ranges(&regex(r"\d+"), input));
+    }
+}
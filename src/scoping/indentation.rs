@@ -0,0 +1,183 @@
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use log::trace;
+
+use super::scope::RangesWithContext;
+use super::Scoper;
+use crate::ranges::{parse_range, ParseRangeError, Ranges};
+
+/// The width a tab stop advances indentation to a multiple of, as `expand` does.
+const TAB_WIDTH: usize = 8;
+
+/// Lines of a range of indentation depths, for querying nested blocks where no
+/// grammar is available.
+///
+/// Depth is the width of a line's leading whitespace, with tabs advancing it to the
+/// next multiple of 8. Lines are in scope in their entirety, including their line
+/// endings, so that consecutive lines form a single block. Blank lines have no depth
+/// of their own: they are in scope if the lines surrounding them are, so blocks are
+/// not split up by them.
+///
+/// ## Example
+///
+/// ```rust
+/// use srgn::scoping::indentation::Indentation;
+/// use srgn::scoping::Scoper;
+///
+/// let nested: Indentation = "4..".parse().unwrap();
+/// let ranges: Vec<_> = nested
+///     .scope_raw("if x:\n    y()\n\n    z()\nw()\n")
+///     .into_iter()
+///     .map(|(range, _)| range)
+///     .collect();
+///
+/// assert_eq!(ranges, vec![6..23]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Indentation {
+    depths: RangeInclusive<usize>,
+}
+
+impl Indentation {
+    /// Scopes lines whose indentation depth is within `depths`.
+    #[must_use]
+    pub const fn new(depths: RangeInclusive<usize>) -> Self {
+        Self { depths }
+    }
+
+    /// The indentation depth of `line`, or [`None`] if it is blank.
+    fn depth(line: &str) -> Option<usize> {
+        let mut depth = 0;
+
+        for c in line.chars() {
+            match c {
+                '\t' => depth += TAB_WIDTH - depth % TAB_WIDTH,
+                '\n' | '\r' => return None,
+                c if c.is_whitespace() => depth += 1,
+                _ => return Some(depth),
+            }
+        }
+
+        None
+    }
+}
+
+impl FromStr for Indentation {
+    type Err = ParseRangeError;
+
+    /// Parses an inclusive range of depths, as per [`parse_range`]: `N` is exactly
+    /// depth `N` (so `0` is top-level only), `N..` depth `N` or deeper, `..M` depth `M`
+    /// or shallower, and `N..M` anything in between.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_range(s, 0).map(Self::new)
+    }
+}
+
+impl Scoper for Indentation {
+    fn scope_raw<'viewee>(&self, input: &'viewee str) -> RangesWithContext<'viewee> {
+        let mut lines = Vec::new();
+        let mut offset = 0;
+        for line in input.split_inclusive('\n') {
+            lines.push((offset..offset + line.len(), Self::depth(line)));
+            offset += line.len();
+        }
+
+        let in_scope = |depth: Option<usize>| depth.is_some_and(|d| self.depths.contains(&d));
+
+        let mut ranges = Vec::new();
+        // Whether the last non-blank line was in scope, and the start of the blank
+        // lines since, which are only in scope if the next non-blank line is, too.
+        let mut previous = false;
+        let mut blanks_start = None;
+        for (range, depth) in lines {
+            if depth.is_none() {
+                if previous {
+                    blanks_start.get_or_insert(range.start);
+                }
+                continue;
+            }
+
+            let current = in_scope(depth);
+            if current {
+                let start = blanks_start.unwrap_or(range.start);
+                ranges.push(start..range.end);
+            }
+
+            previous = current;
+            blanks_start = None;
+        }
+
+        let mut ranges: Ranges<usize> = ranges.into_iter().collect();
+        ranges.merge();
+
+        trace!("Ranges in scope for {:?}: {:?}", self, ranges);
+
+        ranges.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("0", Ok(0..=0))]
+    #[case("8..", Ok(8..=usize::MAX))]
+    #[case("..4", Ok(0..=4))]
+    #[case(" 2 .. 6 ", Ok(2..=6))]
+    #[case("", Err(ParseRangeError::Invalid(String::new())))]
+    #[case("x", Err(ParseRangeError::Invalid(String::from("x"))))]
+    #[case("4-", Err(ParseRangeError::Invalid(String::from("4-"))))]
+    #[case("1,2", Err(ParseRangeError::Invalid(String::from("1,2"))))]
+    #[case("6..2", Err(ParseRangeError::Decreasing(String::from("6..2"))))]
+    fn test_indentation_parsing(
+        #[case] input: &str,
+        #[case] expected: Result<RangeInclusive<usize>, ParseRangeError>,
+    ) {
+        assert_eq!(input.parse::<Indentation>(), expected.map(Indentation::new));
+    }
+
+    #[rstest]
+    #[case("", None)]
+    #[case("x", Some(0))]
+    #[case("  x", Some(2))]
+    #[case("\tx", Some(8))]
+    #[case("  \tx", Some(8))] // Tab stops
+    #[case("\t  x", Some(10))]
+    #[case("    \n", None)]
+    #[case("  \r\n", None)]
+    fn test_indentation_depth(#[case] line: &str, #[case] expected: Option<usize>) {
+        assert_eq!(Indentation::depth(line), expected);
+    }
+
+    #[rstest]
+    #[case("a\n  b\nc\n", "0", vec![0..2, 6..8])]
+    #[case("a\n  b\nc\n", "1..", vec![2..6])]
+    #[case("a\n  b\n    c\n  d\ne", "2..3", vec![2..6, 12..16])]
+    #[case("a\n  b\n\n  c\nd\n", "2..", vec![2..11])] // Blank lines inside of blocks
+    #[case("a\n  b\n\nc\n", "2..", vec![2..6])] // ... but not after them
+    #[case("\n  a\n", "2..", vec![1..5])] // ... or before them
+    #[case("  a\n\n", "2..", vec![0..4])]
+    #[case("a\r\n  b\r\n", "2", vec![3..8])]
+    #[case("a\nb", "1..", vec![])]
+    #[case("", "0", vec![])]
+    fn test_indentation_scoping(
+        #[case] input: &str,
+        #[case] depths: &str,
+        #[case] expected: Vec<Range<usize>>,
+    ) {
+        let indentation: Indentation = depths.parse().unwrap();
+
+        let ranges: Vec<_> = indentation
+            .scope_raw(input)
+            .into_iter()
+            .map(|(r, _)| r)
+            .collect();
+        assert_eq!(ranges, expected);
+    }
+}
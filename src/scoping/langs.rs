@@ -1,29 +1,43 @@
 use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Range;
 
 use log::{debug, info, trace};
 use tree_sitter::{
-    Language as TSLanguage, Parser as TSParser, Query as TSQuery, QueryCursor as TSQueryCursor,
-    QueryError as TSQueryError,
+    Language as TSLanguage, Node, Parser as TSParser, Query as TSQuery,
+    QueryCursor as TSQueryCursor, QueryError as TSQueryError, QueryErrorKind as TSQueryErrorKind,
+    Tree,
 };
 
-use super::scope::RangesWithContext;
+use super::scope::{Overlap, RangesWithContext, ScopeContext};
 use super::Scoper;
 use crate::find::Find;
 use crate::ranges::Ranges;
 #[cfg(doc)]
-use crate::scoping::{
-    scope::Scope::{In, Out},
-    view::ScopedViewBuilder,
+use crate::{
+    actions::CaptureReplacements,
+    scoping::{
+        scope::Scope::{In, Out},
+        view::ScopedViewBuilder,
+    },
 };
 
 /// C.
 pub mod c;
+/// C++.
+pub mod cpp;
 /// C#.
 pub mod csharp;
+/// Languages not built in, from grammars loaded at runtime.
+#[cfg(feature = "runtime-grammars")]
+pub mod external;
 /// Go.
 pub mod go;
 /// Hashicorp Configuration Language
 pub mod hcl;
+/// Re-scoping inputs incrementally, as they are being edited.
+pub mod incremental;
 /// Python.
 pub mod python;
 /// Rust.
@@ -35,12 +49,14 @@ pub mod typescript;
 /// Represents query compiled for a (programming) language L.
 #[derive(Debug)]
 struct CompiledQuery {
-    /// The *positive* query: it will be run against input and its results used for
-    /// scoping.
-    positive_query: TSQuery,
-    /// The *negative* query: if present (if [`IGNORE`] is present) will be run and
-    /// *subtracted* from the positive query.
-    negative_query: Option<TSQuery>,
+    /// The query: it will be run against input and its results used for scoping,
+    /// minus those of ignored captures (see [`IGNORE`]).
+    query: TSQuery,
+    /// Names of the captures to scope to, see [`QuerySource::with_captures`].
+    captures: Option<Vec<String>>,
+    /// Whether to report capture names as context, see
+    /// [`QuerySource::with_capture_contexts`].
+    capture_contexts: bool,
 }
 
 impl CompiledQuery {
@@ -50,57 +66,102 @@ impl CompiledQuery {
     ///
     /// See the concrete type of the [`TSQueryError`] variant for when this method errors.
     fn from_source(lang: &TSLanguage, query: &QuerySource) -> Result<Self, TSQueryError> {
-        Self::from_str(lang, &query.0)
+        let mut compiled = Self::from_str(lang, &query.query, query.captures.as_deref())?;
+        compiled.capture_contexts = query.capture_contexts;
+
+        Ok(compiled)
     }
 
     fn from_prepared_query(lang: &TSLanguage, query: &str) -> Self {
-        Self::from_str(lang, query).expect("syntax of prepared queries is validated by tests")
+        Self::from_str(lang, query, None).expect("syntax of prepared queries is validated by tests")
     }
 
-    fn from_str(lang: &TSLanguage, query: &str) -> Result<Self, TSQueryError> {
-        let positive_query = TSQuery::new(lang, query)?;
-
-        let is_ignored = |name: &str| name.starts_with(IGNORE);
-        let has_ignored_captures = positive_query
-            .capture_names()
-            .iter()
-            .any(|name| is_ignored(name));
-
-        let negative_query = has_ignored_captures
-            .then(|| {
-                let mut query = TSQuery::new(lang, query)?;
-                let acknowledged_captures = query
-                    .capture_names()
-                    .iter()
-                    .filter(|name| !is_ignored(name))
-                    .map(|s| String::from(*s))
-                    .collect::<Vec<_>>();
-
-                for name in acknowledged_captures {
-                    trace!("Disabling capture for: {:?}", name);
-                    query.disable_capture(&name);
+    fn from_str(
+        lang: &TSLanguage,
+        query: &str,
+        captures: Option<&[String]>,
+    ) -> Result<Self, TSQueryError> {
+        // Captures are never disabled, but filtered by name once matched instead:
+        // predicates (`#eq?`, ...) of disabled captures no longer apply, making matches
+        // broader than written.
+        let query = TSQuery::new(lang, query)?;
+
+        if let Some(captures) = captures {
+            for capture in captures {
+                if !query.capture_names().contains(&capture.as_str()) {
+                    return Err(TSQueryError {
+                        row: 0,
+                        column: 0,
+                        offset: 0,
+                        message: capture.clone(),
+                        kind: TSQueryErrorKind::Capture,
+                    });
                 }
-
-                Ok(query)
-            })
-            .transpose()?;
+            }
+        }
 
         Ok(Self {
-            positive_query,
-            negative_query,
+            query,
+            captures: captures.map(<[String]>::to_vec),
+            capture_contexts: false,
         })
     }
+
+    /// Whether captures of the given `name` are in scope, see
+    /// [`LanguageScoper::is_scoping_capture`].
+    fn is_scoping_capture(&self, name: &str) -> bool {
+        !is_ignored(name)
+            && self
+                .captures
+                .as_ref()
+                .map_or(true, |captures| captures.iter().any(|c| c == name))
+    }
 }
 
 /// An uncompiled source for a query over a language, for scoping.
 ///
 /// Parts hit by the query are [`In`] scope, parts not hit are [`Out`] of scope.
 #[derive(Clone, Debug)]
-pub struct QuerySource(Cow<'static, str>);
+pub struct QuerySource {
+    query: Cow<'static, str>,
+    /// Names of the captures to scope to; all of them if [`None`].
+    captures: Option<Vec<String>>,
+    /// Whether to report capture names as context.
+    capture_contexts: bool,
+}
+
+impl QuerySource {
+    /// Only scope to the captures of the given names, instead of all captures in the
+    /// query.
+    ///
+    /// This way, one query can serve several scoping purposes. Compiling the query
+    /// fails if any of the names is not a capture in the query.
+    #[must_use]
+    pub fn with_captures(mut self, captures: Vec<String>) -> Self {
+        self.captures = Some(captures);
+        self
+    }
+
+    /// Report the name of the capture each scope was found by, as
+    /// [`ScopeContext::QueryCapture`].
+    ///
+    /// Actions such as [`CaptureReplacements`] can then treat scopes differently
+    /// depending on the capture they stem from. Where captures overlap, the one
+    /// starting first (and, at equal starts, the longest) wins.
+    #[must_use]
+    pub const fn with_capture_contexts(mut self) -> Self {
+        self.capture_contexts = true;
+        self
+    }
+}
 
 impl From<String> for QuerySource {
     fn from(s: String) -> Self {
-        Self(s.into())
+        Self {
+            query: s.into(),
+            captures: None,
+            capture_contexts: false,
+        }
     }
 }
 
@@ -110,41 +171,55 @@ impl From<String> for QuerySource {
 /// and a result is instead obtained by ignoring unwanted parts of bigger captures.
 pub(super) const IGNORE: &str = "_SRGN_IGNORE";
 
+/// Whether the capture of the given `name` is to be ignored, see [`IGNORE`].
+fn is_ignored(name: &str) -> bool {
+    name.starts_with(IGNORE)
+}
+
 /// A scoper for a language.
 ///
 /// Functions much the same, but provides specific language-related functionality.
-pub trait LanguageScoper: Scoper + Find + Send + Sync {
+pub trait LanguageScoper: Scoper + SyntaxCheck + Find + Send + Sync {
     /// The language's tree-sitter language.
-    fn lang() -> TSLanguage
+    fn lang(&self) -> TSLanguage
     where
         Self: Sized; // Exclude from trait object
 
-    /// The language's *positive* tree-sitter query.
+    /// The language's tree-sitter query.
     ///
-    /// Its results indicate items in scope.
+    /// Its results indicate items in scope, if of captures which are (see
+    /// [`LanguageScoper::is_scoping_capture`]). Results of ignored captures (those
+    /// named `_SRGN_IGNORE`) are *subtracted* from those.
     fn pos_query(&self) -> &TSQuery
     where
         Self: Sized; // Exclude from trait object
 
-    /// The language's *negative* tree-sitter query.
+    /// Whether captures of the given `name` in the query are in scope.
     ///
-    /// Its results will be *subtracted* from the positive ones.
-    fn neg_query(&self) -> Option<&TSQuery>
+    /// Captures to be ignored (named `_SRGN_IGNORE`) never are.
+    fn is_scoping_capture(&self, name: &str) -> bool
     where
-        Self: Sized; // Exclude from trait object
+        Self: Sized, // Exclude from trait object
+    {
+        !is_ignored(name)
+    }
+
+    /// Whether to report the names of captures as [`ScopeContext::QueryCapture`] when
+    /// scoping.
+    fn capture_contexts(&self) -> bool
+    where
+        Self: Sized, // Exclude from trait object
+    {
+        false
+    }
 
     /// The language's tree-sitter parser.
     #[must_use]
-    fn parser() -> TSParser
+    fn parser(&self) -> TSParser
     where
         Self: Sized, // Exclude from trait object
     {
-        let mut parser = TSParser::new();
-        parser
-            .set_language(&Self::lang())
-            .expect("Should be able to load language grammar and parser");
-
-        parser
+        new_parser(&self.lang())
     }
 
     /// Scope the given input using the language's query.
@@ -154,56 +229,174 @@ pub trait LanguageScoper: Scoper + Find + Send + Sync {
     where
         Self: Sized, // Exclude from trait object
     {
-        // tree-sitter is about incremental parsing, which we don't use here
-        let old_tree = None;
-
-        trace!("Parsing into AST: {:?}", input);
-
-        let tree = Self::parser()
-            .parse(input, old_tree)
-            .expect("No language set in parser, or other unrecoverable error");
-
-        let root = tree.root_node();
-        debug!(
-            "S expression of parsed source code is: {:?}",
-            root.to_sexp()
-        );
-
-        let run = |query: &TSQuery| {
-            trace!("Running query: {:?}", query);
-
-            let mut qc = TSQueryCursor::new();
-            let matches = qc.matches(query, root, input.as_bytes());
-
-            let mut ranges: Ranges<usize> = matches
-                .flat_map(|query_match| query_match.captures)
-                .map(|capture| capture.node.byte_range())
-                .collect();
-
-            // ⚠️ tree-sitter queries with multiple captures will return them in some
-            // mixed order (not ordered, and not merged), but we later rely on cleanly
-            // ordered, non-overlapping ranges (a bit unfortunate we have to know about
-            // that remote part over here).
-            ranges.merge();
-            trace!("Querying yielded ranges: {:?}", ranges);
-
-            ranges
-        };
-
-        let ranges = run(self.pos_query());
-        match &self.neg_query() {
-            Some(nq) => ranges - run(nq),
-            None => ranges,
-        }
+        let tree = parse(&self.lang(), input);
+
+        let (captures, negative) = run_query(self.pos_query(), tree.root_node(), input, |name| {
+            self.is_scoping_capture(name)
+        });
+
+        let mut ranges: Ranges<usize> = captures.into_iter().map(|(range, _)| range).collect();
+        // ⚠️ tree-sitter queries with multiple captures will return them in some
+        // mixed order (not ordered, and not merged), but we later rely on cleanly
+        // ordered, non-overlapping ranges (a bit unfortunate we have to know about
+        // that remote part over here).
+        ranges.merge();
+        trace!("Querying yielded ranges: {:?}", ranges);
+
+        ranges - negative
+    }
+
+    /// Scope the given input using the language's query, alongside the name of the
+    /// capture each range was found by.
+    ///
+    /// Where captures overlap, the one starting first (and, at equal starts, the
+    /// longest) wins.
+    fn scope_via_query_with_captures(&self, input: &str) -> RangesWithContext<'static>
+    where
+        Self: Sized, // Exclude from trait object
+    {
+        let tree = parse(&self.lang(), input);
+
+        let (captures, negative) = run_query(self.pos_query(), tree.root_node(), input, |name| {
+            self.is_scoping_capture(name)
+        });
+
+        ranges_with_captures(captures, &negative)
     }
 }
 
+/// Turns raw, named `captures` into ranges with their names as context, minus all
+/// `negative` ranges.
+///
+/// Where captures overlap, they are resolved as per [`Overlap::FirstWins`].
+fn ranges_with_captures(
+    captures: Vec<(Range<usize>, &str)>,
+    negative: &Ranges<usize>,
+) -> RangesWithContext<'static> {
+    let captures = captures
+        .into_iter()
+        .map(|(range, name)| (range, Some(ScopeContext::QueryCapture(name.to_owned()))))
+        .collect();
+
+    // Unordered and possibly overlapping, see `run_query`.
+    let captures = Overlap::FirstWins
+        .resolve(captures)
+        .expect("resolving overlaps by first one winning is infallible");
+    trace!("Querying yielded captures: {:?}", captures);
+
+    captures
+        .into_iter()
+        .flat_map(|(range, context)| {
+            let ranges: Ranges<usize> = std::iter::once(range).collect();
+            (ranges - negative.clone())
+                .into_iter()
+                .map(move |range| (range, context.clone()))
+        })
+        .collect()
+}
+
+thread_local! {
+    /// Parsers, one per language, reused for all inputs parsed on the same thread.
+    ///
+    /// Setting up a parser is cheap compared to parsing a big input, but adds up over
+    /// many small ones (walking a monorepo, ...).
+    static PARSERS: RefCell<HashMap<TSLanguage, TSParser>> = RefCell::new(HashMap::new());
+}
+
+/// A new parser for `lang`.
+fn new_parser(lang: &TSLanguage) -> TSParser {
+    let mut parser = TSParser::new();
+    parser
+        .set_language(lang)
+        .expect("Should be able to load language grammar and parser");
+
+    parser
+}
+
+/// Parses `input` into a syntax tree, using `lang`.
+pub(crate) fn parse(lang: &TSLanguage, input: &str) -> Tree {
+    // tree-sitter is about incremental parsing, which we don't use here
+    let old_tree = None;
+
+    trace!("Parsing into AST: {:?}", input);
+
+    let tree = PARSERS
+        .with_borrow_mut(|parsers| {
+            parsers
+                .entry(lang.clone())
+                .or_insert_with(|| new_parser(lang))
+                .parse(input, old_tree)
+        })
+        .expect("No language set in parser, or other unrecoverable error");
+
+    debug!(
+        "S expression of parsed source code is: {:?}",
+        tree.root_node().to_sexp()
+    );
+
+    tree
+}
+
+/// Runs `query` against the tree at `root`, parsed from `input`, in a single pass.
+///
+/// Returns the captures (unordered, possibly overlapping) whose names are to be `kept`,
+/// alongside the ranges of ignored captures (see [`IGNORE`]), to be subtracted from
+/// them.
+fn run_query<'query>(
+    query: &'query TSQuery,
+    root: Node<'_>,
+    input: &str,
+    kept: impl Fn(&str) -> bool,
+) -> (Vec<(Range<usize>, &'query str)>, Ranges<usize>) {
+    trace!("Running query: {:?}", query);
+
+    let mut qc = TSQueryCursor::new();
+    let matches = qc.matches(query, root, input.as_bytes());
+
+    let names = query.capture_names();
+    let (ignored, captures): (Vec<_>, Vec<_>) = matches
+        .flat_map(|query_match| query_match.captures)
+        .map(|capture| (capture.node.byte_range(), names[capture.index as usize]))
+        .filter(|(_, name)| is_ignored(name) || kept(name))
+        .partition(|(_, name)| is_ignored(name));
+
+    let mut negative: Ranges<usize> = ignored.into_iter().map(|(range, _)| range).collect();
+    negative.merge();
+
+    (captures, negative)
+}
+
 impl<T> Scoper for T
 where
     T: LanguageScoper,
 {
     fn scope_raw<'viewee>(&self, input: &'viewee str) -> RangesWithContext<'viewee> {
-        self.scope_via_query(input).into()
+        if self.capture_contexts() {
+            self.scope_via_query_with_captures(input)
+        } else {
+            self.scope_via_query(input).into()
+        }
+    }
+}
+
+/// Checking input against a language's grammar.
+///
+/// Implemented for all [`LanguageScoper`]s, and available on trait objects of them.
+pub trait SyntaxCheck {
+    /// Whether `input` contains syntax errors (or missing nodes), according to the
+    /// language's grammar.
+    ///
+    /// Useful to verify transformed output still parses, see
+    /// [`LanguageScoper::lang`].
+    fn has_syntax_errors(&self, input: &str) -> bool;
+}
+
+impl<T> SyntaxCheck for T
+where
+    T: LanguageScoper,
+{
+    fn has_syntax_errors(&self, input: &str) -> bool {
+        parse(&self.lang(), input).root_node().has_error()
     }
 }
 
@@ -219,6 +412,8 @@ impl Scoper for &[Box<dyn LanguageScoper>] {
     /// They are OR'd together in the sense that if *any* of the scopers hit, a
     /// position/range is considered in scope. In some sense, this is the opposite of
     /// [`ScopedViewBuilder::explode`], which is subtractive.
+    ///
+    /// As ranges are merged, any [`ScopeContext::QueryCapture`] is lost.
     fn scope_raw<'viewee>(&self, input: &'viewee str) -> RangesWithContext<'viewee> {
         trace!("Scoping many scopes: {:?}", input);
 
@@ -234,8 +429,8 @@ impl Scoper for &[Box<dyn LanguageScoper>] {
             .flat_map(|s| s.scope_raw(input))
             .map(|(range, ctx)| {
                 assert!(
-                    ctx.is_none(),
-                    "When language scoping runs, no contexts exist yet."
+                    !matches!(ctx, Some(ScopeContext::CaptureGroups(_))),
+                    "When language scoping runs, no regex contexts exist yet."
                 );
                 range
             })
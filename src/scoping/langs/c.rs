@@ -41,6 +41,13 @@ pub enum PreparedQuery {
     Strings,
     /// Includes.
     Includes,
+    /// Paths of includes, with their delimiters (`<stdio.h>`, `"local.h"`).
+    IncludePaths,
+    /// Preprocessor directives (`#define`, `#pragma`, `#ifdef X`, ...).
+    ///
+    /// Of conditional directives, only the directives themselves are in scope, not
+    /// the code they enclose.
+    Preprocessor,
     /// Type definitions.
     TypeDef,
     /// `enum` definitions.
@@ -75,12 +82,29 @@ pub enum PreparedQuery {
     CallExpression,
 }
 
+/// Query for preprocessor directives, shared with C++ (which extends C's grammar).
+pub(super) const PREPROCESSOR: &str = r##"
+    [
+        (preproc_include)
+        (preproc_def)
+        (preproc_function_def)
+        (preproc_call)
+    ] @preproc
+    (preproc_if "#if" @preproc condition: (_) @preproc)
+    (preproc_elif "#elif" @preproc condition: (_) @preproc)
+    (preproc_ifdef ["#ifdef" "#ifndef"] @preproc name: (_) @preproc)
+    (preproc_elifdef ["#elifdef" "#elifndef"] @preproc name: (_) @preproc)
+    ["#else" "#endif"] @preproc
+"##;
+
 impl PreparedQuery {
     const fn as_str(self) -> &'static str {
         match self {
             Self::Comments => "(comment) @comment",
             Self::Strings => "[(string_literal) (system_lib_string)] @string",
             Self::Includes => "(preproc_include) @include",
+            Self::IncludePaths => "(preproc_include path: (_) @path)",
+            Self::Preprocessor => PREPROCESSOR,
             Self::TypeDef => "(type_definition) @typedef",
             Self::Enum => "(enum_specifier) @enum",
             Self::Struct => "(struct_specifier) @struct",
@@ -104,16 +128,20 @@ impl PreparedQuery {
 }
 
 impl LanguageScoper for CompiledQuery {
-    fn lang() -> TSLanguage {
+    fn lang(&self) -> TSLanguage {
         tree_sitter_c::LANGUAGE.into()
     }
 
     fn pos_query(&self) -> &TSQuery {
-        &self.0.positive_query
+        &self.0.query
+    }
+
+    fn is_scoping_capture(&self, name: &str) -> bool {
+        self.0.is_scoping_capture(name)
     }
 
-    fn neg_query(&self) -> Option<&TSQuery> {
-        self.0.negative_query.as_ref()
+    fn capture_contexts(&self) -> bool {
+        self.0.capture_contexts
     }
 }
 
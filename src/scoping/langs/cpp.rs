@@ -0,0 +1,129 @@
+use std::fmt::Debug;
+
+use clap::ValueEnum;
+
+use super::c::PREPROCESSOR;
+use super::{LanguageScoper, QuerySource, TSLanguage, TSQuery, TSQueryError};
+use crate::find::Find;
+
+/// A compiled query for the C++ language.
+#[derive(Debug)]
+pub struct CompiledQuery(super::CompiledQuery);
+
+impl TryFrom<QuerySource> for CompiledQuery {
+    type Error = TSQueryError;
+
+    /// Create a new compiled query for the C++ language
+    ///
+    /// # Errors
+    ///
+    /// See the concrete type of the [`TSQueryError`](tree_sitter::QueryError) variant for when this method errors.
+    fn try_from(query: QuerySource) -> Result<Self, Self::Error> {
+        let q = super::CompiledQuery::from_source(&tree_sitter_cpp::LANGUAGE.into(), &query)?;
+        Ok(Self(q))
+    }
+}
+
+impl From<PreparedQuery> for CompiledQuery {
+    fn from(query: PreparedQuery) -> Self {
+        Self(super::CompiledQuery::from_prepared_query(
+            &tree_sitter_cpp::LANGUAGE.into(),
+            query.as_str(),
+        ))
+    }
+}
+
+/// Prepared tree-sitter queries for C++.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum PreparedQuery {
+    /// Comments (single- and multi-line).
+    Comments,
+    /// Strings (regular and raw).
+    Strings,
+    /// Includes.
+    Includes,
+    /// Paths of includes, with their delimiters (`<vector>`, `"local.hpp"`).
+    IncludePaths,
+    /// Preprocessor directives (`#define`, `#pragma`, `#ifdef X`, ...).
+    ///
+    /// Of conditional directives, only the directives themselves are in scope, not
+    /// the code they enclose.
+    Preprocessor,
+    /// `namespace` definitions.
+    Namespace,
+    /// `class` definitions.
+    Class,
+    /// `struct` definitions.
+    Struct,
+    /// `enum` definitions (incl. `enum class`).
+    Enum,
+    /// `union` definitions.
+    Union,
+    /// Templated declarations (`template <typename T> ...`).
+    Template,
+    /// Type aliases (`typedef` and `using X = ...`).
+    TypeAlias,
+    /// `using` declarations and directives (`using std::string`, `using namespace
+    /// std`).
+    Using,
+    /// Function definitions (free functions and methods).
+    FunctionDef,
+    /// Function declarations.
+    FunctionDecl,
+    /// Lambda expressions.
+    Lambda,
+    /// Call expressions.
+    CallExpression,
+    /// Identifiers.
+    Identifier,
+}
+
+impl PreparedQuery {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Comments => "(comment) @comment",
+            Self::Strings => "[(string_literal) (raw_string_literal) (system_lib_string)] @string",
+            Self::Includes => "(preproc_include) @include",
+            Self::IncludePaths => "(preproc_include path: (_) @path)",
+            Self::Preprocessor => PREPROCESSOR,
+            Self::Namespace => "(namespace_definition) @namespace",
+            Self::Class => "(class_specifier body: (_)) @class",
+            Self::Struct => "(struct_specifier body: (_)) @struct",
+            Self::Enum => "(enum_specifier body: (_)) @enum",
+            Self::Union => "(union_specifier body: (_)) @union",
+            Self::Template => "(template_declaration) @template",
+            Self::TypeAlias => "[(type_definition) (alias_declaration)] @alias",
+            Self::Using => "(using_declaration) @using",
+            Self::FunctionDef => "(function_definition) @function_definition",
+            Self::FunctionDecl => "(function_declarator) @function_decl",
+            Self::Lambda => "(lambda_expression) @lambda",
+            Self::CallExpression => "(call_expression) @call",
+            Self::Identifier => "(identifier) @ident",
+        }
+    }
+}
+
+impl LanguageScoper for CompiledQuery {
+    fn lang(&self) -> TSLanguage {
+        tree_sitter_cpp::LANGUAGE.into()
+    }
+
+    fn pos_query(&self) -> &TSQuery {
+        &self.0.query
+    }
+
+    fn is_scoping_capture(&self, name: &str) -> bool {
+        self.0.is_scoping_capture(name)
+    }
+
+    fn capture_contexts(&self) -> bool {
+        self.0.capture_contexts
+    }
+}
+
+impl Find for CompiledQuery {
+    fn extensions(&self) -> &'static [&'static str] {
+        // `h` is left to C, which is more common for it.
+        &["cpp", "cc", "cxx", "c++", "hpp", "hh", "hxx", "h++"]
+    }
+}
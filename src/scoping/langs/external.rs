@@ -0,0 +1,300 @@
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use libloading::{Library, Symbol};
+use log::info;
+use tree_sitter::LanguageError as TSLanguageError;
+
+use super::{LanguageScoper, QuerySource, TSLanguage, TSParser, TSQuery, TSQueryError};
+use crate::find::Find;
+
+/// A tree-sitter grammar, loaded at runtime from a shared library.
+///
+/// Such libraries are built from a grammar's repository by `tree-sitter build`. This
+/// way, languages not built into this crate can be scoped just the same, through
+/// [`CompiledQuery`].
+///
+/// Loaded libraries are never unloaded: languages, parsers and syntax trees all point
+/// into them, and parsers are kept around for reuse.
+#[derive(Debug, Clone)]
+pub struct Grammar {
+    lang: TSLanguage,
+    /// File extensions of the grammar's language, see [`Find::extensions`].
+    extensions: &'static [&'static str],
+}
+
+impl Grammar {
+    /// Loads the grammar from the shared library at `path`.
+    ///
+    /// The library has to export a `tree_sitter_<name>` function, as all tree-sitter
+    /// grammars do. If no `name` is given, it is derived from the file name: `ruby` for
+    /// `libtree-sitter-ruby.so`, `tree-sitter-ruby.dylib` or `ruby.dll`, with any
+    /// dashes turned into underscores.
+    ///
+    /// The library is leaked on purpose: it stays loaded for the rest of the process,
+    /// even once the returned grammar is dropped, as languages, parsers and syntax
+    /// trees built from it might still point into it.
+    ///
+    /// # Errors
+    ///
+    /// See [`GrammarError`].
+    pub fn load(path: &Path, name: Option<&str>) -> Result<Self, GrammarError> {
+        let name = match name {
+            Some(name) => name.to_owned(),
+            None => name_from_path(path).ok_or_else(|| GrammarError::NoName(path.to_owned()))?,
+        };
+        let symbol = format!("tree_sitter_{name}");
+        info!("Loading grammar '{}' from '{}'", name, path.display());
+
+        // SAFETY: loading a library runs its initialization code, and its symbol is
+        // trusted to be a function of the signature shared by all tree-sitter grammars.
+        // Nothing can be verified about either; the user asked for this library, though.
+        #[allow(unsafe_code)]
+        let lang = unsafe {
+            let library = Library::new(path).map_err(|e| GrammarError::Load(path.to_owned(), e))?;
+            let lang = {
+                let language: Symbol<'_, unsafe extern "C" fn() -> TSLanguage> = library
+                    .get(symbol.as_bytes())
+                    .map_err(|e| GrammarError::MissingSymbol(symbol, e))?;
+                language()
+            };
+
+            // The language points into the library, see above.
+            std::mem::forget(library);
+            lang
+        };
+
+        TSParser::new()
+            .set_language(&lang)
+            .map_err(GrammarError::Incompatible)?;
+
+        Ok(Self {
+            lang,
+            extensions: &[],
+        })
+    }
+
+    /// Consider files with any of the given `extensions` (like `rb`, without a leading
+    /// dot) to be of this grammar's language, for example when searching directories.
+    ///
+    /// Like the grammar itself, the extensions are kept around for the rest of the
+    /// program.
+    #[must_use]
+    pub fn with_extensions(mut self, extensions: Vec<String>) -> Self {
+        self.extensions = extensions
+            .into_iter()
+            .map(|ext| &*ext.leak())
+            .collect::<Vec<_>>()
+            .leak();
+        self
+    }
+}
+
+/// Derives a grammar's name from the file name of its library, see [`Grammar::load`].
+fn name_from_path(path: &Path) -> Option<String> {
+    // Only up to the first dot, to also cover versioned names like `libfoo.so.1`.
+    let stem = path.file_name()?.to_str()?.split('.').next()?;
+    let name = stem.strip_prefix("lib").unwrap_or(stem);
+    let name = name
+        .strip_prefix("tree-sitter-")
+        .or_else(|| name.strip_prefix("tree_sitter_"))
+        .unwrap_or(name);
+
+    (!name.is_empty()).then(|| name.replace('-', "_"))
+}
+
+/// An error loading a [`Grammar`].
+#[derive(Debug)]
+pub enum GrammarError {
+    /// No name was given, and none could be derived from the library's path.
+    NoName(PathBuf),
+    /// The library could not be loaded.
+    Load(PathBuf, libloading::Error),
+    /// The library does not export the grammar's function of the given name.
+    MissingSymbol(String, libloading::Error),
+    /// The grammar was generated by a version of tree-sitter incompatible with the one
+    /// in use here.
+    Incompatible(TSLanguageError),
+}
+
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoName(path) => write!(
+                f,
+                "Cannot derive grammar name from '{}', specify it explicitly",
+                path.display()
+            ),
+            Self::Load(path, e) => {
+                write!(f, "Cannot load grammar from '{}': {}", path.display(), e)
+            }
+            Self::MissingSymbol(symbol, e) => {
+                write!(f, "Grammar does not export '{symbol}': {e}")
+            }
+            Self::Incompatible(e) => write!(f, "Grammar is incompatible: {e}"),
+        }
+    }
+}
+
+impl Error for GrammarError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::NoName(_) => None,
+            Self::Load(_, e) | Self::MissingSymbol(_, e) => Some(e),
+            Self::Incompatible(e) => Some(e),
+        }
+    }
+}
+
+/// A compiled query for a [`Grammar`] loaded at runtime.
+///
+/// There are no prepared queries, as nothing is known about the language.
+#[derive(Debug)]
+pub struct CompiledQuery {
+    grammar: Grammar,
+    query: super::CompiledQuery,
+}
+
+impl CompiledQuery {
+    /// Create a new compiled query for the language of `grammar`.
+    ///
+    /// # Errors
+    ///
+    /// See the concrete type of the [`TSQueryError`](tree_sitter::QueryError) variant for when this method errors.
+    pub fn new(grammar: Grammar, query: &QuerySource) -> Result<Self, TSQueryError> {
+        let query = super::CompiledQuery::from_source(&grammar.lang, query)?;
+        Ok(Self { grammar, query })
+    }
+}
+
+impl LanguageScoper for CompiledQuery {
+    fn lang(&self) -> TSLanguage {
+        self.grammar.lang.clone()
+    }
+
+    fn pos_query(&self) -> &TSQuery {
+        &self.query.query
+    }
+
+    fn is_scoping_capture(&self, name: &str) -> bool {
+        self.query.is_scoping_capture(name)
+    }
+
+    fn capture_contexts(&self) -> bool {
+        self.query.capture_contexts
+    }
+}
+
+impl Find for CompiledQuery {
+    fn extensions(&self) -> &'static [&'static str] {
+        self.grammar.extensions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::OnceLock;
+
+    use rstest::rstest;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::scoping::langs::hcl;
+    use crate::scoping::Scoper;
+
+    #[rstest]
+    #[case("libtree-sitter-ruby.so", Some("ruby"))]
+    #[case("tree-sitter-ruby.dylib", Some("ruby"))]
+    #[case("libtree_sitter_ruby.so", Some("ruby"))]
+    #[case("ruby.dll", Some("ruby"))]
+    #[case("/some/dir/libruby.so.1.2", Some("ruby"))]
+    #[case("libtree-sitter-embedded-template.so", Some("embedded_template"))]
+    #[case("lib.so", None)]
+    #[case("", None)]
+    fn test_name_from_path(#[case] path: &str, #[case] expected: Option<&str>) {
+        assert_eq!(name_from_path(Path::new(path)).as_deref(), expected);
+    }
+
+    /// The HCL grammar, built into a shared library once.
+    #[cfg(unix)]
+    fn hcl_library() -> &'static Path {
+        static LIBRARY: OnceLock<TempDir> = OnceLock::new();
+
+        LIBRARY
+            .get_or_init(|| {
+                let src = Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join("src/scoping/langs/tree_sitter_hcl/upstream-main/src");
+                let dir = TempDir::new().unwrap();
+
+                let status =
+                    std::process::Command::new(std::env::var("CC").unwrap_or_else(|_| "cc".into()))
+                        .args(["-shared", "-fPIC", "-I"])
+                        .arg(&src)
+                        .arg(src.join("parser.c"))
+                        .arg(src.join("scanner.c"))
+                        .arg("-o")
+                        .arg(dir.path().join("libtree-sitter-hcl.so"))
+                        .status()
+                        .unwrap();
+                assert!(status.success(), "building grammar library failed");
+
+                dir
+            })
+            .path()
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    #[case("(comment) @c")]
+    #[case("(string_lit) @s (comment) @_SRGN_IGNORE")]
+    #[case("(attribute (identifier) @name)")]
+    fn test_scoping_equals_built_in_grammar(#[case] query: &str) {
+        let input = r#"
+# A comment
+resource "aws_instance" "web" {
+  ami = "ami-a1b2c3d4" // Another
+  tags = { Name = "web" }
+}
+"#;
+        let grammar = Grammar::load(&hcl_library().join("libtree-sitter-hcl.so"), None).unwrap();
+        let query = QuerySource::from(query.to_owned());
+
+        let external = CompiledQuery::new(grammar, &query).unwrap();
+        let built_in = hcl::CompiledQuery::try_from(query).unwrap();
+
+        assert!(!external.scope(input).0.is_empty());
+        assert_eq!(external.scope(input), built_in.scope(input));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_load_missing_symbol() {
+        let res = Grammar::load(&hcl_library().join("libtree-sitter-hcl.so"), Some("nope"));
+
+        assert!(matches!(res, Err(GrammarError::MissingSymbol(s, _)) if s == "tree_sitter_nope"));
+    }
+
+    #[test]
+    fn test_load_missing_library() {
+        let res = Grammar::load(Path::new("/does/not/exist/libtree-sitter-x.so"), None);
+
+        assert!(matches!(res, Err(GrammarError::Load(..))));
+    }
+
+    #[test]
+    fn test_load_without_name() {
+        let res = Grammar::load(Path::new("lib.so"), None);
+
+        assert!(matches!(res, Err(GrammarError::NoName(..))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_invalid_query() {
+        let grammar = Grammar::load(&hcl_library().join("libtree-sitter-hcl.so"), None).unwrap();
+        let query = QuerySource::from("(no_such_node) @x".to_owned());
+
+        assert!(CompiledQuery::new(grammar, &query).is_err());
+    }
+}
@@ -0,0 +1,375 @@
+use std::fmt;
+use std::ops::Range;
+
+use log::{debug, trace};
+pub use tree_sitter::{InputEdit, Point};
+use tree_sitter::{Node, Parser as TSParser, Query as TSQuery, QueryCursor as TSQueryCursor, Tree};
+
+use super::{is_ignored, ranges_with_captures, LanguageScoper};
+use crate::ranges::Ranges;
+use crate::scoping::scope::RangesWithContext;
+
+/// Wraps a [`LanguageScoper`], keeping the syntax tree of the input it last scoped.
+///
+/// After an edit to that input, only the region affected by the edit is parsed and
+/// queried anew, instead of the entire input. This suits editor integrations, which
+/// can then re-scope on every keystroke.
+///
+/// Results are the same as those of scoping from scratch, except for queries relating
+/// nodes far apart from each other (for example, through predicates comparing
+/// captures): changes outside of the affected region are not picked up. When in
+/// doubt, [scope from scratch][`Self::scope`] every now and then.
+///
+/// ## Example
+///
+/// ```rust
+/// use srgn::scoping::langs::incremental::{IncrementalScoper, InputEdit, Point};
+/// use srgn::scoping::langs::python::{CompiledQuery, PreparedQuery};
+///
+/// let mut scoper = IncrementalScoper::new(CompiledQuery::from(PreparedQuery::Strings));
+///
+/// let ranges = scoper.scope("x = 'a'");
+/// assert_eq!(ranges.into_iter().map(|(r, _)| r).collect::<Vec<_>>(), [5..6]);
+///
+/// // Insert `bc` after `a`.
+/// let edit = InputEdit {
+///     start_byte: 6,
+///     old_end_byte: 6,
+///     new_end_byte: 8,
+///     start_position: Point::new(0, 6),
+///     old_end_position: Point::new(0, 6),
+///     new_end_position: Point::new(0, 8),
+/// };
+/// let ranges = scoper.rescope(&edit, "x = 'abc'");
+/// assert_eq!(ranges.into_iter().map(|(r, _)| r).collect::<Vec<_>>(), [5..8]);
+/// ```
+pub struct IncrementalScoper<L> {
+    scoper: L,
+    parser: TSParser,
+    state: Option<State>,
+}
+
+impl<L: fmt::Debug> fmt::Debug for IncrementalScoper<L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IncrementalScoper")
+            .field("scoper", &self.scoper)
+            .field("has_scoped", &self.state.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A capture's byte range and index.
+type Capture = (Range<usize>, u32);
+
+/// What is known about the input last scoped.
+struct State {
+    tree: Tree,
+    /// Raw captures in scope, alongside their capture index.
+    positive: Vec<Capture>,
+    /// Raw ignored captures (see [`IGNORE`](super::IGNORE)), alongside their capture index.
+    negative: Vec<Capture>,
+}
+
+impl<L: LanguageScoper> IncrementalScoper<L> {
+    /// Create a new instance, wrapping the given `scoper`.
+    #[must_use]
+    pub fn new(scoper: L) -> Self {
+        let parser = scoper.parser();
+
+        Self {
+            scoper,
+            parser,
+            state: None,
+        }
+    }
+
+    /// Scope `input` from scratch, forgetting any previous input.
+    pub fn scope(&mut self, input: &str) -> RangesWithContext<'static> {
+        let tree = self.parse(input, None);
+        let root = tree.root_node();
+
+        let (positive, negative) = self.captures(root, input, None);
+
+        self.state = Some(State {
+            tree,
+            positive,
+            negative,
+        });
+
+        self.ranges()
+    }
+
+    /// Re-scope `input`, which is the input last scoped with `edit` applied.
+    ///
+    /// If nothing was scoped yet, scopes from scratch. The `edit` has to accurately
+    /// describe the change (see [`tree_sitter::Tree::edit`]), otherwise results are
+    /// garbage.
+    pub fn rescope(&mut self, edit: &InputEdit, input: &str) -> RangesWithContext<'static> {
+        let Some(mut state) = self.state.take() else {
+            return self.scope(input);
+        };
+
+        state.tree.edit(edit);
+        let tree = self.parse(input, Some(&state.tree));
+
+        let region = affected_region(edit, &state.tree, &tree, input.len());
+        debug!("Re-scoping affected region {region:?}");
+        let root = tree.root_node();
+
+        let (positive, negative) = self.captures(root, input, Some(region.clone()));
+
+        let positive = patch(state.positive, positive, edit, &region);
+        let negative = patch(state.negative, negative, edit, &region);
+
+        self.state = Some(State {
+            tree,
+            positive,
+            negative,
+        });
+
+        self.ranges()
+    }
+
+    fn parse(&mut self, input: &str, old_tree: Option<&Tree>) -> Tree {
+        self.parser
+            .parse(input, old_tree)
+            .expect("No language set in parser, or other unrecoverable error")
+    }
+
+    fn captures(
+        &self,
+        root: Node<'_>,
+        input: &str,
+        region: Option<Range<usize>>,
+    ) -> (Vec<Capture>, Vec<Capture>) {
+        captures(self.scoper.pos_query(), root, input, region, |name| {
+            self.scoper.is_scoping_capture(name)
+        })
+    }
+
+    /// Assembles the current state into ranges, as [`Scoper::scope_raw`] would.
+    ///
+    /// [`Scoper::scope_raw`]: crate::scoping::Scoper::scope_raw
+    fn ranges(&self) -> RangesWithContext<'static> {
+        let state = self.state.as_ref().expect("scoped before");
+
+        let mut negative: Ranges<usize> = state
+            .negative
+            .iter()
+            .map(|(range, _)| range.clone())
+            .collect();
+        negative.merge();
+
+        if self.scoper.capture_contexts() {
+            let names = self.scoper.pos_query().capture_names();
+            let captures = state
+                .positive
+                .iter()
+                .map(|(range, i)| (range.clone(), names[*i as usize]))
+                .collect();
+
+            ranges_with_captures(captures, &negative)
+        } else {
+            let mut positive: Ranges<usize> = state
+                .positive
+                .iter()
+                .map(|(range, _)| range.clone())
+                .collect();
+            positive.merge();
+
+            (positive - negative).into()
+        }
+    }
+}
+
+/// Runs `query` against the tree at `root`, returning all captures (with their
+/// indices) whose names are to be `kept`, alongside all ignored ones (see [`IGNORE`](super::IGNORE)),
+/// of matches intersecting `region`, or of all matches if [`None`].
+fn captures(
+    query: &TSQuery,
+    root: Node<'_>,
+    input: &str,
+    region: Option<Range<usize>>,
+    kept: impl Fn(&str) -> bool,
+) -> (Vec<Capture>, Vec<Capture>) {
+    let mut qc = TSQueryCursor::new();
+    if let Some(region) = region {
+        qc.set_byte_range(region);
+    }
+
+    let names = query.capture_names();
+    qc.matches(query, root, input.as_bytes())
+        .flat_map(|query_match| query_match.captures)
+        .filter(|capture| {
+            let name = names[capture.index as usize];
+            kept(name) || is_ignored(name)
+        })
+        .map(|capture| (capture.node.byte_range(), capture.index))
+        .filter(|(range, _)| !range.is_empty())
+        .partition(|(_, index)| !is_ignored(names[*index as usize]))
+}
+
+/// The byte range of the new input whose scopes might have changed due to `edit`.
+///
+/// It covers the edit itself and everything whose syntax changed, widened by a byte
+/// on either side so that items merely *touching* the edit count as affected, too.
+fn affected_region(edit: &InputEdit, old_tree: &Tree, new_tree: &Tree, len: usize) -> Range<usize> {
+    let (start, end) = old_tree.changed_ranges(new_tree).fold(
+        (edit.start_byte, edit.new_end_byte),
+        |(start, end), changed| (start.min(changed.start_byte), end.max(changed.end_byte)),
+    );
+
+    start.saturating_sub(1)..(end + 1).min(len)
+}
+
+/// Carries `old` captures over past `edit`, replacing all those in the affected
+/// `region` with `fresh` ones.
+fn patch(
+    old: Vec<Capture>,
+    fresh: Vec<Capture>,
+    edit: &InputEdit,
+    region: &Range<usize>,
+) -> Vec<Capture> {
+    let mut captures: Vec<Capture> = old
+        .into_iter()
+        .filter_map(|(mut range, i)| {
+            if range.start >= edit.old_end_byte {
+                // Behind the edit, so moved along with everything after it.
+                range.start = range.start - edit.old_end_byte + edit.new_end_byte;
+                range.end = range.end - edit.old_end_byte + edit.new_end_byte;
+            } else if range.end > edit.start_byte {
+                // Edited.
+                return None;
+            }
+
+            let is_affected = range.start < region.end && region.start < range.end;
+            (!is_affected).then_some((range, i))
+        })
+        .chain(fresh)
+        .collect();
+
+    // Captures of matches only partially in the region are found again.
+    captures.sort_by_key(|(range, i)| (range.start, range.end, *i));
+    captures.dedup();
+    trace!("Patched captures: {captures:?}");
+
+    captures
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::scoping::langs::python::{CompiledQuery, PreparedQuery};
+    use crate::scoping::langs::QuerySource;
+    use crate::scoping::scope::ROScopes;
+    use crate::scoping::Scoper;
+
+    /// Applies replacing `range` of `input` by `replacement`, returning the new input
+    /// and the corresponding edit.
+    fn edit(input: &str, range: Range<usize>, replacement: &str) -> (String, InputEdit) {
+        let point = |s: &str| {
+            let row = s.matches('\n').count();
+            let column = s.len() - s.rfind('\n').map_or(0, |i| i + 1);
+            Point::new(row, column)
+        };
+
+        let new = format!(
+            "{}{}{}",
+            &input[..range.start],
+            replacement,
+            &input[range.end..]
+        );
+        let new_end = range.start + replacement.len();
+
+        let edit = InputEdit {
+            start_byte: range.start,
+            old_end_byte: range.end,
+            new_end_byte: new_end,
+            start_position: point(&input[..range.start]),
+            old_end_position: point(&input[..range.end]),
+            new_end_position: point(&new[..new_end]),
+        };
+
+        (new, edit)
+    }
+
+    const INPUT: &str = r#"def foo(a):
+    # A comment
+    return "a" + 'b'
+
+
+def test_bar():
+    """Docstring."""
+    assert foo("x") == "xab"  # Another
+"#;
+
+    #[rstest]
+    #[case(0..0, "# Leading\n")] // Insert at start
+    #[case(16..16, "x")] // Insert into comment
+    #[case(36..37, "")] // Delete string contents
+    #[case(36..36, "abc\" + \"")] // Split a string
+    #[case(34..45, "")] // Delete both strings
+    #[case(47..49, "def baz():\n    return '''\n'''\n\n")] // Insert function
+    #[case(50..63, "")] // Delete function head
+    #[case(34..34, "\"")] // Unbalance quotes
+    #[case(INPUT.len()..INPUT.len(), "x = 'end'")] // Append
+    #[case(0..INPUT.len(), "")] // Delete everything
+    fn test_rescope_equals_scoping_from_scratch(
+        #[case] range: Range<usize>,
+        #[case] replacement: &str,
+        #[values(
+            PreparedQuery::Strings,
+            PreparedQuery::Comments,
+            PreparedQuery::DocStrings,
+            PreparedQuery::Def
+        )]
+        prepared: PreparedQuery,
+    ) {
+        let (new, edit) = edit(INPUT, range, replacement);
+
+        let mut incremental = IncrementalScoper::new(CompiledQuery::from(prepared));
+        incremental.scope(INPUT);
+        let rescoped = incremental.rescope(&edit, &new);
+
+        let expected = CompiledQuery::from(prepared).scope(&new);
+        assert_eq!(ROScopes::from_raw_ranges(&new, rescoped), expected);
+    }
+
+    #[test]
+    fn test_rescope_repeatedly() {
+        let query =
+            QuerySource::from("(string) @s (comment) @c".to_owned()).with_capture_contexts();
+        let mut incremental =
+            IncrementalScoper::new(CompiledQuery::try_from(query.clone()).unwrap());
+
+        let mut input = INPUT.to_owned();
+        incremental.scope(&input);
+
+        // Type out a new line, keystroke by keystroke.
+        let mut offset = 47;
+        for c in "x = 'y'  # z\n".chars() {
+            let (new, e) = edit(&input, offset..offset, &c.to_string());
+            input = new;
+            offset += c.len_utf8();
+
+            let rescoped = incremental.rescope(&e, &input);
+            let expected = CompiledQuery::try_from(query.clone())
+                .unwrap()
+                .scope(&input);
+            assert_eq!(ROScopes::from_raw_ranges(&input, rescoped), expected);
+        }
+    }
+
+    #[test]
+    fn test_rescope_without_previous_scopes_from_scratch() {
+        let (new, edit) = edit(INPUT, 0..0, "x");
+        let mut incremental = IncrementalScoper::new(CompiledQuery::from(PreparedQuery::Strings));
+
+        let rescoped = incremental.rescope(&edit, &new);
+
+        let expected = CompiledQuery::from(PreparedQuery::Strings).scope(&new);
+        assert_eq!(ROScopes::from_raw_ranges(&new, rescoped), expected);
+    }
+}
@@ -190,16 +190,20 @@ impl PreparedQuery {
 }
 
 impl LanguageScoper for CompiledQuery {
-    fn lang() -> TSLanguage {
+    fn lang(&self) -> TSLanguage {
         tree_sitter_python::LANGUAGE.into()
     }
 
     fn pos_query(&self) -> &TSQuery {
-        &self.0.positive_query
+        &self.0.query
     }
 
-    fn neg_query(&self) -> Option<&TSQuery> {
-        self.0.negative_query.as_ref()
+    fn is_scoping_capture(&self, name: &str) -> bool {
+        self.0.is_scoping_capture(name)
+    }
+
+    fn capture_contexts(&self) -> bool {
+        self.0.capture_contexts
     }
 }
 
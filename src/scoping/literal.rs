@@ -4,6 +4,7 @@ use std::ops::Range;
 
 use log::trace;
 use unescape::unescape;
+use unicode_categories::UnicodeCategories;
 
 use super::scope::RangesWithContext;
 use super::Scoper;
@@ -11,7 +12,60 @@ use crate::ranges::Ranges;
 
 /// A literal string for querying.
 #[derive(Debug)]
-pub struct Literal(String);
+pub struct Literal {
+    literal: String,
+    words: bool,
+}
+
+impl Literal {
+    /// Only scope occurrences bounded by word boundaries on both sides, as `\b` in a
+    /// regex would, so `id` no longer scopes part of `identifier`.
+    #[must_use]
+    pub const fn with_word_boundaries(mut self) -> Self {
+        self.words = true;
+        self
+    }
+
+    /// Finds all occurrences which are bounded by word boundaries.
+    ///
+    /// Unlike plain searching, rejected occurrences might overlap with accepted ones, so
+    /// searching resumes right after the start of each rejected one.
+    fn find_words(&self, input: &str) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+
+        let Some(first) = self.literal.chars().next() else {
+            // Boundaries around nothing are meaningless.
+            return ranges;
+        };
+        let last = self.literal.chars().next_back().unwrap_or(first);
+
+        let mut pos = 0;
+        while let Some(i) = input[pos..].find(&self.literal) {
+            let range = pos + i..pos + i + self.literal.len();
+
+            let before = input[..range.start].chars().next_back();
+            let after = input[range.end..].chars().next();
+            let bounded =
+                is_word(before) != is_word(Some(first)) && is_word(Some(last)) != is_word(after);
+
+            if bounded {
+                pos = range.end;
+                ranges.push(range);
+            } else {
+                pos = range.start + first.len_utf8();
+            }
+        }
+
+        ranges
+    }
+}
+
+/// Whether `c` is a word character, as `\w` in a regex. Nothing is not.
+fn is_word(c: Option<char>) -> bool {
+    c.is_some_and(|c| {
+        c.is_alphanumeric() || c.is_mark() || c.is_punctuation_connector() || c == '\u{200d}'
+    })
+}
 
 /// An error that can occur when parsing a literal.
 #[derive(Debug)]
@@ -39,22 +93,29 @@ impl TryFrom<String> for Literal {
         let unescaped = unescape(&literal)
             .ok_or_else(|| LiteralError::InvalidEscapeSequences(literal.to_string()))?;
 
-        Ok(Self(unescaped))
+        Ok(Self {
+            literal: unescaped,
+            words: false,
+        })
     }
 }
 
 impl Scoper for Literal {
     fn scope_raw<'viewee>(&self, input: &'viewee str) -> RangesWithContext<'viewee> {
         let ranges = {
-            let len = self.0.len();
-
-            let ranges: Ranges<usize> = input
-                .match_indices(&self.0)
-                .map(|(i, _)| Range {
-                    start: i,
-                    end: i + len,
-                })
-                .collect();
+            let len = self.literal.len();
+
+            let ranges: Ranges<usize> = if self.words {
+                self.find_words(input).into_iter().collect()
+            } else {
+                input
+                    .match_indices(&self.literal)
+                    .map(|(i, _)| Range {
+                        start: i,
+                        end: i + len,
+                    })
+                    .collect()
+            };
 
             trace!("Ranges in scope for {:?}: {:?}", self, ranges);
 
@@ -161,4 +222,34 @@ mod tests {
 
         assert_eq!(actual, expected);
     }
+
+    #[rstest]
+    #[case("id", "id", vec![0..2])]
+    #[case("identifier", "id", vec![])]
+    #[case("an id, my_id, id2 and (id)", "id", vec![3..5, 23..25])]
+    #[case("idid id", "id", vec![5..7])]
+    #[case("ñid id", "id", vec![5..7])] // Unicode letters are word characters
+    #[case("id\u{301} id", "id", vec![5..7])] // So are marks
+    #[case("a-b a-bc", "a-b", vec![0..3])]
+    #[case("x-a-y -a-", "-a-", vec![1..4])] // Bounded like `\b` in a regex
+    #[case("a-a-a", "a-a", vec![0..3])]
+    #[case("aa aaa", "aa", vec![0..2])]
+    #[case("", "id", vec![])]
+    #[case("id", "", vec![])]
+    fn test_literal_scoping_words(
+        #[case] input: &str,
+        #[case] literal: &str,
+        #[case] expected: Vec<Range<usize>>,
+    ) {
+        let literal = Literal::try_from(literal.to_owned())
+            .unwrap()
+            .with_word_boundaries();
+
+        let ranges: Vec<_> = literal
+            .scope_raw(input)
+            .into_iter()
+            .map(|(r, _)| r)
+            .collect();
+        assert_eq!(ranges, expected);
+    }
 }
@@ -0,0 +1,147 @@
+use log::trace;
+
+use super::scope::RangesWithContext;
+use super::Scoper;
+use crate::ranges::Ranges;
+
+/// The marker turning processing off for all lines up to the next [`ON`].
+pub const OFF: &str = "srgn:off";
+/// The marker turning processing back on after [`OFF`].
+pub const ON: &str = "srgn:on";
+/// The marker excluding the line after it from processing.
+pub const IGNORE_NEXT_LINE: &str = "srgn:ignore-next-line";
+
+/// A marker found on a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Marker {
+    Off,
+    On,
+    IgnoreNextLine,
+}
+
+/// Everything *not* excluded by inline markers, so that generated blocks and
+/// intentional exceptions survive transformations of entire trees.
+///
+/// Markers are usually placed in comments, but are found anywhere on a line. Lines
+/// from [`OFF`] up to and including [`ON`] are excluded, as is the line following
+/// [`IGNORE_NEXT_LINE`]. Lines carrying markers are excluded themselves, so the markers
+/// cannot be transformed away. An [`OFF`] without an [`ON`] excludes the rest of the
+/// input. Lines are in scope in their entirety, including their line endings.
+///
+/// ## Example
+///
+/// ```rust
+/// use srgn::scoping::markers::IgnoreMarkers;
+/// use srgn::scoping::Scoper;
+///
+/// let input = "a\n# srgn:off\nb\n# srgn:on\nc\n";
+/// let ranges: Vec<_> = IgnoreMarkers
+///     .scope_raw(input)
+///     .into_iter()
+///     .map(|(range, _)| range)
+///     .collect();
+///
+/// assert_eq!(ranges, vec![0..2, 25..27]);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IgnoreMarkers;
+
+impl IgnoreMarkers {
+    /// The first marker on `line`, if any.
+    ///
+    /// Markers need to stand on their own, so `srgn:online` is not one.
+    fn marker(line: &str) -> Option<Marker> {
+        let markers = [
+            // Longest first, though no marker is a prefix of another one currently.
+            (IGNORE_NEXT_LINE, Marker::IgnoreNextLine),
+            (OFF, Marker::Off),
+            (ON, Marker::On),
+        ];
+
+        line.match_indices("srgn:").find_map(|(i, _)| {
+            let rest = &line[i..];
+            markers.iter().find_map(|(text, marker)| {
+                let after = rest.strip_prefix(text)?;
+                let standalone =
+                    !after.starts_with(|c: char| c.is_alphanumeric() || c == '-' || c == '_');
+                standalone.then_some(*marker)
+            })
+        })
+    }
+}
+
+impl Scoper for IgnoreMarkers {
+    fn scope_raw<'viewee>(&self, input: &'viewee str) -> RangesWithContext<'viewee> {
+        let mut ranges = Vec::new();
+        let mut offset = 0;
+        let mut off = false;
+        let mut ignore_next = false;
+
+        for line in input.split_inclusive('\n') {
+            let range = offset..offset + line.len();
+            offset = range.end;
+
+            let marker = Self::marker(line);
+            if !(off || ignore_next || marker.is_some()) {
+                ranges.push(range);
+            }
+
+            ignore_next = marker == Some(Marker::IgnoreNextLine);
+            match marker {
+                Some(Marker::Off) => off = true,
+                Some(Marker::On) => off = false,
+                Some(Marker::IgnoreNextLine) | None => {}
+            }
+        }
+
+        let mut ranges: Ranges<usize> = ranges.into_iter().collect();
+        ranges.merge();
+
+        trace!("Ranges not excluded by markers: {:?}", ranges);
+
+        ranges.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("", None)]
+    #[case("x = 1", None)]
+    #[case("# srgn:off", Some(Marker::Off))]
+    #[case("// srgn:on\n", Some(Marker::On))]
+    #[case("/* srgn:ignore-next-line */", Some(Marker::IgnoreNextLine))]
+    #[case("x = 1  # srgn:off", Some(Marker::Off))]
+    #[case("# srgn:online", None)]
+    #[case("# srgn:off-limits", None)]
+    #[case("# srgn: off", None)]
+    #[case("# srgn:ignore", None)]
+    #[case("# srgn:offline, srgn:on", Some(Marker::On))]
+    fn test_marker(#[case] line: &str, #[case] expected: Option<Marker>) {
+        assert_eq!(IgnoreMarkers::marker(line), expected);
+    }
+
+    #[rstest]
+    #[case("", vec![])]
+    #[case("a\nb\n", vec![0..4])]
+    #[case("a\n# srgn:off\nb\n# srgn:on\nc\n", vec![0..2, 25..27])]
+    #[case("a\n# srgn:off\nb\n", vec![0..2])] // Never turned back on
+    #[case("# srgn:on\na\n", vec![10..12])] // Stray markers are excluded, too
+    #[case("a\n# srgn:ignore-next-line\nb\nc", vec![0..2, 28..29])]
+    #[case("# srgn:ignore-next-line\n# srgn:ignore-next-line\na\nb\n", vec![50..52])]
+    #[case("a\r\n// srgn:ignore-next-line\r\nb\r\nc\r\n", vec![0..3, 32..35])]
+    fn test_ignore_markers_scoping(#[case] input: &str, #[case] expected: Vec<Range<usize>>) {
+        let ranges: Vec<_> = IgnoreMarkers
+            .scope_raw(input)
+            .into_iter()
+            .map(|(r, _)| r)
+            .collect();
+        assert_eq!(ranges, expected);
+    }
+}
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
+use std::convert::Infallible;
 use std::error::Error;
 use std::fmt;
+use std::str::FromStr;
 
 use super::scope::{RangesWithContext, ScopeContext};
 use super::Scoper;
@@ -11,11 +13,16 @@ use crate::{RegexPattern, GLOBAL_SCOPE};
 pub struct Regex {
     pattern: RegexPattern,
     captures: Vec<CaptureGroup>,
+    /// The capture group to scope instead of the entire match, if any.
+    group: Option<CaptureGroup>,
 }
 
 /// A capture group in a regex, which can be either named (`(?<name>REGEX)`) or numbered
 /// (`(REGEX)`).
+///
+/// Serializes as just the name or number, so that it can key maps.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(untagged))]
 pub enum CaptureGroup {
     /// A named capture group.
     Named(String),
@@ -33,6 +40,27 @@ impl fmt::Display for CaptureGroup {
     }
 }
 
+impl CaptureGroup {
+    /// The match of this group in `captures`, if it participated.
+    fn get<'h>(&self, captures: &fancy_regex::Captures<'h>) -> Option<fancy_regex::Match<'h>> {
+        match self {
+            Self::Named(name) => captures.name(name),
+            Self::Numbered(number) => captures.get(*number),
+        }
+    }
+}
+
+impl FromStr for CaptureGroup {
+    type Err = Infallible;
+
+    /// Parses a group as referred to in patterns and replacements: all digits for a
+    /// numbered group, anything else for a named one.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.parse()
+            .map_or_else(|_| Self::Named(s.to_owned()), Self::Numbered))
+    }
+}
+
 impl Regex {
     /// Create a new regular expression.
     #[must_use]
@@ -50,6 +78,155 @@ impl Regex {
         Self {
             pattern,
             captures: capture_names,
+            group: None,
+        }
+    }
+
+    /// Scope only the given capture `group` of each match, instead of the entire
+    /// match. The rest of the match still has to match, anchoring the group, but is
+    /// left alone. Matches in which the group does not participate are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the pattern has no such group.
+    #[allow(clippy::result_large_err)] // Same error as for `TryFrom<String>`
+    pub fn with_group(mut self, group: CaptureGroup) -> Result<Self, RegexError> {
+        if !self.captures.contains(&group) {
+            return Err(RegexError(RegexErrorKind::UnknownGroup(group)));
+        }
+
+        self.group = Some(group);
+        Ok(self)
+    }
+
+    /// Return a builder for a regular expression of the given `pattern`.
+    ///
+    /// For API discoverability.
+    #[must_use]
+    pub fn builder(pattern: &str) -> RegexBuilder {
+        RegexBuilder::new(pattern)
+    }
+}
+
+/// Builds a [`Regex`] from a pattern, with flags set through methods instead of inline
+/// syntax like `(?i)`.
+///
+/// Unicode support is always on; disabling it is not supported.
+///
+/// ## Example
+///
+/// ```rust
+/// use srgn::scoping::regex::Regex;
+/// use srgn::scoping::view::ScopedViewBuilder;
+///
+/// let regex = Regex::builder("^hello.")
+///     .case_insensitive(true)
+///     .multi_line(true)
+///     .dot_matches_new_line(true)
+///     .build()
+///     .unwrap();
+///
+/// let mut builder = ScopedViewBuilder::new("x\nHello\n");
+/// builder.explode(&regex);
+/// let mut view = builder.build();
+/// view.delete();
+///
+/// assert_eq!(view.to_string(), "x\n");
+/// ```
+///
+/// Scoping only part of each match:
+///
+/// ```rust
+/// use srgn::scoping::regex::{CaptureGroup, Regex};
+/// use srgn::scoping::view::ScopedViewBuilder;
+///
+/// let regex = Regex::builder(r"version = (?<v>[\d.]+)")
+///     .group(Some(CaptureGroup::Named("v".into())))
+///     .build()
+///     .unwrap();
+///
+/// let mut builder = ScopedViewBuilder::new("version = 1.2\nother = 3.4\n");
+/// builder.explode(&regex);
+/// let mut view = builder.build();
+/// view.replace("2.0".into()).unwrap();
+///
+/// assert_eq!(view.to_string(), "version = 2.0\nother = 3.4\n");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexBuilder {
+    pattern: String,
+    case_insensitive: bool,
+    multi_line: bool,
+    dot_matches_new_line: bool,
+    group: Option<CaptureGroup>,
+}
+
+impl RegexBuilder {
+    /// Create a new builder for the given `pattern`, with all flags off.
+    #[must_use]
+    pub fn new(pattern: &str) -> Self {
+        Self {
+            pattern: pattern.to_owned(),
+            case_insensitive: false,
+            multi_line: false,
+            dot_matches_new_line: false,
+            group: None,
+        }
+    }
+
+    /// Match letters regardless of their case, like `(?i)`.
+    pub fn case_insensitive(&mut self, yes: bool) -> &mut Self {
+        self.case_insensitive = yes;
+        self
+    }
+
+    /// Have `^` and `$` match at the start and end of each line, not only of the
+    /// entire input, like `(?m)`.
+    pub fn multi_line(&mut self, yes: bool) -> &mut Self {
+        self.multi_line = yes;
+        self
+    }
+
+    /// Have `.` match newlines, too, like `(?s)`.
+    pub fn dot_matches_new_line(&mut self, yes: bool) -> &mut Self {
+        self.dot_matches_new_line = yes;
+        self
+    }
+
+    /// Scope only the given capture `group` of each match, see [`Regex::with_group`].
+    /// [`None`] scopes entire matches.
+    pub fn group(&mut self, group: Option<CaptureGroup>) -> &mut Self {
+        self.group = group;
+        self
+    }
+
+    /// Build the [`Regex`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if the pattern is invalid, or lacks the requested capture group.
+    #[allow(clippy::result_large_err)] // Same error as for `TryFrom<String>`
+    pub fn build(&self) -> Result<Regex, RegexError> {
+        let flags: String = [
+            (self.case_insensitive, 'i'),
+            (self.multi_line, 'm'),
+            (self.dot_matches_new_line, 's'),
+        ]
+        .into_iter()
+        .filter_map(|(set, flag)| set.then_some(flag))
+        .collect();
+
+        let pattern = if flags.is_empty() {
+            RegexPattern::new(&self.pattern)
+        } else {
+            RegexPattern::new(&format!("(?{flags}){}", self.pattern))
+        }
+        .map_err(RegexError::from)?;
+
+        let regex = Regex::new(pattern);
+        match self.group.clone() {
+            Some(group) => regex.with_group(group),
+            None => Ok(regex),
         }
     }
 }
@@ -58,11 +235,28 @@ impl Regex {
 ///
 /// Simple wrapper.
 #[derive(Debug)]
-pub struct RegexError(fancy_regex::Error);
+pub struct RegexError(RegexErrorKind);
+
+#[derive(Debug)]
+enum RegexErrorKind {
+    Pattern(fancy_regex::Error),
+    UnknownGroup(CaptureGroup),
+}
+
+impl From<fancy_regex::Error> for RegexError {
+    fn from(err: fancy_regex::Error) -> Self {
+        Self(RegexErrorKind::Pattern(err))
+    }
+}
 
 impl fmt::Display for RegexError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Invalid regex: {}", self.0)
+        match &self.0 {
+            RegexErrorKind::Pattern(e) => write!(f, "Invalid regex: {e}"),
+            RegexErrorKind::UnknownGroup(group) => {
+                write!(f, "Regex has no capture group {group}")
+            }
+        }
     }
 }
 
@@ -72,7 +266,7 @@ impl TryFrom<String> for Regex {
     type Error = RegexError;
 
     fn try_from(pattern: String) -> Result<Self, Self::Error> {
-        let pattern = RegexPattern::new(&pattern).map_err(RegexError)?;
+        let pattern = RegexPattern::new(&pattern)?;
 
         Ok(Self::new(pattern))
     }
@@ -93,21 +287,21 @@ impl Scoper for Regex {
                     let capture_context: HashMap<CaptureGroup, &str> = self
                         .captures
                         .iter()
-                        .filter_map(|cg| {
-                            match cg {
-                                CaptureGroup::Named(name) => cap.name(name.as_str()),
-                                CaptureGroup::Numbered(number) => cap.get(*number),
-                            }
-                            .map(|r#match| (cg.clone(), r#match.as_str()))
-                        })
+                        .filter_map(|cg| cg.get(&cap).map(|r#match| (cg.clone(), r#match.as_str())))
                         .collect();
 
-                    ranges.push((
-                        cap.get(0)
+                    let range = match &self.group {
+                        None => cap
+                            .get(0)
                             .expect("index 0 guaranteed to contain whole match")
                             .range(),
-                        Some(ScopeContext::CaptureGroups(capture_context)),
-                    ));
+                        Some(group) => match group.get(&cap) {
+                            Some(r#match) => r#match.range(),
+                            None => continue, // Group did not participate
+                        },
+                    };
+
+                    ranges.push((range, Some(ScopeContext::CaptureGroups(capture_context))));
                 }
                 // Let's blow up on purpose instead of silently continuing; any of
                 // these errors a user will likely want to know about, as they
@@ -461,6 +655,89 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[rstest]
+    #[case("a", false, false, false, "aA", vec![0..1])]
+    #[case("a", true, false, false, "aA", vec![0..1, 1..2])]
+    #[case("^a$", false, false, false, "a\na", vec![])]
+    #[case("^a$", false, true, false, "a\na", vec![0..1, 2..3])]
+    #[case("a.a", false, false, false, "a\na", vec![])]
+    #[case("a.a", false, false, true, "a\na", vec![0..3])]
+    #[case("(?-i)a", true, false, false, "aA", vec![0..1])] // Inline flags take precedence
+    #[case("^A.$", true, true, true, "x\na\n\n", vec![2..4])]
+    fn test_regex_builder_flags(
+        #[case] pattern: &str,
+        #[case] case_insensitive: bool,
+        #[case] multi_line: bool,
+        #[case] dot_matches_new_line: bool,
+        #[case] input: &str,
+        #[case] expected: Vec<std::ops::Range<usize>>,
+    ) {
+        let regex = Regex::builder(pattern)
+            .case_insensitive(case_insensitive)
+            .multi_line(multi_line)
+            .dot_matches_new_line(dot_matches_new_line)
+            .build()
+            .unwrap();
+
+        let ranges: Vec<_> = regex.scope_raw(input).into_iter().map(|(r, _)| r).collect();
+        assert_eq!(ranges, expected);
+    }
+
+    #[rstest]
+    #[case(r"a(b)c", "1", "abc abc", vec![1..2, 5..6])]
+    #[case(r"a(?<x>b)c", "x", "abc", vec![1..2])]
+    #[case(r"a(?<x>b)c", "0", "abc", vec![0..3])] // Entire match
+    #[case(r"a(b)|c(d)", "2", "ab cd", vec![4..5])] // Non-participating skipped
+    #[case(r"(?<=x)(y)", "1", "xy y", vec![1..2])]
+    #[case(r"a(b?)", "1", "a", vec![])] // Empty groups discarded
+    #[case(r"(ä)(ö)", "2", "äö", vec![2..4])]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_regex_group_scoping(
+        #[case] pattern: &str,
+        #[case] group: &str,
+        #[case] input: &str,
+        #[case] expected: Vec<std::ops::Range<usize>>,
+    ) {
+        let regex = Regex::builder(pattern)
+            .group(Some(group.parse().unwrap()))
+            .build()
+            .unwrap();
+
+        let mut builder = ScopedViewBuilder::new(input);
+        builder.explode(&regex);
+        let view = builder.build();
+
+        let ranges: Vec<_> = view.located().map(|located| located.range).collect();
+        assert_eq!(ranges, expected);
+    }
+
+    #[rstest]
+    #[case(r"a(b)", "2")]
+    #[case(r"a(?<x>b)", "y")]
+    #[case(r"a(?<x>b)", "1")] // Named groups have no number
+    fn test_regex_group_unknown(#[case] pattern: &str, #[case] group: &str) {
+        let err = Regex::builder(pattern)
+            .group(Some(group.parse().unwrap()))
+            .build()
+            .unwrap_err();
+
+        assert!(err.to_string().starts_with("Regex has no capture group"));
+    }
+
+    #[rstest]
+    #[case("0", CaptureGroup::Numbered(0))]
+    #[case("12", CaptureGroup::Numbered(12))]
+    #[case("name", CaptureGroup::Named("name".into()))]
+    #[case("1a", CaptureGroup::Named("1a".into()))]
+    fn test_capture_group_parsing(#[case] input: &str, #[case] expected: CaptureGroup) {
+        assert_eq!(input.parse::<CaptureGroup>().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_regex_builder_invalid_pattern() {
+        assert!(Regex::builder("(").case_insensitive(true).build().is_err());
+    }
+
     mod fuzzyish {
         use std::time::{Duration, Instant};
 
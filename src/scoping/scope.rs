@@ -1,8 +1,10 @@
 use std::borrow::Cow;
+use std::cmp::Reverse;
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 use std::ops::Range;
 
-use itertools::Itertools;
 use log::{debug, trace};
 
 use super::regex::CaptureGroup;
@@ -11,6 +13,7 @@ use crate::scoping::scope::Scope::{In, Out};
 
 /// Indicates whether a given string part is in scope.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Scope<'viewee, T> {
     /// The given part is in scope for processing.
     In(T, Option<ScopeContext<'viewee>>),
@@ -22,14 +25,17 @@ pub enum Scope<'viewee, T> {
 
 /// A read-only scope.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ROScope<'viewee>(pub Scope<'viewee, &'viewee str>);
 
 /// Multiple read-only scopes.
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ROScopes<'viewee>(pub Vec<ROScope<'viewee>>);
 
 /// A read-write scope.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct RWScope<'viewee>(pub Scope<'viewee, Cow<'viewee, str>>);
 
 #[cfg(test)] // For convenience; not legal in normal code
@@ -44,6 +50,7 @@ impl<'viewee> From<Scope<'viewee, &'viewee str>> for RWScope<'viewee> {
 
 /// Multiple read-write scopes.
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct RWScopes<'viewee>(pub Vec<RWScope<'viewee>>);
 
 #[cfg(test)] // For convenience; not legal in normal code
@@ -52,7 +59,7 @@ where
     I: IntoIterator<Item = Scope<'viewee, &'viewee str>>,
 {
     fn from(value: I) -> Self {
-        Self(value.into_iter().map(Into::into).collect_vec())
+        Self(value.into_iter().map(Into::into).collect())
     }
 }
 
@@ -80,24 +87,113 @@ impl From<Ranges<usize>> for RangesWithContext<'_> {
     }
 }
 
+/// How to resolve ranges overlapping each other, for example captures of a
+/// tree-sitter query nested in one another.
+///
+/// Ranges merely bordering each other do not overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overlap {
+    /// The range starting first wins (at equal starts, the longest; if also of equal
+    /// length, the one given first). Ranges overlapping it are dropped entirely.
+    #[default]
+    FirstWins,
+    /// Overlapping ranges are merged into one, keeping the context of the first.
+    Merge,
+    /// Overlapping ranges are an error.
+    Error,
+}
+
+impl Overlap {
+    /// Sorts `ranges` by their start, resolving any overlaps between them. Empty ranges
+    /// are dropped.
+    ///
+    /// # Errors
+    ///
+    /// Errors for [`Overlap::Error`] if any ranges overlap, reporting the first pair.
+    pub fn resolve(
+        self,
+        mut ranges: RangesWithContext<'_>,
+    ) -> Result<RangesWithContext<'_>, OverlapError> {
+        ranges.retain(|(range, _)| !range.is_empty());
+        ranges.sort_by_key(|(range, _)| (range.start, Reverse(range.end)));
+
+        let mut resolved: RangesWithContext<'_> = Vec::with_capacity(ranges.len());
+        for (range, context) in ranges {
+            match resolved.last_mut() {
+                Some((last, _)) if range.start < last.end => match self {
+                    Self::FirstWins => trace!("Dropping {range:?}, overlapping {last:?}"),
+                    Self::Merge => last.end = last.end.max(range.end),
+                    Self::Error => {
+                        return Err(OverlapError {
+                            first: last.clone(),
+                            second: range,
+                        })
+                    }
+                },
+                _ => resolved.push((range, context)),
+            }
+        }
+
+        Ok(resolved)
+    }
+}
+
+/// Ranges overlapped, see [`Overlap::Error`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverlapError {
+    /// The range starting first.
+    pub first: Range<usize>,
+    /// The range overlapping [`Self::first`].
+    pub second: Range<usize>,
+}
+
+impl fmt::Display for OverlapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Ranges {:?} and {:?} overlap", self.first, self.second)
+    }
+}
+
+impl Error for OverlapError {}
+
 impl<'viewee> ROScopes<'viewee> {
     /// Construct a new instance from the given raw ranges.
     ///
     /// The passed `input` will be traversed according to `ranges`: all specified
     /// `ranges` are taken as [`In`] scope, everything not covered by a range is [`Out`]
-    /// of scope.
+    /// of scope. Overlapping `ranges` are resolved as per [`Overlap::FirstWins`]; see
+    /// [`Self::try_from_raw_ranges`] for other options.
     ///
     /// ## Panics
     ///
     /// Panics if the given `ranges` contain indices out-of-bounds for `input`.
     #[must_use]
     pub fn from_raw_ranges(input: &'viewee str, ranges: RangesWithContext<'viewee>) -> Self {
+        Self::try_from_raw_ranges(input, ranges, Overlap::FirstWins)
+            .expect("resolving overlaps by first one winning is infallible")
+    }
+
+    /// Same as [`Self::from_raw_ranges`], resolving overlapping `ranges` as per
+    /// `overlap`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if `ranges` overlap and `overlap` is [`Overlap::Error`].
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the given `ranges` contain indices out-of-bounds for `input`.
+    pub fn try_from_raw_ranges(
+        input: &'viewee str,
+        ranges: RangesWithContext<'viewee>,
+        overlap: Overlap,
+    ) -> Result<Self, OverlapError> {
         trace!("Constructing scopes from raw ranges: {:?}", ranges);
 
+        let ranges = overlap.resolve(ranges)?;
         let mut scopes = Vec::with_capacity(ranges.len());
 
         let mut last_end = 0;
-        for (Range { start, end }, context) in ranges.into_iter().sorted_by_key(|(r, _)| r.start) {
+        for (Range { start, end }, context) in ranges {
             let range = last_end..start;
             let out = &input[range.clone()];
             if !out.is_empty() {
@@ -121,7 +217,7 @@ impl<'viewee> ROScopes<'viewee> {
 
         debug!("Scopes: {:?}", scopes);
 
-        ROScopes(scopes)
+        Ok(ROScopes(scopes))
     }
 
     /// Inverts the scopes: what was previously [`In`] is now [`Out`], and vice versa.
@@ -211,9 +307,12 @@ impl<'viewee> From<&'viewee RWScope<'viewee>> for &'viewee str {
 /// For example, a scope might have been created by a regular expression, in which case
 /// capture groups might have matched.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ScopeContext<'viewee> {
     /// Regular expression capture groups mapped to the content they matched.
     CaptureGroups(HashMap<CaptureGroup, &'viewee str>),
+    /// Name of the tree-sitter query capture which matched.
+    QueryCapture(String),
 }
 
 #[cfg(test)]
@@ -263,4 +362,95 @@ mod tests {
     ) {
         assert!((scopes == string) == equal);
     }
+
+    #[rstest]
+    #[case(vec![], Overlap::FirstWins, Ok(vec![]))]
+    #[case(vec![1..2, 0..1], Overlap::Error, Ok(vec![0..1, 1..2]))] // Bordering
+    #[case(vec![0..0, 0..1], Overlap::Error, Ok(vec![0..1]))] // Empty dropped
+    //
+    #[case(vec![0..3, 1..2], Overlap::FirstWins, Ok(vec![0..3]))]
+    #[case(vec![1..2, 0..3], Overlap::FirstWins, Ok(vec![0..3]))]
+    #[case(vec![0..2, 1..4, 3..5], Overlap::FirstWins, Ok(vec![0..2, 3..5]))]
+    #[case(vec![0..2, 0..4], Overlap::FirstWins, Ok(vec![0..4]))] // Longest wins
+    //
+    #[case(vec![0..3, 1..2], Overlap::Merge, Ok(vec![0..3]))]
+    #[case(vec![0..2, 1..4, 3..5], Overlap::Merge, Ok(vec![0..5]))]
+    #[case(vec![0..2, 2..4, 3..5], Overlap::Merge, Ok(vec![0..2, 2..5]))]
+    //
+    #[case(vec![0..2, 1..4], Overlap::Error, Err(OverlapError { first: 0..2, second: 1..4 }))]
+    #[case(vec![3..5, 0..4], Overlap::Error, Err(OverlapError { first: 0..4, second: 3..5 }))]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_overlap_resolve(
+        #[case] ranges: Vec<Range<usize>>,
+        #[case] overlap: Overlap,
+        #[case] expected: Result<Vec<Range<usize>>, OverlapError>,
+    ) {
+        let ranges = ranges.into_iter().map(|range| (range, None)).collect();
+
+        let resolved = overlap
+            .resolve(ranges)
+            .map(|ranges| ranges.into_iter().map(|(range, _)| range).collect());
+
+        assert_eq!(resolved, expected);
+    }
+
+    #[test]
+    fn test_overlap_resolve_keeps_first_context() {
+        let context = |name: &str| Some(ScopeContext::QueryCapture(name.to_owned()));
+        let ranges = vec![
+            (1..3, context("b")),
+            (0..2, context("a")),
+            (0..2, context("c")),
+        ];
+
+        assert_eq!(
+            Overlap::FirstWins.resolve(ranges.clone()).unwrap(),
+            vec![(0..2, context("a"))]
+        );
+        assert_eq!(
+            Overlap::Merge.resolve(ranges).unwrap(),
+            vec![(0..3, context("a"))]
+        );
+    }
+
+    #[rstest]
+    #[case(vec![0..3, 1..2], "abcd", vec![In("abc", None), Out("d")])]
+    #[case(vec![2..4, 0..3], "abcd", vec![In("abc", None), Out("d")])]
+    #[case(vec![1..3, 1..2, 2..4], "abcd", vec![Out("a"), In("bc", None), Out("d")])]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_from_raw_ranges_with_overlaps(
+        #[case] ranges: Vec<Range<usize>>,
+        #[case] input: &str,
+        #[case] expected: Vec<Scope<'_, &str>>,
+    ) {
+        let ranges = ranges.into_iter().map(|range| (range, None)).collect();
+
+        let scopes = ROScopes::from_raw_ranges(input, ranges);
+
+        assert_eq!(
+            scopes,
+            ROScopes(expected.into_iter().map(ROScope).collect())
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize() {
+        let scopes = ROScopes(vec![
+            ROScope(Out("x = ")),
+            ROScope(In(
+                "ab",
+                Some(ScopeContext::CaptureGroups(HashMap::from([(
+                    CaptureGroup::Numbered(0),
+                    "ab",
+                )]))),
+            )),
+            ROScope(In("c", Some(ScopeContext::QueryCapture("s".to_owned())))),
+        ]);
+
+        assert_eq!(
+            serde_json::to_string(&scopes).unwrap(),
+            r#"[{"Out":"x = "},{"In":["ab",{"CaptureGroups":{"0":"ab"}}]},{"In":["c",{"QueryCapture":"s"}]}]"#
+        );
+    }
 }
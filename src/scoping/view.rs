@@ -1,15 +1,20 @@
 use std::borrow::Cow;
 use std::fmt;
+use std::ops::Range;
 
 use itertools::Itertools;
 use log::{debug, trace, warn};
+use tree_sitter::Language as TSLanguage;
 
 use crate::actions::{self, Action, ActionError};
+use crate::newlines::LineEnding;
+use crate::ranges::Ranges;
 use crate::scoping::dosfix::DosFix;
+use crate::scoping::langs;
 use crate::scoping::scope::Scope::{In, Out};
-#[cfg(doc)]
-use crate::scoping::scope::ScopeContext;
-use crate::scoping::scope::{ROScope, ROScopes, RWScope, RWScopes};
+use crate::scoping::scope::{
+    Overlap, OverlapError, ROScope, ROScopes, RWScope, RWScopes, RangesWithContext, ScopeContext,
+};
 use crate::scoping::Scoper;
 
 /// A view of some input, sorted into parts, which are either [`In`] or [`Out`] of scope
@@ -20,6 +25,7 @@ use crate::scoping::Scoper;
 ///
 /// The main avenue for constructing a view is [`Self::builder`].
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ScopedView<'viewee> {
     scopes: RWScopes<'viewee>,
 }
@@ -52,14 +58,33 @@ impl<'viewee> ScopedView<'viewee> {
     /// This method is infallible, as it does not access any [`ScopeContext`].
     ///
     /// See implementors of [`Action`] for available types.
-    #[allow(clippy::missing_panics_doc)] // 🤞
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `action` itself is fallible and fails (see [`Action::try_act`]).
+    /// Use [`Self::try_map_without_context`] for those.
     pub fn map_without_context(&mut self, action: &impl Action) -> &mut Self {
-        self.map_impl(action, false)
-            .expect("not accessing context, so is infallible");
+        if let Err(e) = self.map_impl(action, false) {
+            panic!("action failed, despite infallible application: {e}");
+        }
 
         self
     }
 
+    /// Same as [`Self::map_without_context`], but for fallible actions (see
+    /// [`Action::try_act`]).
+    ///
+    /// # Errors
+    ///
+    /// Errors if the `action` fails for any [`In`] scope item. Items already mapped by
+    /// then stay mapped.
+    pub fn try_map_without_context(
+        &mut self,
+        action: &impl Action,
+    ) -> Result<&mut Self, ActionError> {
+        self.map_impl(action, false)
+    }
+
     /// Same as [`Self::map_without_context`], but will access any [`ScopeContext`],
     /// which is fallible.
     ///
@@ -72,30 +97,37 @@ impl<'viewee> ScopedView<'viewee> {
         Ok(self)
     }
 
+    /// Same as [`Self::map_with_context`], but applies the `action` to [`In`] scope
+    /// items in parallel, on [`rayon`]'s global thread pool.
+    ///
+    /// Results end up in the same order as if mapped sequentially. Parallelism only
+    /// pays off for expensive actions (calling out to external programs, ...) over
+    /// many items; for cheap ones, the overhead dominates.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the `action` fails for any [`In`] scope item. If it fails for
+    /// several, it is unspecified which error is returned. Other items might be mapped
+    /// or not by then.
+    #[cfg(feature = "parallel")]
+    pub fn map_par(&mut self, action: &impl Action) -> Result<&mut Self, ActionError> {
+        use rayon::prelude::*;
+
+        self.scopes
+            .0
+            .par_iter_mut()
+            .try_for_each(|scope| map_scope(scope, action, true))?;
+
+        Ok(self)
+    }
+
     fn map_impl(
         &mut self,
         action: &impl Action,
         use_context: bool,
     ) -> Result<&mut Self, ActionError> {
         for scope in &mut self.scopes.0 {
-            match scope {
-                RWScope(In(s, ctx)) => {
-                    debug!("Mapping with context: {:?}", ctx);
-                    let res = match (&ctx, use_context) {
-                        (Some(c), true) => action.act_with_context(s, c)?,
-                        _ => action.act(s),
-                    };
-                    debug!(
-                        "Replacing '{}' with '{}'",
-                        s.escape_debug(),
-                        res.escape_debug()
-                    );
-                    *scope = RWScope(In(Cow::Owned(res), ctx.clone()));
-                }
-                RWScope(Out(s)) => {
-                    debug!("Appending '{}'", s.escape_debug());
-                }
-            }
+            map_scope(scope, action, use_context)?;
         }
 
         Ok(self)
@@ -106,18 +138,90 @@ impl<'viewee> ScopedView<'viewee> {
         debug!("Squeezing view by collapsing all consecutive in-scope occurrences.");
 
         let mut prev_was_in = false;
-        self.scopes.0.retain(|scope| {
-            let keep = !(prev_was_in && matches!(scope, RWScope(In { .. })));
-            prev_was_in = matches!(scope, RWScope(In { .. }));
+        for scope in std::mem::take(&mut self.scopes.0) {
+            let is_in = matches!(scope, RWScope(In { .. }));
+            let keep = !(prev_was_in && is_in);
+            prev_was_in = is_in;
             trace!("keep: {}, scope: {:?}", keep, scope);
-            keep
-        });
+
+            if keep {
+                self.scopes.0.push(scope);
+            } else if !<&str>::from(&scope).is_empty() {
+                // The kept occurrence now stands in for content which changed.
+                if let Some(RWScope(In(s, _))) = self.scopes.0.last_mut() {
+                    s.to_mut();
+                }
+            }
+        }
 
         debug!("Squeezed: {:?}", self.scopes);
 
         self
     }
 
+    /// Convert line endings in [`In`] scope items to the given `ending`, where actions
+    /// introduced them.
+    ///
+    /// Useful after applying actions which might have synthesized newlines (e.g.
+    /// replacements containing `\n`), to not end up with mixed line endings. `before`
+    /// is this view as it was before applying those actions. Line endings found in the
+    /// input are kept as they are: items unchanged by actions are left alone, as are
+    /// changed items which originally contained line endings other than `ending`
+    /// (entirely so, as it is unknown which of their line endings actions introduced).
+    /// [`Out`] of scope items are left alone, too.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `before` does not have as many items as this view.
+    pub fn convert_line_endings(&mut self, ending: LineEnding, before: &Self) -> &mut Self {
+        debug!("Converting line endings of changed in-scope items to '{ending}'.");
+
+        let mut preceded_by_cr = false;
+        let mut originally_preceded_by_cr = false;
+        for (scope, original) in self.scopes.0.iter_mut().zip_eq(&before.scopes.0) {
+            let original: &str = original.into();
+
+            if let RWScope(In(s @ Cow::Owned(_), _)) = scope {
+                let foreign_endings = matches!(
+                    ending.convert(original, originally_preceded_by_cr),
+                    Cow::Owned(_)
+                );
+
+                if !foreign_endings {
+                    if let Cow::Owned(converted) = ending.convert(s, preceded_by_cr) {
+                        *s = Cow::Owned(converted);
+                    }
+                }
+            }
+
+            let s: &str = (&*scope).into();
+            if !s.is_empty() {
+                preceded_by_cr = s.ends_with('\r');
+            }
+            if !original.is_empty() {
+                originally_preceded_by_cr = original.ends_with('\r');
+            }
+        }
+
+        self
+    }
+
+    /// Check whether the contents of this view differ from the input it was built from.
+    ///
+    /// Cheap, as no comparison with the input takes place: items left untouched by
+    /// actions and other manipulations remain borrowed from the input. Useful to, for
+    /// example, skip writing files back if nothing changed.
+    ///
+    /// Errs on the side of caution: items changed by one action but changed back by
+    /// another count as changed.
+    #[must_use]
+    pub fn changed(&self) -> bool {
+        self.scopes
+            .0
+            .iter()
+            .any(|s| matches!(s, RWScope(In(Cow::Owned(_), _))))
+    }
+
     /// Check whether anything is [`In`] scope for this view.
     #[must_use]
     pub fn has_any_in_scope(&self) -> bool {
@@ -165,6 +269,193 @@ impl<'viewee> ScopedView<'viewee> {
 
         ScopedViewLines(lines.into_iter().map(ScopedView::new).collect_vec())
     }
+
+    /// Iterate over all [`In`] scope items, along with where they are located.
+    ///
+    /// Locations refer to the contents of this view. As long as no action was mapped
+    /// over it, these are the original input. Afterwards, they are the view's current,
+    /// mapped contents (as [displayed][`fmt::Display`]).
+    pub fn located(&self) -> impl Iterator<Item = Located<'_>> {
+        locate(self.scopes.0.iter().map(|RWScope(scope)| match scope {
+            In(s, ctx) => (&**s, true, ctx.as_ref()),
+            Out(s) => (*s, false, None),
+        }))
+    }
+
+    /// Map byte offsets between the input of this view and its transformed contents
+    /// (as [displayed][`fmt::Display`]), after actions were mapped over it.
+    ///
+    /// `original` is this very view *before* any actions were mapped over it (for
+    /// example, a clone taken right after [building][`ScopedViewBuilder::build`]).
+    /// Useful for editor integrations, to carry diagnostics, cursors and the like
+    /// across a rewrite.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `original` is made up of a different number of scopes than this view,
+    /// which happens if it is not this view's original, or if scopes were restructured
+    /// (like by [`Self::squeeze`]) in between.
+    #[must_use]
+    pub fn source_map(&self, original: &ScopedView<'_>) -> SourceMap {
+        let mut offsets = (0, 0);
+
+        let scopes = original
+            .scopes
+            .0
+            .iter()
+            .zip_eq(&self.scopes.0)
+            .filter_map(|(before, after)| {
+                let is_in = matches!(after, RWScope(In(..)));
+                let (before, after): (&str, &str) = (before.into(), after.into());
+                let mapped = MappedScope {
+                    original: offsets.0..offsets.0 + before.len(),
+                    transformed: offsets.1..offsets.1 + after.len(),
+                };
+                offsets = (mapped.original.end, mapped.transformed.end);
+
+                is_in.then_some(mapped)
+            })
+            .collect();
+
+        SourceMap { scopes }
+    }
+}
+
+/// A position in some input, see [`Located`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Position {
+    /// The 1-based line number.
+    pub line: usize,
+    /// The 1-based column, counted in bytes from the start of the line.
+    pub column: usize,
+}
+
+impl Position {
+    /// The position right after `text`, which starts at this position.
+    fn after(self, text: &str) -> Self {
+        text.rfind('\n').map_or(
+            Self {
+                line: self.line,
+                column: self.column + text.len(),
+            },
+            |i| Self {
+                line: self.line + text.matches('\n').count(),
+                column: text.len() - i,
+            },
+        )
+    }
+}
+
+/// An [`In`] scope item, located within the input it is part of.
+///
+/// Obtained via [`ScopedViewBuilder::located`] or [`ScopedView::located`], for
+/// consumers which need to point *at* items (linters, editor integrations, ...)
+/// instead of transforming them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Located<'a> {
+    /// The byte offsets of the item.
+    pub range: Range<usize>,
+    /// The position of the item's first byte.
+    pub start: Position,
+    /// The position right after the item's last byte, exclusive like `range`.
+    pub end: Position,
+    /// The item's contents.
+    pub text: &'a str,
+    /// Context the item was scoped with, if any.
+    pub context: Option<&'a ScopeContext<'a>>,
+}
+
+/// An [`In`] scope item's byte offsets, before and after actions were mapped over it.
+/// See [`SourceMap`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MappedScope {
+    /// The item's byte offsets in the original input.
+    pub original: Range<usize>,
+    /// The item's byte offsets in the transformed contents.
+    pub transformed: Range<usize>,
+}
+
+/// A mapping between byte offsets of some input and of its transformed version,
+/// obtained via [`ScopedView::source_map`].
+///
+/// Only [`In`] scope items can change, everything in between is merely shifted. Offsets
+/// *within* an item map to the same relative offset in its counterpart, as far as that
+/// reaches (there is no telling how actions moved contents around within an item).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SourceMap {
+    scopes: Vec<MappedScope>,
+}
+
+impl SourceMap {
+    /// All [`In`] scope items, in order.
+    #[must_use]
+    pub fn scopes(&self) -> &[MappedScope] {
+        &self.scopes
+    }
+
+    /// Map a byte `offset` of the original input to the transformed contents.
+    #[must_use]
+    pub fn to_transformed(&self, offset: usize) -> usize {
+        self.translate(offset, |s| (&s.original, &s.transformed))
+    }
+
+    /// Map a byte `offset` of the transformed contents to the original input.
+    #[must_use]
+    pub fn to_original(&self, offset: usize) -> usize {
+        self.translate(offset, |s| (&s.transformed, &s.original))
+    }
+
+    /// Map `offset`, where `ranges` gives the ranges of each item to map from and to.
+    fn translate(
+        &self,
+        offset: usize,
+        ranges: impl Fn(&MappedScope) -> (&Range<usize>, &Range<usize>),
+    ) -> usize {
+        // Last item starting at or before `offset`.
+        let i = self.scopes.partition_point(|s| ranges(s).0.start <= offset);
+        let Some((from, to)) = i.checked_sub(1).map(|i| ranges(&self.scopes[i])) else {
+            // Before any item, where nothing moved.
+            return offset;
+        };
+
+        if offset < from.end {
+            // Within the item.
+            (to.start + (offset - from.start)).min(to.end)
+        } else {
+            // Past the item, shifted along with its end.
+            to.end + (offset - from.end)
+        }
+    }
+}
+
+/// Locates all [`In`] scope items of `scopes`, which make up some input in their
+/// entirety. Items are given as their contents, whether they are in scope, and their
+/// context.
+fn locate<'a>(
+    scopes: impl Iterator<Item = (&'a str, bool, Option<&'a ScopeContext<'a>>)>,
+) -> impl Iterator<Item = Located<'a>> {
+    let mut offset = 0;
+    let mut position = Position { line: 1, column: 1 };
+
+    scopes.filter_map(move |(text, is_in, context)| {
+        let range = offset..offset + text.len();
+        let start = position;
+
+        offset = range.end;
+        position = position.after(text);
+
+        is_in.then_some(Located {
+            range,
+            start,
+            end: position,
+            text,
+            context,
+        })
+    })
 }
 
 /// A view over a [`ScopedView`], split by its individual lines. Each line is its own
@@ -266,6 +557,39 @@ impl ScopedView<'_> {
     }
 }
 
+/// Applies `action` to `scope`, if it is [`In`] scope.
+fn map_scope(
+    scope: &mut RWScope<'_>,
+    action: &impl Action,
+    use_context: bool,
+) -> Result<(), ActionError> {
+    match scope {
+        RWScope(In(s, ctx)) => {
+            debug!("Mapping with context: {:?}", ctx);
+            let res = match (&ctx, use_context) {
+                (Some(c), true) => action.act_with_context(s, c)?,
+                _ => action.try_act(s)?,
+            };
+            if res == *s {
+                debug!("Keeping '{}' (unchanged)", s.escape_debug());
+                return Ok(());
+            }
+
+            debug!(
+                "Replacing '{}' with '{}'",
+                s.escape_debug(),
+                res.escape_debug()
+            );
+            *scope = RWScope(In(Cow::Owned(res), ctx.clone()));
+        }
+        RWScope(Out(s)) => {
+            debug!("Appending '{}'", s.escape_debug());
+        }
+    }
+
+    Ok(())
+}
+
 impl fmt::Display for ScopedView<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for scope in &self.scopes.0 {
@@ -343,15 +667,74 @@ impl<'viewee> ScopedViewBuilder<'viewee> {
     ///
     /// after application. Anything [`Out`] out of scope can never be brought back.
     ///
+    /// Parts remaining [`In`] scope inherit any [`ScopeContext::QueryCapture`] of the
+    /// scope they were split from. Contexts returned by the `scoper` itself are
+    /// dropped in that case. Ranges returned by the `scoper` which overlap each other
+    /// are resolved as per [`Overlap::FirstWins`]; see [`Self::try_explode`] for other
+    /// options.
+    ///
     /// ## Panics
     ///
     /// Panics if the [`Scoper`] scopes such that the view is no longer consistent, i.e.
     /// gaps were created and the original input can no longer be reconstructed from the
     /// new view. This would be an internal bug.
     pub fn explode(&mut self, scoper: &impl Scoper) -> &mut Self {
+        self.explode_by(|s| Ok(scoper.scope(s)))
+            .expect("scoping without resolving overlaps is infallible")
+    }
+
+    /// Same as [`Self::explode`], resolving ranges returned by the `scoper` which
+    /// overlap each other as per `overlap`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use srgn::scoping::scope::{Overlap, RangesWithContext};
+    /// use srgn::scoping::view::ScopedViewBuilder;
+    /// use srgn::scoping::Scoper;
+    ///
+    /// struct Nested;
+    ///
+    /// impl Scoper for Nested {
+    ///     fn scope_raw<'viewee>(&self, _input: &'viewee str) -> RangesWithContext<'viewee> {
+    ///         vec![(0..2, None), (1..4, None)]
+    ///     }
+    /// }
+    ///
+    /// let mut builder = ScopedViewBuilder::new("abcd");
+    /// assert!(builder.clone().try_explode(&Nested, Overlap::Error).is_err());
+    ///
+    /// builder.try_explode(&Nested, Overlap::Merge).unwrap();
+    /// let mut view = builder.build();
+    /// view.delete();
+    /// assert_eq!(view.to_string(), "");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Errors if ranges overlap and `overlap` is [`Overlap::Error`]. The view under
+    /// construction is left untouched then.
+    ///
+    /// ## Panics
+    ///
+    /// Panics under the same conditions as [`Self::explode`].
+    pub fn try_explode(
+        &mut self,
+        scoper: &impl Scoper,
+        overlap: Overlap,
+    ) -> Result<&mut Self, OverlapError> {
+        self.explode_by(|s| ROScopes::try_from_raw_ranges(s, scoper.scope_raw(s), overlap))
+    }
+
+    /// Replaces each [`In`] scope by whatever `scope_with` returns for it, see
+    /// [`Self::explode`].
+    fn explode_by(
+        &mut self,
+        mut scope_with: impl FnMut(&'viewee str) -> Result<ROScopes<'viewee>, OverlapError>,
+    ) -> Result<&mut Self, OverlapError> {
         trace!("Exploding scopes: {:?}", self.scopes);
         let mut new = Vec::with_capacity(self.scopes.0.len());
-        for scope in self.scopes.0.drain(..) {
+        for scope in &self.scopes.0 {
             trace!("Exploding scope: {:?}", scope);
 
             if scope.is_empty() {
@@ -360,15 +743,22 @@ impl<'viewee> ScopedViewBuilder<'viewee> {
             }
 
             match scope {
-                ROScope(In(s, ..)) => {
-                    let mut new_scopes = scoper.scope(s);
+                ROScope(In(s, ctx)) => {
+                    let mut new_scopes = scope_with(s)?;
                     new_scopes.0.retain(|s| !s.is_empty());
+                    if let Some(ctx @ ScopeContext::QueryCapture(_)) = ctx {
+                        for ROScope(scope) in &mut new_scopes.0 {
+                            if let In(_, child_ctx) = scope {
+                                *child_ctx = Some(ctx.clone());
+                            }
+                        }
+                    }
                     new.extend(new_scopes.0);
                 }
                 // Be explicit about the `Out(_)` case, so changing the enum is a
                 // compile error
                 ROScope(Out("")) => {}
-                out @ ROScope(Out(_)) => new.push(out),
+                out @ ROScope(Out(_)) => new.push(out.clone()),
             }
 
             trace!("Exploded scope, new scopes are: {:?}", new);
@@ -390,10 +780,216 @@ impl<'viewee> ScopedViewBuilder<'viewee> {
             env!("CARGO_PKG_REPOSITORY")
         );
 
+        Ok(self)
+    }
+
+    /// Inverts the view under construction: what was [`In`] scope is now [`Out`] of
+    /// scope, and vice versa.
+    ///
+    /// Consecutive parts ending up [`In`] scope are merged into one. Any
+    /// [`ScopeContext`] is lost, as previously [`Out`] of scope parts never had any.
+    pub fn invert(&mut self) -> &mut Self {
+        trace!("Inverting scopes: {:?}", self.scopes);
+
+        let mut start = 0;
+        let mut ranges: Ranges<usize> = self
+            .scopes
+            .0
+            .iter()
+            .filter_map(|ROScope(scope)| {
+                let (s, was_in) = match scope {
+                    In(s, ..) => (s, true),
+                    Out(s) => (s, false),
+                };
+                let range = start..start + s.len();
+                start = range.end;
+                (!was_in).then_some(range)
+            })
+            .collect();
+        ranges.merge();
+
+        self.scopes = ROScopes::from_raw_ranges(self.viewee, ranges.into());
+        trace!("Inverted scopes: {:?}", self.scopes);
+
+        self
+    }
+
+    /// Narrows the view under construction down to the given `ranges`, which refer to
+    /// positions in the *entire* input (unlike scopers passed to [`Self::explode`],
+    /// which only ever see individual scopes).
+    ///
+    /// Parts of [`In`] scopes not covered by any of `ranges` are [`Out`] of scope
+    /// afterwards. Parts remaining [`In`] scope keep their [`ScopeContext`].
+    ///
+    /// ## Panics
+    ///
+    /// Panics if any of `ranges` does not lie on [`char`] boundaries of the input.
+    pub fn restrict(&mut self, ranges: &Ranges<usize>) -> &mut Self {
+        trace!(
+            "Restricting scopes {:?} to ranges: {:?}",
+            self.scopes,
+            ranges
+        );
+
+        let mut ranges = ranges.clone();
+        ranges.merge();
+
+        let mut new = Vec::with_capacity(self.scopes.0.len());
+        let mut start = 0;
+        for ROScope(scope) in self.scopes.0.drain(..) {
+            match scope {
+                In(s, ctx) => {
+                    let end = start + s.len();
+                    let local: RangesWithContext<'_> = ranges
+                        .iter()
+                        .filter(|range| range.start < end && range.end > start)
+                        .map(|range| {
+                            let range = range.start.max(start) - start..range.end.min(end) - start;
+                            (range, ctx.clone())
+                        })
+                        .collect();
+                    new.extend(ROScopes::from_raw_ranges(s, local).0);
+                    start = end;
+                }
+                Out(s) => {
+                    start += s.len();
+                    new.push(ROScope(Out(s)));
+                }
+            }
+        }
+
+        self.scopes.0 = new;
+        trace!("Restricted scopes: {:?}", self.scopes);
+
+        self
+    }
+
+    /// Grows each [`In`] scope of the view under construction to cover the full lines
+    /// it touches, including their line endings.
+    ///
+    /// Useful for actions only making sense on entire lines, like deletion. Scopes
+    /// ending up overlapping are merged into one, keeping the [`ScopeContext`] of the
+    /// first.
+    pub fn expand_to_lines(&mut self) -> &mut Self {
+        let viewee = self.viewee;
+
+        self.expand(|range| {
+            let start = viewee[..range.start].rfind('\n').map_or(0, |i| i + 1);
+            let end = if viewee[..range.end].ends_with('\n') {
+                range.end
+            } else {
+                viewee[range.end..]
+                    .find('\n')
+                    .map_or(viewee.len(), |i| range.end + i + 1)
+            };
+
+            start..end
+        })
+    }
+
+    /// Grows each [`In`] scope of the view under construction to the closest enclosing
+    /// syntax node of the given `kind` (like `function_definition`), as parsed by
+    /// `lang`. Scopes not enclosed by any such node stay as they are.
+    ///
+    /// Useful for actions only making sense on entire nodes, like deleting a function
+    /// based on a comment in it. Scopes ending up overlapping are merged into one,
+    /// keeping the [`ScopeContext`] of the first.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use srgn::scoping::regex::Regex;
+    /// use srgn::scoping::view::ScopedViewBuilder;
+    ///
+    /// let input = "def foo():\n    return 1  # TODO\n\ndef bar():\n    return 2\n";
+    /// let python = tree_sitter_python::LANGUAGE.into();
+    ///
+    /// let mut builder = ScopedViewBuilder::new(input);
+    /// builder.explode(&Regex::try_from(String::from("TODO")).unwrap());
+    /// builder.expand_to_ancestor(&python, "function_definition");
+    ///
+    /// let mut view = builder.build();
+    /// view.delete();
+    ///
+    /// assert_eq!(view.to_string(), "\n\ndef bar():\n    return 2\n");
+    /// ```
+    pub fn expand_to_ancestor(&mut self, lang: &TSLanguage, kind: &str) -> &mut Self {
+        let tree = langs::parse(lang, self.viewee);
+        let root = tree.root_node();
+
+        self.expand(|range| {
+            let mut node = root.descendant_for_byte_range(range.start, range.end);
+            while let Some(candidate) = node {
+                if candidate.kind() == kind {
+                    return candidate.byte_range();
+                }
+                node = candidate.parent();
+            }
+
+            range
+        })
+    }
+
+    /// Replaces each [`In`] scope by whatever `grow` returns for its range in the entire
+    /// input, merging overlapping results.
+    fn expand(&mut self, grow: impl Fn(Range<usize>) -> Range<usize>) -> &mut Self {
+        trace!("Expanding scopes: {:?}", self.scopes);
+
+        let mut start = 0;
+        let mut ranges: RangesWithContext<'viewee> = Vec::new();
+        for ROScope(scope) in self.scopes.0.drain(..) {
+            match scope {
+                In(s, ctx) => {
+                    ranges.push((grow(start..start + s.len()), ctx));
+                    start += s.len();
+                }
+                Out(s) => start += s.len(),
+            }
+        }
+
+        self.scopes = ROScopes::try_from_raw_ranges(self.viewee, ranges, Overlap::Merge)
+            .expect("merging overlaps is infallible");
+        trace!("Expanded scopes: {:?}", self.scopes);
+
+        self
+    }
+
+    /// Keeps only the first `n` [`In`] scopes of the view under construction; all
+    /// later ones are [`Out`] of scope afterwards.
+    ///
+    /// Allows acting on only the first `n` occurrences of something.
+    pub fn take(&mut self, n: usize) -> &mut Self {
+        trace!("Taking first {} scopes of: {:?}", n, self.scopes);
+
+        let mut seen = 0;
+        for ROScope(scope) in &mut self.scopes.0 {
+            if let In(s, ..) = scope {
+                if seen < n {
+                    seen += 1;
+                } else {
+                    *scope = Out(s);
+                }
+            }
+        }
+
+        trace!("Took scopes: {:?}", self.scopes);
+
         self
     }
 }
 
+/// Introspection.
+impl ScopedViewBuilder<'_> {
+    /// Iterate over all [`In`] scope items of the view under construction, along with
+    /// where they are located in the original input.
+    pub fn located(&self) -> impl Iterator<Item = Located<'_>> {
+        locate(self.scopes.0.iter().map(|ROScope(scope)| match scope {
+            In(s, ctx) => (*s, true, ctx.as_ref()),
+            Out(s) => (*s, false, None),
+        }))
+    }
+}
+
 impl<'viewee> IntoIterator for ScopedViewBuilder<'viewee> {
     type Item = ROScope<'viewee>;
 
@@ -406,13 +1002,18 @@ impl<'viewee> IntoIterator for ScopedViewBuilder<'viewee> {
 
 #[cfg(test)]
 mod tests {
+    use std::ops::Range;
+
     use itertools::Itertools;
     use rstest::rstest;
 
-    use super::ScopedView;
-    use crate::scoping::scope::RWScopes;
+    use super::{Located, MappedScope, Position, ScopedView};
+    use crate::actions::{Action, ActionError};
+    use crate::newlines::LineEnding;
     use crate::scoping::scope::Scope::{self, In, Out};
+    use crate::scoping::scope::{Overlap, RWScopes, RangesWithContext};
     use crate::scoping::view::ScopedViewBuilder;
+    use crate::scoping::Scoper;
     use crate::RegexPattern;
 
     #[rstest]
@@ -530,6 +1131,425 @@ mod tests {
         let result = view.to_string();
 
         assert_eq!(result, expected);
+        assert_eq!(view.changed(), result != input);
+    }
+
+    #[rstest]
+    #[case("", "a", vec![])]
+    #[case("a", "a", vec![Out("a")])]
+    #[case("b", "a", vec![In("b", None)])]
+    #[case("bab", "a", vec![In("b", None), Out("a"), In("b", None)])]
+    #[case("aba", "a", vec![Out("a"), In("b", None), Out("a")])]
+    #[case("aab", "a", vec![Out("aa"), In("b", None)])]
+    fn test_invert(
+        #[case] input: &str,
+        #[case] pattern: RegexPattern,
+        #[case] expected: Vec<Scope<'_, &str>>,
+    ) {
+        let mut builder = ScopedViewBuilder::new(input);
+        builder.explode(&crate::scoping::regex::Regex::new(pattern));
+        builder.invert();
+
+        assert_eq!(builder.build(), ScopedView::new(RWScopes::from(expected)));
+    }
+
+    #[test]
+    fn test_invert_merges_consecutive_scopes() {
+        let mut builder = ScopedViewBuilder::new("abc");
+        builder.explode(&crate::scoping::regex::Regex::new(
+            RegexPattern::new("b").unwrap(),
+        ));
+        builder.invert(); // `a`, `c` in scope
+        builder.invert(); // `b` in scope again
+        builder.explode(&crate::scoping::regex::Regex::new(
+            RegexPattern::new("x").unwrap(),
+        ));
+        builder.invert(); // Everything in scope, as a single scope
+
+        assert_eq!(
+            builder.build(),
+            ScopedView::new(RWScopes::from(vec![In("abc", None)]))
+        );
+    }
+
+    #[rstest]
+    #[case("", vec![0..1], vec![])]
+    #[case("abc", vec![], vec![Out("abc")])]
+    #[case("abc", vec![0..3], vec![In("abc", None)])]
+    #[case("abc", vec![1..2], vec![Out("a"), In("b", None), Out("c")])]
+    #[case("abc", vec![2..3, 0..1], vec![In("a", None), Out("b"), In("c", None)])]
+    #[case("abc", vec![0..2, 1..3], vec![In("abc", None)])]
+    fn test_restrict(
+        #[case] input: &str,
+        #[case] ranges: Vec<Range<usize>>,
+        #[case] expected: Vec<Scope<'_, &str>>,
+    ) {
+        let mut builder = ScopedViewBuilder::new(input);
+        builder.restrict(&ranges.into_iter().collect());
+
+        assert_eq!(builder.build(), ScopedView::new(RWScopes::from(expected)));
+    }
+
+    #[rstest]
+    #[case(Overlap::FirstWins, Some(vec![In("ab", None), Out("cde")]))]
+    #[case(Overlap::Merge, Some(vec![In("abcd", None), Out("e")]))]
+    #[case(Overlap::Error, None)]
+    fn test_try_explode(#[case] overlap: Overlap, #[case] expected: Option<Vec<Scope<'_, &str>>>) {
+        struct Nested;
+
+        impl Scoper for Nested {
+            fn scope_raw<'viewee>(&self, _input: &'viewee str) -> RangesWithContext<'viewee> {
+                vec![(0..2, None), (1..4, None)]
+            }
+        }
+
+        let mut builder = ScopedViewBuilder::new("abcde");
+        let res = builder.try_explode(&Nested, overlap).map(|_| ());
+
+        if let Some(expected) = expected {
+            assert!(res.is_ok());
+            assert_eq!(builder.build(), ScopedView::new(RWScopes::from(expected)));
+        } else {
+            assert!(res.is_err());
+            // Left untouched
+            assert_eq!(builder, ScopedViewBuilder::new("abcde"));
+        }
+    }
+
+    #[test]
+    fn test_restrict_keeps_out_of_scope() {
+        let mut builder = ScopedViewBuilder::new("abcd");
+        builder.explode(&crate::scoping::literal::Literal::try_from("bc".to_owned()).unwrap());
+        builder.restrict(&std::iter::once(2..4).collect());
+
+        assert_eq!(
+            builder.build(),
+            ScopedView::new(RWScopes::from(vec![
+                Out("a"),
+                Out("b"),
+                In("c", None),
+                Out("d")
+            ]))
+        );
+    }
+
+    #[rstest]
+    #[case("aaa", 0, vec![Out("a"), Out("a"), Out("a")])]
+    #[case("aaa", 1, vec![In("a", None), Out("a"), Out("a")])]
+    #[case("aaa", 2, vec![In("a", None), In("a", None), Out("a")])]
+    #[case("aaa", 3, vec![In("a", None), In("a", None), In("a", None)])]
+    #[case("aaa", 4, vec![In("a", None), In("a", None), In("a", None)])]
+    #[case("bab", 1, vec![Out("b"), In("a", None), Out("b")])]
+    fn test_take(#[case] input: &str, #[case] n: usize, #[case] expected: Vec<Scope<'_, &str>>) {
+        let mut builder = ScopedViewBuilder::new(input);
+        builder.explode(&crate::scoping::literal::Literal::try_from("a".to_owned()).unwrap());
+        builder.take(n);
+
+        assert_eq!(builder.build(), ScopedView::new(RWScopes::from(expected)));
+    }
+
+    #[rstest]
+    #[case("", "a", vec![])]
+    #[case("a", "a", vec![In("a", None)])]
+    #[case("xax", "a", vec![In("xax", None)])]
+    #[case("xa\ny", "a", vec![In("xa\n", None), Out("y")])]
+    #[case("x\nya\nz", "a", vec![Out("x\n"), In("ya\n", None), Out("z")])]
+    #[case("x\r\nya\r\nz", "a", vec![Out("x\r\n"), In("ya\r\n", None), Out("z")])]
+    #[case("x\n", "\n", vec![In("x\n", None)])] // Already ends at line end
+    #[case("x\ny", "x\ny", vec![In("x\ny", None)])] // Spans lines
+    #[case("aa\nb\na", "a", vec![In("aa\n", None), Out("b\n"), In("a", None)])] // Merged
+    #[case("a\na", "a", vec![In("a\n", None), In("a", None)])] // Bordering, not merged
+    fn test_expand_to_lines(
+        #[case] input: &str,
+        #[case] pattern: &str,
+        #[case] expected: Vec<Scope<'_, &str>>,
+    ) {
+        let mut builder = ScopedViewBuilder::new(input);
+        builder.explode(&crate::scoping::literal::Literal::try_from(pattern.to_owned()).unwrap());
+        builder.expand_to_lines();
+
+        assert_eq!(builder.build(), ScopedView::new(RWScopes::from(expected)));
+    }
+
+    #[rstest]
+    #[case("x = 1", "1", "function_definition", vec![Out("x = "), In("1", None)])] // No such ancestor
+    #[case(
+        "def f():\n    return 1\n",
+        "1",
+        "return_statement",
+        vec![Out("def f():\n    "), In("return 1", None), Out("\n")]
+    )]
+    #[case(
+        "def f():\n    return 1\n",
+        "1",
+        "function_definition",
+        vec![In("def f():\n    return 1", None), Out("\n")]
+    )]
+    #[case(
+        "def f():\n    return 1\n",
+        "return", // Node itself qualifies
+        "return_statement",
+        vec![Out("def f():\n    "), In("return 1", None), Out("\n")]
+    )]
+    #[case(
+        "def f():\n    return 1 + 1\n",
+        "1",
+        "function_definition",
+        vec![In("def f():\n    return 1 + 1", None), Out("\n")] // Merged
+    )]
+    fn test_expand_to_ancestor(
+        #[case] input: &str,
+        #[case] pattern: &str,
+        #[case] kind: &str,
+        #[case] expected: Vec<Scope<'_, &str>>,
+    ) {
+        let mut builder = ScopedViewBuilder::new(input);
+        builder.explode(&crate::scoping::literal::Literal::try_from(pattern.to_owned()).unwrap());
+        builder.expand_to_ancestor(&tree_sitter_python::LANGUAGE.into(), kind);
+
+        assert_eq!(builder.build(), ScopedView::new(RWScopes::from(expected)));
+    }
+
+    type LineColumn = (usize, usize);
+
+    #[rstest]
+    #[case("", "a", vec![])]
+    #[case("a", "a", vec![(0..1, (1, 1), (1, 2), "a")])]
+    #[case("bab", "a", vec![(1..2, (1, 2), (1, 3), "a")])]
+    #[case("a\nba", "a", vec![(0..1, (1, 1), (1, 2), "a"), (3..4, (2, 2), (2, 3), "a")])]
+    #[case("x\na\n", "a\n", vec![(2..4, (2, 1), (3, 1), "a\n")])]
+    #[case("x\r\nab\nc", "b\nc", vec![(4..7, (2, 2), (3, 2), "b\nc")])]
+    #[case("äa", "a", vec![(2..3, (1, 3), (1, 4), "a")])] // Columns count bytes
+    fn test_located(
+        #[case] input: &str,
+        #[case] pattern: &str,
+        #[case] expected: Vec<(Range<usize>, LineColumn, LineColumn, &str)>,
+    ) {
+        let mut builder = ScopedViewBuilder::new(input);
+        builder.explode(&crate::scoping::literal::Literal::try_from(pattern.to_owned()).unwrap());
+
+        let pos = |(line, column)| Position { line, column };
+        let expected = expected
+            .into_iter()
+            .map(|(range, start, end, text)| Located {
+                range,
+                start: pos(start),
+                end: pos(end),
+                text,
+                context: None,
+            })
+            .collect_vec();
+
+        assert_eq!(builder.located().collect_vec(), expected);
+        assert_eq!(builder.build().located().collect_vec(), expected);
+    }
+
+    #[test]
+    fn test_located_after_mapping() {
+        let mut builder = ScopedViewBuilder::new("a\nb a");
+        builder.explode(&crate::scoping::literal::Literal::try_from("a".to_owned()).unwrap());
+        let mut view = builder.build();
+        view.replace("xyz\n".to_owned()).unwrap();
+
+        // Locations refer to the mapped contents
+        assert_eq!(
+            view.located()
+                .map(|l| (l.range, l.start.line))
+                .collect_vec(),
+            [(0..4, 1), (7..11, 3)]
+        );
+    }
+
+    #[test]
+    fn test_source_map() {
+        let mut builder = ScopedViewBuilder::new("a bb a");
+        builder.explode(&crate::scoping::literal::Literal::try_from("a".to_owned()).unwrap());
+        let original = builder.build();
+        let mut view = original.clone();
+        view.replace("xyz".to_owned()).unwrap();
+        assert_eq!(view.to_string(), "xyz bb xyz");
+
+        let map = view.source_map(&original);
+        assert_eq!(
+            map.scopes(),
+            [
+                MappedScope {
+                    original: 0..1,
+                    transformed: 0..3
+                },
+                MappedScope {
+                    original: 5..6,
+                    transformed: 7..10
+                },
+            ]
+        );
+
+        // Original -> transformed: within items, between them, and past the end
+        assert_eq!(
+            (0..=6).map(|i| map.to_transformed(i)).collect_vec(),
+            [0, 3, 4, 5, 6, 7, 10]
+        );
+        // Transformed -> original: offsets within items beyond their counterpart clamp
+        assert_eq!(
+            (0..=10).map(|i| map.to_original(i)).collect_vec(),
+            [0, 1, 1, 1, 2, 3, 4, 5, 6, 6, 6]
+        );
+    }
+
+    #[test]
+    fn test_source_map_unchanged() {
+        let mut builder = ScopedViewBuilder::new("a b");
+        builder.explode(&crate::scoping::literal::Literal::try_from("b".to_owned()).unwrap());
+        let view = builder.build();
+
+        let map = view.source_map(&view);
+        assert_eq!(
+            (0..=3).map(|i| map.to_transformed(i)).collect_vec(),
+            [0, 1, 2, 3]
+        );
+        assert_eq!(
+            (0..=3).map(|i| map.to_original(i)).collect_vec(),
+            [0, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "zip_eq")]
+    fn test_source_map_of_other_view() {
+        let mut builder = ScopedViewBuilder::new("a b");
+        builder.explode(&crate::scoping::literal::Literal::try_from("b".to_owned()).unwrap());
+
+        drop(
+            builder
+                .build()
+                .source_map(&ScopedViewBuilder::new("a b").build()),
+        );
+    }
+
+    /// Uppercases, but fails on inputs containing `x`.
+    struct FallibleUpper;
+
+    impl Action for FallibleUpper {
+        fn act(&self, input: &str) -> String {
+            self.try_act(input).unwrap()
+        }
+
+        fn try_act(&self, input: &str) -> Result<String, ActionError> {
+            if input.contains('x') {
+                return Err(ActionError::Failed(format!("cannot handle '{input}'")));
+            }
+
+            Ok(input.to_uppercase())
+        }
+    }
+
+    #[rstest]
+    #[case("a b", Ok("A B"))]
+    #[case("a x", Err(ActionError::Failed("cannot handle 'x'".to_owned())))]
+    fn test_map_fallible(#[case] input: &str, #[case] expected: Result<&str, ActionError>) {
+        let regex = crate::scoping::regex::Regex::try_from(r"\w".to_owned()).unwrap();
+
+        let mut builder = ScopedViewBuilder::new(input);
+        builder.explode(&regex);
+        let view = builder.build();
+
+        let with_context = view
+            .clone()
+            .map_with_context(&FallibleUpper)
+            .map(|v| v.to_string());
+        let without_context = view
+            .clone()
+            .try_map_without_context(&FallibleUpper)
+            .map(|v| v.to_string());
+
+        let expected = expected.map(str::to_owned);
+        assert_eq!(with_context, expected);
+        assert_eq!(without_context, expected);
+
+        #[cfg(feature = "parallel")]
+        assert_eq!(
+            view.clone().map_par(&FallibleUpper).map(|v| v.to_string()),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("a\nb", "b", "x\ny", LineEnding::CrLf, "a\nx\r\ny")] // Introduced
+    #[case("a\r\nb", "b", "x\r\ny", LineEnding::Lf, "a\r\nx\ny")] // Introduced
+    #[case("a\r\nb\n", "(?s).+", "x\ny", LineEnding::Lf, "x\ny")]
+    #[case("a\r\nb\n", "(?s).+", "x\r\ny", LineEnding::Lf, "x\r\ny")] // Mixed before
+    #[case("a\r\nb\n", "b", "x\ny", LineEnding::CrLf, "a\r\nx\r\ny\n")] // Out of scope
+    #[case("a\r\nb", "a", "$0", LineEnding::Lf, "a\r\nb")] // Unchanged
+    fn test_convert_line_endings(
+        #[case] input: &str,
+        #[case] pattern: &str,
+        #[case] replacement: &str,
+        #[case] ending: LineEnding,
+        #[case] expected: &str,
+    ) {
+        let mut builder = ScopedViewBuilder::new(input);
+        builder.explode(&crate::scoping::regex::Regex::try_from(pattern.to_owned()).unwrap());
+        let mut view = builder.build();
+        let before = view.clone();
+
+        let action = crate::actions::Replacement::try_from(replacement.to_owned()).unwrap();
+        view.map_with_context(&action).unwrap();
+        view.convert_line_endings(ending, &before);
+
+        assert_eq!(view.to_string(), expected);
+    }
+
+    #[rstest]
+    #[case("abc", "b", |v: &mut ScopedView<'_>| { v.lower(); }, false)]
+    #[case("abc", "b", |v: &mut ScopedView<'_>| { v.upper(); }, true)]
+    #[case("abc", "x", |v: &mut ScopedView<'_>| { v.upper(); }, false)]
+    #[case("ABC", ".", |v: &mut ScopedView<'_>| { v.upper(); }, false)]
+    #[case("aBc", ".", |v: &mut ScopedView<'_>| { v.upper(); }, true)]
+    #[case("a\n", "\n", |v: &mut ScopedView<'_>| { v.convert_line_endings(LineEnding::Lf, &v.clone()); }, false)]
+    #[case("a\n", "\n", |v: &mut ScopedView<'_>| { v.convert_line_endings(LineEnding::CrLf, &v.clone()); }, false)] // Not from actions
+    #[case("a\n", "a", |v: &mut ScopedView<'_>| { let before = v.clone(); v.replace("\n".into()).unwrap().convert_line_endings(LineEnding::CrLf, &before); }, true)]
+    #[case("abc", "b", |v: &mut ScopedView<'_>| { v.upper().lower(); }, true)] // Cautious
+    fn test_changed(
+        #[case] input: &str,
+        #[case] pattern: &str,
+        #[case] manipulate: fn(&mut ScopedView<'_>),
+        #[case] expected: bool,
+    ) {
+        let mut builder = ScopedViewBuilder::new(input);
+        builder.explode(&crate::scoping::regex::Regex::try_from(pattern.to_owned()).unwrap());
+        let mut view = builder.build();
+        assert!(!view.changed());
+
+        manipulate(&mut view);
+
+        assert_eq!(view.changed(), expected);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_map_par_preserves_order() {
+        let input = (0..10_000).map(|i| format!("a{i}")).join(" ");
+        let regex = crate::scoping::regex::Regex::try_from(r"\w+".to_owned()).unwrap();
+
+        let mut builder = ScopedViewBuilder::new(&input);
+        builder.explode(&regex);
+        let view = builder.build();
+
+        let mut sequential = view.clone();
+        sequential.map_with_context(&FallibleUpper).unwrap();
+        let mut parallel = view;
+        parallel.map_par(&FallibleUpper).unwrap();
+
+        assert_eq!(parallel, sequential);
+        assert_eq!(parallel.to_string(), input.to_uppercase());
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot handle 'x'")]
+    fn test_map_without_context_panics_on_failure() {
+        ScopedViewBuilder::new("x")
+            .build()
+            .map_without_context(&FallibleUpper);
     }
 
     #[rstest]
@@ -691,4 +1711,17 @@ mod tests {
             assert_eq!(si, *exp);
         }
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize_located() {
+        let mut builder = ScopedViewBuilder::new("a\nxb");
+        builder.explode(&crate::scoping::regex::Regex::try_from("xb".to_owned()).unwrap());
+        let located = builder.located().collect::<Vec<_>>();
+
+        assert_eq!(
+            serde_json::to_string(&located).unwrap(),
+            r#"[{"range":{"start":2,"end":4},"start":{"line":2,"column":1},"end":{"line":2,"column":3},"text":"xb","context":{"CaptureGroups":{"0":"xb"}}}]"#
+        );
+    }
 }
@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use log::{debug, trace};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::scoping::view::{Located, ScopedViewBuilder};
+use crate::scoping::Scoper;
+use crate::spec::{ActionSpec, ApplyError, ScopeSpec};
+
+/// How many compiled scopers to keep around at most, before starting afresh.
+const MAX_CACHED_SCOPERS: usize = 256;
+
+/// Error codes of the JSON-RPC 2.0 specification.
+pub(crate) mod codes {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    /// Start of the range reserved for implementation-defined server errors.
+    pub const SERVER_ERROR: i64 = -32000;
+}
+
+/// A server answering [JSON-RPC 2.0](https://www.jsonrpc.org/specification) requests
+/// to scope and act, one message per line.
+///
+/// Scopes are described as in [`try_apply`][crate::spec::try_apply]. Scopers compiled
+/// for them (regexes, tree-sitter queries, ...) are kept around, so that repeated
+/// requests only pay for the actual work. This makes it viable for editor plugins and
+/// build tools to call into it many times over, which would otherwise spend much of
+/// their time starting up.
+///
+/// Supported methods are:
+///
+/// - `apply`, with params `{"input": "...", "scopes": [...], "actions": [...]}`,
+///   resulting in `{"output": "...", "changed": true}`.
+/// - `scope`, with params `{"input": "...", "scopes": [...]}`, resulting in the
+///   [`Located`] items in scope.
+///
+/// Batches of requests are answered in one go, notifications (requests without an
+/// `id`) not at all.
+///
+/// ## Example
+///
+/// ```rust
+/// use srgn::server::Server;
+///
+/// let requests = concat!(
+///     r#"{"jsonrpc": "2.0", "id": 1, "method": "apply", "params": "#,
+///     r#"{"input": "x = 1  # fix me", "scopes": [{"python": "comments"}], "actions": ["upper"]}}"#,
+///     "\n",
+/// );
+///
+/// let mut output = Vec::new();
+/// Server::default().serve(requests.as_bytes(), &mut output).unwrap();
+///
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     r##"{"id":1,"jsonrpc":"2.0","result":{"changed":true,"output":"x = 1  # FIX ME"}}"##.to_owned() + "\n"
+/// );
+/// ```
+#[derive(Default)]
+pub struct Server {
+    /// Compiled scopers, by the JSON of the scope they were compiled from.
+    scopers: HashMap<String, Box<dyn Scoper>>,
+}
+
+impl fmt::Debug for Server {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("scopers", &self.scopers.keys())
+            .finish()
+    }
+}
+
+/// Params of the `apply` method.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ApplyParams {
+    input: String,
+    #[serde(default)]
+    scopes: Vec<Value>,
+    #[serde(default)]
+    actions: Vec<ActionSpec>,
+}
+
+/// Params of the `scope` method.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScopeParams {
+    input: String,
+    #[serde(default)]
+    scopes: Vec<Value>,
+}
+
+/// An error answering a single request.
+#[derive(Debug)]
+pub(crate) enum CallError {
+    /// The method does not exist.
+    MethodNotFound(String),
+    /// The params are not of the expected shape.
+    InvalidParams(serde_json::Error),
+    /// Scoping or acting failed.
+    Apply(ApplyError),
+}
+
+impl CallError {
+    pub(crate) const fn code(&self) -> i64 {
+        match self {
+            Self::MethodNotFound(_) => codes::METHOD_NOT_FOUND,
+            Self::InvalidParams(_) | Self::Apply(ApplyError::Spec(_)) => codes::INVALID_PARAMS,
+            Self::Apply(_) => codes::SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MethodNotFound(method) => write!(f, "Method not found: {method}"),
+            Self::InvalidParams(e) => write!(f, "Invalid params: {e}"),
+            Self::Apply(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for CallError {}
+
+impl From<ApplyError> for CallError {
+    fn from(err: ApplyError) -> Self {
+        Self::Apply(err)
+    }
+}
+
+impl Server {
+    /// Answers all messages read from `reader` by writing responses to `writer`,
+    /// until `reader` is exhausted.
+    ///
+    /// # Errors
+    ///
+    /// Errors if reading or writing fails. Invalid messages are answered with an
+    /// error response instead.
+    pub fn serve(&mut self, reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            if let Some(response) = self.handle(&line) {
+                serde_json::to_writer(&mut writer, &response)?;
+                writeln!(writer)?;
+                // Clients wait for each response before sending more.
+                writer.flush()?;
+            }
+        }
+
+        debug!("Input exhausted, shutting down server.");
+        Ok(())
+    }
+
+    /// Answers a single `message`, a request or a batch of them. Returns [`None`] if
+    /// no response is due, as for notifications.
+    pub fn handle(&mut self, message: &str) -> Option<Value> {
+        trace!("Handling message: {message}");
+
+        match serde_json::from_str(message) {
+            Ok(Value::Array(requests)) if requests.is_empty() => Some(error_response(
+                &Value::Null,
+                codes::INVALID_REQUEST,
+                "Empty batch",
+            )),
+            Ok(Value::Array(requests)) => {
+                let responses: Vec<_> = requests
+                    .into_iter()
+                    .filter_map(|request| self.handle_request(request))
+                    .collect();
+
+                (!responses.is_empty()).then_some(Value::Array(responses))
+            }
+            Ok(request) => self.handle_request(request),
+            Err(e) => Some(error_response(
+                &Value::Null,
+                codes::PARSE_ERROR,
+                &e.to_string(),
+            )),
+        }
+    }
+
+    fn handle_request(&mut self, request: Value) -> Option<Value> {
+        let Value::Object(mut request) = request else {
+            return Some(error_response(
+                &Value::Null,
+                codes::INVALID_REQUEST,
+                "Request is not an object",
+            ));
+        };
+
+        let id = request.remove("id");
+        let method = match request.remove("method") {
+            Some(Value::String(method)) if request.get("jsonrpc") == Some(&json!("2.0")) => method,
+            _ => {
+                return Some(error_response(
+                    &id.unwrap_or_default(),
+                    codes::INVALID_REQUEST,
+                    "Request lacks a method or `\"jsonrpc\": \"2.0\"`",
+                ))
+            }
+        };
+        let params = request.remove("params").unwrap_or_default();
+
+        debug!("Calling method '{method}'.");
+        let result = self.call(&method, params);
+
+        // Notifications are never answered, not even on errors.
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(e) => error_response(&id, e.code(), &e.to_string()),
+        })
+    }
+
+    fn call(&mut self, method: &str, params: Value) -> Result<Value, CallError> {
+        match method {
+            "apply" => {
+                let params: ApplyParams =
+                    serde_json::from_value(params).map_err(CallError::InvalidParams)?;
+
+                let mut builder = ScopedViewBuilder::new(&params.input);
+                for scoper in self.scopers(params.scopes)? {
+                    builder.explode(&scoper);
+                }
+
+                let mut view = builder.build();
+                for action in params.actions {
+                    action.apply(&mut view)?;
+                }
+
+                Ok(json!({"output": view.to_string(), "changed": view.changed()}))
+            }
+            "scope" => {
+                let params: ScopeParams =
+                    serde_json::from_value(params).map_err(CallError::InvalidParams)?;
+
+                let mut builder = ScopedViewBuilder::new(&params.input);
+                for scoper in self.scopers(params.scopes)? {
+                    builder.explode(&scoper);
+                }
+
+                let located: Vec<Located<'_>> = builder.located().collect();
+                Ok(serde_json::to_value(located).expect("located items to serialize"))
+            }
+            _ => Err(CallError::MethodNotFound(method.to_owned())),
+        }
+    }
+
+    /// Scopers for the given `scopes`, compiled anew only if not seen before.
+    fn scopers(&mut self, scopes: Vec<Value>) -> Result<Vec<&dyn Scoper>, ApplyError> {
+        let keys: Vec<String> = scopes.iter().map(Value::to_string).collect();
+
+        if self.scopers.len() + keys.len() > MAX_CACHED_SCOPERS {
+            debug!("Scoper cache full, clearing.");
+            self.scopers.clear();
+        }
+
+        for (key, scope) in keys.iter().zip(scopes) {
+            if !self.scopers.contains_key(key) {
+                trace!("Compiling scoper for {key}.");
+                let compiled = ScopeSpec::deserialize(scope)?.into_scoper()?;
+                self.scopers.insert(key.clone(), compiled);
+            }
+        }
+
+        Ok(keys.iter().map(|key| self.scopers[key].as_ref()).collect())
+    }
+}
+
+pub(crate) fn error_response(id: &Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "apply", "params": {"input": "a b", "scopes": [{"regex": "b"}], "actions": ["upper"]}}"#,
+        Some(json!({"jsonrpc": "2.0", "id": 1, "result": {"output": "a B", "changed": true}}))
+    )]
+    #[case(
+        r#"{"jsonrpc": "2.0", "id": "x", "method": "apply", "params": {"input": "a b"}}"#,
+        Some(json!({"jsonrpc": "2.0", "id": "x", "result": {"output": "a b", "changed": false}}))
+    )]
+    #[case(
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "scope", "params": {"input": "a\nbb", "scopes": [{"literal": "b"}]}}"#,
+        Some(json!({"jsonrpc": "2.0", "id": 1, "result": [
+            {"range": {"start": 2, "end": 3}, "start": {"line": 2, "column": 1}, "end": {"line": 2, "column": 2}, "text": "b", "context": null},
+            {"range": {"start": 3, "end": 4}, "start": {"line": 2, "column": 2}, "end": {"line": 2, "column": 3}, "text": "b", "context": null},
+        ]}))
+    )]
+    #[case(
+        r#"[
+            {"jsonrpc": "2.0", "id": 1, "method": "apply", "params": {"input": "a", "actions": ["upper"]}},
+            {"jsonrpc": "2.0", "method": "apply", "params": {"input": "b", "actions": ["upper"]}},
+            {"jsonrpc": "2.0", "id": 2, "method": "apply", "params": {"input": "c", "actions": ["delete"]}}
+        ]"#,
+        Some(json!([
+            {"jsonrpc": "2.0", "id": 1, "result": {"output": "A", "changed": true}},
+            {"jsonrpc": "2.0", "id": 2, "result": {"output": "", "changed": true}},
+        ]))
+    )]
+    #[case(
+        r#"{"jsonrpc": "2.0", "method": "apply", "params": {"input": "a"}}"#,
+        None // Notification
+    )]
+    #[case(
+        r#"[{"jsonrpc": "2.0", "method": "nope"}]"#,
+        None // Only notifications
+    )]
+    fn test_server_handle(#[case] message: &str, #[case] expected: Option<Value>) {
+        assert_eq!(Server::default().handle(message), expected);
+    }
+
+    #[rstest]
+    #[case("{", codes::PARSE_ERROR)]
+    #[case("[]", codes::INVALID_REQUEST)]
+    #[case("1", codes::INVALID_REQUEST)]
+    #[case(r#"{"id": 1, "method": "apply"}"#, codes::INVALID_REQUEST)] // No version
+    #[case(r#"{"jsonrpc": "2.0", "id": 1}"#, codes::INVALID_REQUEST)]
+    #[case(
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "nope"}"#,
+        codes::METHOD_NOT_FOUND
+    )]
+    #[case(
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "apply"}"#,
+        codes::INVALID_PARAMS
+    )]
+    #[case(
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "apply", "params": {"input": "a", "actions": ["shout"]}}"#,
+        codes::INVALID_PARAMS
+    )]
+    #[case(
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "scope", "params": {"input": "a", "scopes": [{"cobol": "comments"}]}}"#,
+        codes::INVALID_PARAMS
+    )]
+    #[case(
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "scope", "params": {"input": "a", "scopes": [{"regex": "("}]}}"#,
+        codes::SERVER_ERROR
+    )]
+    fn test_server_handle_errors(#[case] message: &str, #[case] expected: i64) {
+        let response = Server::default().handle(message).unwrap();
+
+        assert_eq!(response["error"]["code"], json!(expected), "{response}");
+    }
+
+    #[test]
+    fn test_server_caches_scopers() {
+        let mut server = Server::default();
+        let request = r#"{"jsonrpc": "2.0", "id": 1, "method": "apply", "params": {"input": "a", "scopes": [{"regex": "a"}, {"python": "comments"}]}}"#;
+
+        server.handle(request);
+        server.handle(request);
+        assert_eq!(server.scopers.len(), 2);
+
+        server.handle(
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "scope", "params": {"input": "a", "scopes": [{"regex": "b"}]}}"#,
+        );
+        assert_eq!(server.scopers.len(), 3);
+    }
+
+    #[test]
+    fn test_server_serve() {
+        let requests = concat!(
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "apply", "params": {"input": "a", "actions": ["upper"]}}"#,
+            "\n\n",
+            r#"{"jsonrpc": "2.0", "method": "apply", "params": {"input": "a"}}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "nope"}"#,
+            "\n",
+        );
+
+        let mut output = Vec::new();
+        Server::default()
+            .serve(requests.as_bytes(), &mut output)
+            .unwrap();
+
+        let responses: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["result"]["output"], json!("A"));
+        assert_eq!(
+            responses[1]["error"]["code"],
+            json!(codes::METHOD_NOT_FOUND)
+        );
+    }
+}
@@ -0,0 +1,340 @@
+use std::error::Error;
+use std::fmt;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use tree_sitter::QueryError as TSQueryError;
+
+use crate::actions::ActionError;
+use crate::ranges::ParseRangeError;
+use crate::scoping::columns::Columns;
+use crate::scoping::indentation::Indentation;
+use crate::scoping::langs::{c, cpp, csharp, go, hcl, python, rust, typescript, QuerySource};
+use crate::scoping::literal::{Literal, LiteralError};
+use crate::scoping::regex::{Regex, RegexError};
+use crate::scoping::view::{ScopedView, ScopedViewBuilder};
+use crate::scoping::Scoper;
+
+/// A scope, as given in a `scope_spec` of [`try_apply`].
+///
+/// Named after the corresponding command line options: `{"regex": "[a-z]+"}`,
+/// `{"python": "comments"}` for a prepared query, `{"python-query": "(comment) @c"}`
+/// for a custom one, and so on.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) enum ScopeSpec {
+    Regex(String),
+    Literal(String),
+    Columns(String),
+    Indentation(String),
+    C(String),
+    CQuery(String),
+    Cpp(String),
+    CppQuery(String),
+    Csharp(String),
+    CsharpQuery(String),
+    Go(String),
+    GoQuery(String),
+    Hcl(String),
+    HclQuery(String),
+    Python(String),
+    PythonQuery(String),
+    Rust(String),
+    RustQuery(String),
+    Typescript(String),
+    TypescriptQuery(String),
+}
+
+/// An action, as given in an `action_spec` of [`try_apply`].
+///
+/// Named after the corresponding command line options: `"upper"`, `"delete"`,
+/// `{"replace": "x"}` and so on.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) enum ActionSpec {
+    Replace(String),
+    Delete,
+    Squeeze,
+    Upper,
+    Lower,
+    Titlecase,
+    Normalize,
+    #[cfg(feature = "symbols")]
+    Symbols,
+    #[cfg(feature = "german")]
+    German,
+}
+
+/// An error in [`try_apply`].
+#[derive(Debug)]
+pub enum ApplyError {
+    /// A spec is not valid JSON of the expected shape.
+    Spec(serde_json::Error),
+    /// A regular expression pattern is invalid.
+    Regex(Box<RegexError>),
+    /// A literal string is invalid.
+    Literal(LiteralError),
+    /// Column ranges are invalid.
+    Columns(ParseRangeError),
+    /// Indentation depths are invalid.
+    Indentation(ParseRangeError),
+    /// A prepared query of the given name does not exist.
+    PreparedQuery(String),
+    /// A custom query is invalid.
+    Query(TSQueryError),
+    /// An action failed.
+    Action(ActionError),
+}
+
+impl fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Spec(e) => write!(f, "Invalid spec: {e}"),
+            Self::Regex(e) => write!(f, "Invalid regex: {e}"),
+            Self::Literal(e) => write!(f, "Invalid literal: {e}"),
+            Self::Columns(e) => write!(f, "Invalid columns: {e}"),
+            Self::Indentation(e) => write!(f, "Invalid indentation: {e}"),
+            Self::PreparedQuery(e) => write!(f, "Invalid prepared query: {e}"),
+            Self::Query(e) => write!(f, "Invalid query: {e}"),
+            Self::Action(e) => write!(f, "Action failed: {e}"),
+        }
+    }
+}
+
+impl Error for ApplyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Spec(e) => Some(e),
+            Self::Regex(e) => Some(e.as_ref()),
+            Self::Literal(e) => Some(e),
+            Self::Columns(e) | Self::Indentation(e) => Some(e),
+            Self::PreparedQuery(_) => None,
+            Self::Query(e) => Some(e),
+            Self::Action(e) => Some(e),
+        }
+    }
+}
+
+impl From<serde_json::Error> for ApplyError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Spec(err)
+    }
+}
+
+impl From<RegexError> for ApplyError {
+    fn from(err: RegexError) -> Self {
+        Self::Regex(Box::new(err))
+    }
+}
+
+impl From<LiteralError> for ApplyError {
+    fn from(err: LiteralError) -> Self {
+        Self::Literal(err)
+    }
+}
+
+impl From<TSQueryError> for ApplyError {
+    fn from(err: TSQueryError) -> Self {
+        Self::Query(err)
+    }
+}
+
+impl From<ActionError> for ApplyError {
+    fn from(err: ActionError) -> Self {
+        Self::Action(err)
+    }
+}
+
+/// Scopes `input` as per `scope_spec`, applies the actions of `action_spec` in order
+/// and returns the result.
+///
+/// Both specs are JSON arrays. Scopes narrow each other down in order, like on the
+/// command line; no scopes at all means all of `input` is in scope.
+///
+/// ## Example
+///
+/// ```rust
+/// use srgn::spec::try_apply;
+///
+/// let output = try_apply("x = 1  # fix me", r#"[{"python": "comments"}]"#, r#"["upper"]"#);
+///
+/// assert_eq!(output.unwrap(), "x = 1  # FIX ME");
+/// ```
+///
+/// # Errors
+///
+/// See [`ApplyError`].
+pub fn try_apply(input: &str, scope_spec: &str, action_spec: &str) -> Result<String, ApplyError> {
+    let scopes: Vec<ScopeSpec> = serde_json::from_str(scope_spec)?;
+    let actions: Vec<ActionSpec> = serde_json::from_str(action_spec)?;
+
+    let mut builder = ScopedViewBuilder::new(input);
+    for scope in scopes {
+        builder.explode(&scope.into_scoper()?);
+    }
+
+    let mut view = builder.build();
+    for action in actions {
+        action.apply(&mut view)?;
+    }
+
+    Ok(view.to_string())
+}
+
+impl ActionSpec {
+    /// Applies this action to all in-scope items of `view`.
+    pub(crate) fn apply(&self, view: &mut ScopedView<'_>) -> Result<(), ApplyError> {
+        match self {
+            Self::Replace(replacement) => view.replace(replacement.clone())?,
+            Self::Delete => view.delete(),
+            Self::Squeeze => view.squeeze(),
+            Self::Upper => view.upper(),
+            Self::Lower => view.lower(),
+            Self::Titlecase => view.titlecase(),
+            Self::Normalize => view.normalize(),
+            #[cfg(feature = "symbols")]
+            Self::Symbols => view.symbols(),
+            #[cfg(feature = "german")]
+            Self::German => view.german(),
+        };
+
+        Ok(())
+    }
+}
+
+impl ScopeSpec {
+    /// Builds the scoper this spec describes.
+    pub(crate) fn into_scoper(self) -> Result<Box<dyn Scoper>, ApplyError> {
+        /// The prepared query of the given language module, by its name as on the
+        /// command line.
+        macro_rules! prepared {
+            ($lang:ident, $name:expr) => {
+                Box::new($lang::CompiledQuery::from(
+                    $lang::PreparedQuery::from_str(&$name, false)
+                        .map_err(ApplyError::PreparedQuery)?,
+                ))
+            };
+        }
+
+        /// A custom query for the given language module.
+        macro_rules! custom {
+            ($lang:ident, $query:expr) => {
+                Box::new($lang::CompiledQuery::try_from(QuerySource::from($query))?)
+            };
+        }
+
+        let scoper: Box<dyn Scoper> = match self {
+            Self::Regex(pattern) => Box::new(Regex::try_from(pattern)?),
+            Self::Literal(literal) => Box::new(Literal::try_from(literal)?),
+            Self::Columns(columns) => {
+                Box::new(columns.parse::<Columns>().map_err(ApplyError::Columns)?)
+            }
+            Self::Indentation(depths) => Box::new(
+                depths
+                    .parse::<Indentation>()
+                    .map_err(ApplyError::Indentation)?,
+            ),
+            Self::C(name) => prepared!(c, name),
+            Self::CQuery(query) => custom!(c, query),
+            Self::Cpp(name) => prepared!(cpp, name),
+            Self::CppQuery(query) => custom!(cpp, query),
+            Self::Csharp(name) => prepared!(csharp, name),
+            Self::CsharpQuery(query) => custom!(csharp, query),
+            Self::Go(name) => prepared!(go, name),
+            Self::GoQuery(query) => custom!(go, query),
+            Self::Hcl(name) => prepared!(hcl, name),
+            Self::HclQuery(query) => custom!(hcl, query),
+            Self::Python(name) => prepared!(python, name),
+            Self::PythonQuery(query) => custom!(python, query),
+            Self::Rust(name) => prepared!(rust, name),
+            Self::RustQuery(query) => custom!(rust, query),
+            Self::Typescript(name) => prepared!(typescript, name),
+            Self::TypescriptQuery(query) => custom!(typescript, query),
+        };
+
+        Ok(scoper)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("hello world", "[]", r#"["upper"]"#, "HELLO WORLD")]
+    #[case(
+        "hello world",
+        r#"[{"regex": "o"}]"#,
+        r#"[{"replace": "0"}]"#,
+        "hell0 w0rld"
+    )]
+    #[case("a.b", r#"[{"literal": "."}]"#, r#"["delete"]"#, "ab")]
+    #[case("aaa b", r#"[{"regex": "a"}]"#, r#"["squeeze", "upper"]"#, "A b")]
+    #[case("abc\ndef", r#"[{"columns": "2.."}]"#, r#"["upper"]"#, "aBC\ndEF")]
+    #[case("a\n  b\n", r#"[{"indentation": "1.."}]"#, r#"["upper"]"#, "a\n  B\n")]
+    #[case(
+        "x = 'a'  # b c\n",
+        r#"[{"python": "comments"}, {"regex": "\\w+"}]"#,
+        r#"["upper"]"#,
+        "x = 'a'  # B C\n"
+    )]
+    #[case(
+        "x = 'a'  # b\n",
+        r#"[{"python-query": "(string_content) @s"}]"#,
+        r#"["upper"]"#,
+        "x = 'A'  # b\n"
+    )]
+    #[case(
+        "fn f() {} // x",
+        r#"[{"rust": "comments"}]"#,
+        r#"["delete"]"#,
+        "fn f() {} "
+    )]
+    #[case("Hello", "[]", "[]", "Hello")]
+    fn test_try_apply(
+        #[case] input: &str,
+        #[case] scope_spec: &str,
+        #[case] action_spec: &str,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(try_apply(input, scope_spec, action_spec).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("{}", "[]")] // Not an array
+    #[case(r#"[{"regex": "a", "literal": "b"}]"#, "[]")] // Two scopes in one
+    #[case(r#"[{"cobol": "comments"}]"#, "[]")]
+    #[case("[]", r#"["shout"]"#)]
+    fn test_try_apply_invalid_spec(#[case] scope_spec: &str, #[case] action_spec: &str) {
+        assert!(matches!(
+            try_apply("x", scope_spec, action_spec),
+            Err(ApplyError::Spec(_))
+        ));
+    }
+
+    #[test]
+    fn test_try_apply_invalid_scopes() {
+        assert!(matches!(
+            try_apply("x", r#"[{"regex": "("}]"#, "[]"),
+            Err(ApplyError::Regex(_))
+        ));
+        assert!(matches!(
+            try_apply("x", r#"[{"python": "nope"}]"#, "[]"),
+            Err(ApplyError::PreparedQuery(_))
+        ));
+        assert!(matches!(
+            try_apply("x", r#"[{"go-query": "(nope) @n"}]"#, "[]"),
+            Err(ApplyError::Query(_))
+        ));
+        assert!(matches!(
+            try_apply("x", r#"[{"columns": "0"}]"#, "[]"),
+            Err(ApplyError::Columns(_))
+        ));
+        assert!(matches!(
+            try_apply("x", r#"[{"indentation": "x"}]"#, "[]"),
+            Err(ApplyError::Indentation(_))
+        ));
+    }
+}
@@ -0,0 +1,216 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use log::trace;
+
+use crate::actions::{Action, ActionError, Actions};
+use crate::newlines::split_line_ending;
+use crate::scoping::view::ScopedViewBuilder;
+use crate::scoping::Scoper;
+
+/// An error while streaming, see [`apply_stream`].
+#[derive(Debug)]
+pub enum StreamError {
+    /// Reading or writing failed. Input which is not valid UTF-8 shows up as
+    /// [`io::ErrorKind::InvalidData`].
+    Io(io::Error),
+    /// An action failed.
+    Action(ActionError),
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error while streaming: {e}"),
+            Self::Action(e) => write!(f, "Error in an action while streaming: {e}"),
+        }
+    }
+}
+
+impl Error for StreamError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Action(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for StreamError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<ActionError> for StreamError {
+    fn from(err: ActionError) -> Self {
+        Self::Action(err)
+    }
+}
+
+/// Scopes `reader` using `scoper`, applies all `actions` in order and writes the
+/// result to `writer`, one line at a time.
+///
+/// Memory use is bounded by the longest line, not the entire input, so this suits
+/// huge documents and long-running pipelines. The flip side: scopes can never span
+/// multiple lines, and line endings themselves are never in scope. Scopers which need
+/// to see more than a single line at once (like language grammar-aware ones) are not
+/// fit for streaming.
+///
+/// `writer` is flushed once all input is through; wrap it in a [`io::LineWriter`] to
+/// have each line written out as soon as it is done.
+///
+/// Returns whether anything at all was in scope.
+///
+/// ## Example
+///
+/// ```rust
+/// use srgn::actions::{Action, Upper};
+/// use srgn::scoping::regex::Regex;
+/// use srgn::stream::apply_stream;
+///
+/// let input = "hello\nworld\n";
+/// let mut output = Vec::new();
+///
+/// let scoper = Regex::try_from(String::from("^w")).unwrap();
+/// let actions: [Box<dyn Action>; 1] = [Box::new(Upper::default())];
+///
+/// let any_in_scope = apply_stream(input.as_bytes(), &mut output, &scoper, &actions).unwrap();
+///
+/// assert!(any_in_scope);
+/// assert_eq!(String::from_utf8(output).unwrap(), "hello\nWorld\n");
+/// ```
+///
+/// # Errors
+///
+/// Errors if reading, writing or any of the `actions` fails. Output written up to that
+/// point is not rolled back.
+pub fn apply_stream(
+    mut reader: impl BufRead,
+    mut writer: impl Write,
+    scoper: &impl Scoper,
+    actions: &[Box<dyn Action>],
+) -> Result<bool, StreamError> {
+    let mut line = String::new();
+    let mut any_in_scope = false;
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        trace!("Streaming line: {:?}", line);
+
+        let (content, ending) = split_line_ending(&line);
+
+        let mut builder = ScopedViewBuilder::new(content);
+        builder.explode(scoper);
+        let mut view = builder.build();
+        any_in_scope |= view.has_any_in_scope();
+
+        view.map_with_context(&Actions::new(actions))?;
+
+        write!(writer, "{view}{ending}")?;
+    }
+
+    writer.flush()?;
+
+    Ok(any_in_scope)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::actions::{Deletion, Upper};
+    use crate::scoping::regex::Regex;
+
+    fn stream(input: &[u8], pattern: &str, actions: &[Box<dyn Action>]) -> (String, bool) {
+        let scoper = Regex::try_from(pattern.to_owned()).unwrap();
+        let mut output = Vec::new();
+
+        let any_in_scope = apply_stream(input, &mut output, &scoper, actions).unwrap();
+
+        (String::from_utf8(output).unwrap(), any_in_scope)
+    }
+
+    #[rstest]
+    #[case("", "a", "", false)]
+    #[case("b", "a", "b", false)]
+    #[case("a", "a", "A", true)]
+    #[case("a\nb\na", "a", "A\nb\nA", true)]
+    #[case("a\r\nb\r\n", "a", "A\r\nb\r\n", true)]
+    #[case("a\r\r\n", "\r", "a\r\r\n", true)] // Only a single line ending
+    #[case("ab\n", "b$", "aB\n", true)] // Anchors apply per line
+    #[case("ab\nab\n", "^a", "Ab\nAb\n", true)]
+    #[case("a\nb\n", r"\s", "a\nb\n", false)] // Line endings never in scope
+    #[case("a\nb\n", r"a\nb", "a\nb\n", false)] // Scopes do not span lines
+    fn test_apply_stream(
+        #[case] input: &str,
+        #[case] pattern: &str,
+        #[case] expected: &str,
+        #[case] expected_any_in_scope: bool,
+    ) {
+        let actions: [Box<dyn Action>; 1] = [Box::new(Upper::default())];
+
+        assert_eq!(
+            stream(input.as_bytes(), pattern, &actions),
+            (expected.to_owned(), expected_any_in_scope)
+        );
+    }
+
+    #[test]
+    fn test_apply_stream_applies_actions_in_order() {
+        let actions: [Box<dyn Action>; 2] = [
+            Box::new(|s: &str| format!("{s}x")),
+            Box::new(Upper::default()),
+        ];
+
+        assert_eq!(stream(b"a b\n", "a", &actions), ("AX b\n".to_owned(), true));
+    }
+
+    #[test]
+    fn test_apply_stream_without_actions() {
+        assert_eq!(stream(b"a\n", "a", &[]), ("a\n".to_owned(), true));
+    }
+
+    #[test]
+    fn test_apply_stream_invalid_utf8() {
+        let scoper = Regex::try_from("a".to_owned()).unwrap();
+        let actions: [Box<dyn Action>; 1] = [Box::new(Deletion::default())];
+        let mut output = Vec::new();
+
+        let res = apply_stream(&b"a\n\xff\n"[..], &mut output, &scoper, &actions);
+
+        assert!(matches!(res, Err(StreamError::Io(e)) if e.kind() == io::ErrorKind::InvalidData));
+        // Lines before the failure are through already.
+        assert_eq!(output, b"\n");
+    }
+
+    #[test]
+    fn test_apply_stream_failing_action() {
+        struct Failing;
+
+        impl Action for Failing {
+            fn act(&self, input: &str) -> String {
+                self.try_act(input).unwrap()
+            }
+
+            fn try_act(&self, _input: &str) -> Result<String, ActionError> {
+                Err(ActionError::Failed("nope".to_owned()))
+            }
+        }
+
+        let scoper = Regex::try_from("a".to_owned()).unwrap();
+        let actions: [Box<dyn Action>; 1] = [Box::new(Failing)];
+
+        let res = apply_stream(&b"b\na\n"[..], Vec::new(), &scoper, &actions);
+
+        assert!(matches!(
+            res,
+            Err(StreamError::Action(ActionError::Failed(reason))) if reason == "nope"
+        ));
+    }
+}
@@ -0,0 +1,22 @@
+use wasm_bindgen::prelude::{wasm_bindgen, JsError};
+
+pub use crate::spec::{try_apply, ApplyError};
+
+/// Scopes `input` as per `scope_spec`, applies the actions of `action_spec` in order
+/// and returns the result. This is the entry point for JavaScript.
+///
+/// Both specs are JSON arrays. Scopes narrow each other down in order, like on the
+/// command line; no scopes at all means all of `input` is in scope. For example,
+/// uppercasing all words in Python comments:
+///
+/// ```javascript
+/// apply(source, '[{"python": "comments"}, {"regex": "\\\\w+"}]', '["upper"]');
+/// ```
+///
+/// # Errors
+///
+/// See [`ApplyError`], surfacing in JavaScript as a thrown `Error`.
+#[wasm_bindgen]
+pub fn apply(input: &str, scope_spec: &str, action_spec: &str) -> Result<String, JsError> {
+    Ok(try_apply(input, scope_spec, action_spec)?)
+}
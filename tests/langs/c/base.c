@@ -75,3 +75,12 @@ int main(void) {
 
     return 0;
 }
+
+#define MAX(a, b) ((a) > (b) ? (a) : (b))
+#ifndef DEBUG
+#pragma message("release build")
+#elif defined(VERBOSE)
+int verbose = 1;
+#else
+int verbose = 0;
+#endif
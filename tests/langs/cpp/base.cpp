@@ -0,0 +1,68 @@
+#include <vector>
+#include "base.hpp"
+
+#define SQUARE(x) ((x) * (x))
+#ifdef DEBUG
+#pragma message("debug build")
+#endif
+
+/* Multiline comment.
+ * New line.
+ */
+namespace shapes {
+
+using std::vector;
+using namespace std;
+typedef unsigned int uint;
+using Points = vector<int>;
+
+enum class Color { Red, Green };
+
+union U {
+    char test[4];
+    int a;
+};
+
+struct Point {
+    int x;
+    int y;
+};
+
+// A class.
+class Shape {
+public:
+    virtual ~Shape() = default;
+    virtual double area() const = 0;
+};
+
+class Square : public Shape {
+public:
+    explicit Square(double side) : side_(side) {}
+
+    double area() const override {
+        return side_ * side_;
+    }
+
+private:
+    double side_;
+};
+
+template <typename T>
+T twice(T value) {
+    return value + value;
+}
+
+double total(const vector<Shape *> &shapes);
+
+}  // namespace shapes
+
+int main() {
+    auto greeting = "Hello, World!";
+    auto raw = R"(raw "string")";
+    auto add = [](int a, int b) { return a + b; };
+
+    shapes::Square square(2.0);
+    printf("%s %f %d\n", greeting, square.area(), add(1, 2));
+
+    return shapes::twice(0);
+}
@@ -2,8 +2,10 @@ use std::ops::Range;
 
 use rstest::rstest;
 use serde::{Deserialize, Serialize};
-use srgn::scoping::langs::{c, csharp, go, hcl, python, rust, typescript, LanguageScoper};
-use srgn::scoping::scope::Scope;
+use srgn::scoping::langs::{
+    c, cpp, csharp, go, hcl, python, rust, typescript, LanguageScoper, QuerySource,
+};
+use srgn::scoping::scope::{Scope, ScopeContext};
 use srgn::scoping::view::ScopedViewBuilder;
 
 /// A type that when serialized, will visually highlight the portions of a line which
@@ -818,6 +820,106 @@ impl InScopeLinePart {
     include_str!("c/base.c"),
    c::CompiledQuery::from (c::PreparedQuery::CallExpression),
 )]
+#[case(
+    "base.c_includepaths",
+    include_str!("c/base.c"),
+    c::CompiledQuery::from(c::PreparedQuery::IncludePaths),
+)]
+#[case(
+    "base.c_preprocessor",
+    include_str!("c/base.c"),
+    c::CompiledQuery::from(c::PreparedQuery::Preprocessor),
+)]
+#[case(
+    "base.cpp_comments",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::Comments),
+)]
+#[case(
+    "base.cpp_strings",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::Strings),
+)]
+#[case(
+    "base.cpp_includes",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::Includes),
+)]
+#[case(
+    "base.cpp_includepaths",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::IncludePaths),
+)]
+#[case(
+    "base.cpp_preprocessor",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::Preprocessor),
+)]
+#[case(
+    "base.cpp_namespace",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::Namespace),
+)]
+#[case(
+    "base.cpp_class",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::Class),
+)]
+#[case(
+    "base.cpp_struct",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::Struct),
+)]
+#[case(
+    "base.cpp_enum",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::Enum),
+)]
+#[case(
+    "base.cpp_union",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::Union),
+)]
+#[case(
+    "base.cpp_template",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::Template),
+)]
+#[case(
+    "base.cpp_typealias",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::TypeAlias),
+)]
+#[case(
+    "base.cpp_using",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::Using),
+)]
+#[case(
+    "base.cpp_functiondef",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::FunctionDef),
+)]
+#[case(
+    "base.cpp_functiondecl",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::FunctionDecl),
+)]
+#[case(
+    "base.cpp_lambda",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::Lambda),
+)]
+#[case(
+    "base.cpp_callexpr",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::CallExpression),
+)]
+#[case(
+    "base.cpp_identifier",
+    include_str!("cpp/base.cpp"),
+    cpp::CompiledQuery::from(cpp::PreparedQuery::Identifier),
+)]
 fn test_language_scopers(
     #[case] snapshot_name: &str,
     #[case] contents: &str,
@@ -859,3 +961,252 @@ fn test_language_scopers(
 
     insta::assert_yaml_snapshot!(snapshot_name, inscope_parts);
 }
+
+#[rstest]
+#[case(&[], &["foo", "return 1"])]
+#[case(&["name"], &["foo"])]
+#[case(&["body"], &["return 1"])]
+#[case(&["name", "body"], &["foo", "return 1"])]
+fn test_query_capture_selection(#[case] captures: &[&str], #[case] expected: &[&str]) {
+    let query = QuerySource::from(String::from(
+        "(function_definition name: (identifier) @name body: (block) @body)",
+    ));
+    let query = if captures.is_empty() {
+        query
+    } else {
+        query.with_captures(captures.iter().map(ToString::to_string).collect())
+    };
+    let lang = python::CompiledQuery::try_from(query).unwrap();
+
+    let mut builder = ScopedViewBuilder::new("def foo():\n    return 1\n");
+    builder.explode(&lang);
+    let view = builder.build();
+
+    let in_scope: Vec<String> = view
+        .scopes()
+        .0
+        .iter()
+        .filter(|scope| matches!(scope.0, Scope::In(..)))
+        .map(|scope| <&str>::from(scope).to_owned())
+        .collect();
+
+    assert_eq!(in_scope, expected);
+}
+
+#[test]
+fn test_query_capture_selection_keeps_predicates() {
+    let query = QuerySource::from(String::from(
+        r#"(function_definition name: (identifier) @name (#eq? @name "bar") body: (block) @body)"#,
+    ))
+    .with_captures(vec!["body".into()]);
+    let lang = python::CompiledQuery::try_from(query).unwrap();
+
+    let mut builder =
+        ScopedViewBuilder::new("def foo():\n    return 1\n\ndef bar():\n    return 2\n");
+    builder.explode(&lang);
+    let view = builder.build();
+
+    let in_scope: Vec<String> = view
+        .scopes()
+        .0
+        .iter()
+        .filter(|scope| matches!(scope.0, Scope::In(..)))
+        .map(|scope| <&str>::from(scope).to_owned())
+        .collect();
+
+    // The predicate on the unselected capture still applies.
+    assert_eq!(in_scope, ["return 2"]);
+}
+
+#[test]
+fn test_query_capture_selection_unknown_capture() {
+    let query =
+        QuerySource::from(String::from("(identifier) @name")).with_captures(vec!["nope".into()]);
+
+    assert!(python::CompiledQuery::try_from(query).is_err());
+}
+
+#[test]
+fn test_query_capture_contexts() {
+    let query = QuerySource::from(String::from(
+        "(function_definition name: (identifier) @name parameters: (parameters) @params)",
+    ))
+    .with_capture_contexts();
+    let lang = python::CompiledQuery::try_from(query).unwrap();
+
+    let mut builder = ScopedViewBuilder::new(
+        "def foo(x): pass
+",
+    );
+    builder.explode(&lang);
+    let view = builder.build();
+
+    let in_scope: Vec<(String, Option<ScopeContext<'_>>)> = view
+        .scopes()
+        .0
+        .iter()
+        .filter_map(|scope| match &scope.0 {
+            Scope::In(s, ctx) => Some((s.to_string(), ctx.clone())),
+            Scope::Out(_) => None,
+        })
+        .collect();
+
+    assert_eq!(
+        in_scope,
+        [
+            (
+                "foo".to_owned(),
+                Some(ScopeContext::QueryCapture("name".to_owned()))
+            ),
+            (
+                "(x)".to_owned(),
+                Some(ScopeContext::QueryCapture("params".to_owned()))
+            ),
+        ]
+    );
+}
+
+/// A query, scoping to only the given `captures` (or all, if empty).
+fn query(query: &str, captures: &[&str]) -> QuerySource {
+    let query = QuerySource::from(query.to_owned());
+
+    if captures.is_empty() {
+        query
+    } else {
+        query.with_captures(captures.iter().map(ToString::to_string).collect())
+    }
+}
+
+/// Queries scope exactly what tree-sitter itself matches: predicates apply to
+/// unselected and ignored captures just the same, quantified captures yield all their
+/// nodes, and anchors hold.
+#[rstest]
+#[case::python_predicate_on_unselected(
+    python::CompiledQuery::try_from(query(r#"(function_definition name: (identifier) @n (#eq? @n "foo")) @f"#, &["f"])).unwrap(),
+    "def foo():\n    x\n    y\n\ndef bar():\n    z\n",
+    &["def foo():\n    x\n    y"],
+)]
+#[case::python_predicate_on_ignored(
+    python::CompiledQuery::try_from(query(r#"(function_definition) @f (function_definition name: (identifier) @n (#eq? @n "foo") body: (_) @_SRGN_IGNORE)"#, &[])).unwrap(),
+    "def foo():\n    x\n    y\n\ndef bar():\n    z\n",
+    &["def foo():\n    ", "def bar():\n    z"],
+)]
+#[case::python_any_of(
+    python::CompiledQuery::try_from(query(r#"(function_definition name: (identifier) @n (#any-of? @n "bar" "baz")) @f"#, &["f"])).unwrap(),
+    "def foo():\n    x\n    y\n\ndef bar():\n    z\n",
+    &["def bar():\n    z"],
+)]
+#[case::python_quantifier(
+    python::CompiledQuery::try_from(query("(module . (comment)+ @c)", &[])).unwrap(),
+    "# a\n# b\nx = 1\n# c\n",
+    &["# a", "# b"],
+)]
+#[case::python_anchor(
+    python::CompiledQuery::try_from(query("(block . (expression_statement) @first)", &[])).unwrap(),
+    "def foo():\n    x\n    y\n\ndef bar():\n    z\n",
+    &["x", "z"],
+)]
+#[case::rust_predicate_on_unselected(
+    rust::CompiledQuery::try_from(query(r#"(function_item name: (identifier) @n (#eq? @n "bar")) @f"#, &["f"])).unwrap(),
+    "fn foo() { x; y; }\nfn bar() { z; }\n",
+    &["fn bar() { z; }"],
+)]
+#[case::rust_predicate_on_ignored(
+    rust::CompiledQuery::try_from(query(r#"(function_item) @f (function_item name: (identifier) @n (#not-eq? @n "bar") body: (_) @_SRGN_IGNORE)"#, &[])).unwrap(),
+    "fn foo() { x; y; }\nfn bar() { z; }\n",
+    &["fn foo() ", "fn bar() { z; }"],
+)]
+#[case::rust_quantifier_and_anchor(
+    rust::CompiledQuery::try_from(query("(block . (expression_statement)+ @s (expression_statement) @last .)", &["s"])).unwrap(),
+    "fn foo() { x; y; z; }\n",
+    &["x;", "y;"],
+)]
+#[case::go_predicate_on_unselected(
+    go::CompiledQuery::try_from(query(r#"(function_declaration name: (identifier) @n (#match? @n "^b")) @f"#, &["f"])).unwrap(),
+    "package main\nfunc foo() {}\nfunc bar() {}\n",
+    &["func bar() {}"],
+)]
+#[case::go_quantifier(
+    go::CompiledQuery::try_from(query("(source_file (comment)+ @c . (function_declaration))", &[])).unwrap(),
+    "package main\n// a\n// b\nfunc foo() {}\n",
+    &["// a", "// b"],
+)]
+#[case::c_predicate_on_unselected(
+    c::CompiledQuery::try_from(query(r#"(function_definition declarator: (function_declarator declarator: (identifier) @n) (#eq? @n "foo")) @f"#, &["f"])).unwrap(),
+    "int foo(void) { return 1; }\nint bar(void) { return 2; }\n",
+    &["int foo(void) { return 1; }"],
+)]
+#[case::c_anchor(
+    c::CompiledQuery::try_from(query("(compound_statement . (_) @first)", &[])).unwrap(),
+    "void foo(void) { a(); b(); }\n",
+    &["a();"],
+)]
+#[case::csharp_predicate_on_unselected(
+    csharp::CompiledQuery::try_from(query(r#"(method_declaration name: (identifier) @n (#any-of? @n "Bar" "Baz")) @m"#, &["m"])).unwrap(),
+    "class A { void Foo() {} void Bar() {} }\n",
+    &["void Bar() {}"],
+)]
+#[case::hcl_predicate_on_unselected(
+    hcl::CompiledQuery::try_from(query(r#"(attribute (identifier) @k (#eq? @k "b")) @a"#, &["a"])).unwrap(),
+    "a = 1\nb = 2\n",
+    &["b = 2"],
+)]
+#[case::typescript_predicate_on_unselected(
+    typescript::CompiledQuery::try_from(query(r#"(function_declaration name: (identifier) @n (#not-match? @n "^f")) @f"#, &["f"])).unwrap(),
+    "function foo() {}\nfunction bar() {}\n",
+    &["function bar() {}"],
+)]
+fn test_query_semantics(
+    #[case] lang: impl LanguageScoper,
+    #[case] input: &str,
+    #[case] expected: &[&str],
+) {
+    let mut builder = ScopedViewBuilder::new(input);
+    builder.explode(&lang);
+    let view = builder.build();
+
+    let in_scope: Vec<String> = view
+        .scopes()
+        .0
+        .iter()
+        .filter(|scope| matches!(scope.0, Scope::In(..)))
+        .map(|scope| <&str>::from(scope).to_owned())
+        .collect();
+
+    assert_eq!(in_scope, expected);
+}
+
+#[rstest]
+#[case::python_fine(
+    Box::new(python::CompiledQuery::from(python::PreparedQuery::Comments)),
+    "x = f(1)\n",
+    false
+)]
+#[case::python_error(
+    Box::new(python::CompiledQuery::from(python::PreparedQuery::Comments)),
+    "def (:\n    x\n",
+    true
+)]
+#[case::rust_fine(
+    Box::new(rust::CompiledQuery::from(rust::PreparedQuery::Comments)),
+    "fn f() {}\n",
+    false
+)]
+#[case::rust_missing(
+    Box::new(rust::CompiledQuery::from(rust::PreparedQuery::Comments)),
+    "fn f() {\n",
+    true
+)]
+#[case::go_error(
+    Box::new(go::CompiledQuery::from(go::PreparedQuery::Comments)),
+    "package main\nfunc {\n",
+    true
+)]
+fn test_has_syntax_errors(
+    #[case] lang: Box<dyn LanguageScoper>,
+    #[case] input: &str,
+    #[case] expected: bool,
+) {
+    // Available on trait objects.
+    assert_eq!(lang.has_syntax_errors(input), expected);
+}
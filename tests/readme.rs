@@ -507,6 +507,7 @@ mod tests {
                                     tag("python-query-file"),
                                     tag("rust-query-file"),
                                     tag("hcl-query-file"),
+                                    tag("cpp-query-file"),
                                     tag("go-query-file"),
                                     tag("c-query-file"),
                                 )),
@@ -518,6 +519,7 @@ mod tests {
                                     tag("python-query"),
                                     tag("rust-query"),
                                     tag("hcl-query"),
+                                    tag("cpp-query"),
                                     tag("go-query"),
                                     tag("c-query"),
                                 )),
@@ -528,11 +530,17 @@ mod tests {
                                     tag("python"),
                                     tag("rust"),
                                     tag("hcl"),
+                                    tag("cpp"),
                                     tag("go"),
                                     tag("c"),
                                 )),
                                 // Misc. flags used in the docs
-                                alt((tag("glob"), tag("stdin-override-to"), tag("threads"))),
+                                alt((
+                                    tag("glob"),
+                                    tag("stdin-override-to"),
+                                    tag("threads"),
+                                    tag("preset"),
+                                )),
                                 // Shorthands
                                 alt((
                                     tag("tsx"),